@@ -0,0 +1,125 @@
+//! Builds an index from a wordlist and writes it in the archive layout, so it can be loaded with
+//! `MetaAutocompleter::load_from` or `MetaAutocompleter::load_mmap` instead of being built at startup
+//!
+//! Usage: strprox-build INPUT OUTPUT [--field NAME] [--weight NAME] [--top K] [--level LEVEL]
+//!
+//! INPUT is read as CSV with headers if it ends in .csv, as JSON lines if it ends in .jsonl or .ndjson,
+//! and as one string per line otherwise.
+//! `--field` names the column or member to index (default "string") and `--weight` the one with the weights of strings.
+//! `--top` precomputes the best K strings of every node, and `--level` compresses the output with zstd
+use std::{
+    error::Error,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process,
+};
+
+use strprox::prefix::meta::{load_csv, load_jsonl, load_lines, Record};
+use strprox::MetaAutocompleter;
+
+const USAGE: &str =
+    "usage: strprox-build INPUT OUTPUT [--field NAME] [--weight NAME] [--top K] [--level LEVEL]";
+
+/// Parsed command line arguments
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    field: String,
+    weight: Option<String>,
+    top: Option<usize>,
+    level: Option<i32>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut paths = Vec::new();
+        let mut parsed = Self {
+            input: PathBuf::new(),
+            output: PathBuf::new(),
+            field: "string".to_string(),
+            weight: None,
+            top: None,
+            level: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--field" => parsed.field = args.next()?,
+                "--weight" => parsed.weight = Some(args.next()?),
+                "--top" => parsed.top = Some(args.next()?.parse().ok()?),
+                "--level" => parsed.level = Some(args.next()?.parse().ok()?),
+                _ if arg.starts_with("--") => return None,
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+        let [input, output] = <[PathBuf; 2]>::try_from(paths).ok()?;
+        parsed.input = input;
+        parsed.output = output;
+        Some(parsed)
+    }
+}
+
+/// Returns the strings of the records in `input` with their weights
+fn load(args: &Args) -> Result<Vec<(String, u32)>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(&args.input)?);
+    let weight = args.weight.as_deref();
+    fn pairs<P>(records: Vec<Record<'static, P>>) -> Vec<(String, u32)> {
+        records
+            .into_iter()
+            .map(|record| (record.string.into_owned(), record.weight))
+            .collect()
+    }
+    let extension = args
+        .input
+        .extension()
+        .and_then(|extension| extension.to_str());
+    Ok(match extension {
+        Some("csv") => pairs(load_csv(reader, &args.field, weight)?),
+        Some("jsonl" | "ndjson") => pairs(load_jsonl(reader, &args.field, weight)?),
+        _ => pairs(load_lines(reader)?),
+    })
+}
+
+/// Writes the index to `output`, compressed at `level` if any
+fn save(
+    autocompleter: &MetaAutocompleter,
+    output: &Path,
+    level: Option<i32>,
+) -> Result<(), Box<dyn Error>> {
+    match level {
+        None => autocompleter.save_to(output)?,
+        #[cfg(feature = "zstd")]
+        Some(level) => autocompleter.save_compressed_to(output, level)?,
+        #[cfg(not(feature = "zstd"))]
+        Some(_) => return Err("--level requires the zstd feature".into()),
+    }
+    Ok(())
+}
+
+fn main() {
+    let Some(args) = Args::parse(std::env::args().skip(1)) else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+    let run = || -> Result<usize, Box<dyn Error>> {
+        let pairs = load(&args)?;
+        let mut autocompleter = MetaAutocompleter::new_weighted(
+            pairs.len(),
+            pairs
+                .iter()
+                .map(|(string, weight)| (string.as_str().into(), *weight)),
+        );
+        if let Some(k) = args.top {
+            autocompleter.precompute_top(k);
+        }
+        save(&autocompleter, &args.output, args.level)?;
+        Ok(autocompleter.len())
+    };
+    match run() {
+        Ok(len) => println!("wrote {} strings to {}", len, args.output.display()),
+        Err(error) => {
+            eprintln!("strprox-build: {}", error);
+            process::exit(1);
+        }
+    }
+}
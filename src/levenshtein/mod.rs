@@ -1,5 +1,8 @@
-use crate::{MeasuredPrefix, TreeString};
-use std::{cmp::min, collections::BinaryHeap};
+use crate::{MeasuredPrefix, MeasuredString, TreeString};
+use std::{
+    cmp::{max, min},
+    collections::{BinaryHeap, HashMap},
+};
 
 #[cfg(test)]
 use rand::{
@@ -9,14 +12,27 @@ use rand::{
     Rng,
 };
 
-/// Returns the last row of the Levenshtein edit distance matrix between two strings as char-slices,
+/// Returns the last row of the Levenshtein edit distance matrix between two strings as slices of characters (or clusters),
 /// where the row is for the edit distances between varying prefixes of `second` with a certain prefix of `first`
-pub(super) fn final_lev_row(first: &[char], second: &[char]) -> Vec<usize> {
+pub(super) fn final_lev_row<T: PartialEq>(first: &[T], second: &[T]) -> Vec<usize> {
+    let mut prev_row = Vec::new();
+    lev_row_into(first, second, &mut prev_row, &mut Vec::new());
+    prev_row
+}
+
+/// Same as `final_lev_row`, but writes the row to `prev_row`, reusing its allocation and that of `current_row`
+fn lev_row_into<T: PartialEq>(
+    first: &[T],
+    second: &[T],
+    prev_row: &mut Vec<usize>,
+    current_row: &mut Vec<usize>,
+) {
     // using a two-row memoization https://en.wikipedia.org/wiki/Levenshtein_distance#Iterative_with_two_matrix_rows
     let row_len = second.len() + 1;
-    let mut prev_row = Vec::with_capacity(row_len);
+    prev_row.clear();
     prev_row.resize(row_len, 0);
-    let mut current_row = prev_row.clone();
+    current_row.clear();
+    current_row.resize(row_len, 0);
 
     for j in 0..=second.len() {
         prev_row[j] = j;
@@ -39,9 +55,48 @@ pub(super) fn final_lev_row(first: &[char], second: &[char]) -> Vec<usize> {
 
             current_row[column] = dist;
         }
-        std::mem::swap(&mut prev_row, &mut current_row);
+        std::mem::swap(prev_row, current_row);
     }
-    prev_row
+}
+
+/// Buffers for computing many edit distances without allocating for each of them
+#[derive(Clone, Debug, Default)]
+pub struct Scratch {
+    first: Vec<char>,
+    second: Vec<char>,
+    prev_row: Vec<usize>,
+    current_row: Vec<usize>,
+}
+
+impl Scratch {
+    /// Returns empty buffers, which grow to the longest strings they're used for
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Returns the same row as `final_lev_row` between two strings, computed in the buffers
+    fn final_lev_row(&mut self, first: &str, second: &str) -> &[usize] {
+        self.first.clear();
+        self.first.extend(first.chars());
+        self.second.clear();
+        self.second.extend(second.chars());
+        lev_row_into(
+            &self.first,
+            &self.second,
+            &mut self.prev_row,
+            &mut self.current_row,
+        );
+        &self.prev_row
+    }
+}
+
+/// Same as `edit_distance`, but reuses the buffers of `scratch` instead of allocating
+pub fn distance_with(scratch: &mut Scratch, first: &str, second: &str) -> usize {
+    *scratch.final_lev_row(first, second).last().unwrap()
+}
+
+/// Same as `prefix_edit_distance`, but reuses the buffers of `scratch` instead of allocating
+pub fn prefix_distance_with(scratch: &mut Scratch, first: &str, second: &str) -> usize {
+    *scratch.final_lev_row(first, second).iter().min().unwrap()
 }
 
 /// Returns `string` as a Vec of its characters
@@ -54,28 +109,930 @@ pub(crate) fn to_char_vec(string: &str) -> Vec<char> {
 pub fn prefix_edit_distance(first: &str, second: &str) -> usize {
     let first: Vec<char> = to_char_vec(first);
     let second: Vec<char> = to_char_vec(second);
-    match final_lev_row(&first[..], &second[..]).into_iter().min() {
-        Some(distance) => distance,
-        None => {
-            // If it's None, it means that at least one of the strings are empty, so the edit distance
-            // is the number of characters to insert into an empty string from `first`
-            debug_assert!(first.is_empty() || second.is_empty());
-            first.len()
+    slice_prefix_edit_distance(&first, &second)
+}
+
+/// Same as `prefix_edit_distance`, but also returns the length in bytes of the shortest prefix of `second`
+/// with that distance, so `second` can be split into the part that matched `first` and the suggested rest
+pub fn prefix_edit_distance_with_end(first: &str, second: &str) -> (usize, usize) {
+    let first: Vec<char> = to_char_vec(first);
+    let chars: Vec<char> = to_char_vec(second);
+    let row = final_lev_row(&first, &chars);
+    // the first column with the smallest distance
+    let column = (0..row.len()).min_by_key(|&column| row[column]).unwrap();
+    let end = second
+        .char_indices()
+        .nth(column)
+        .map_or(second.len(), |(end, _)| end);
+    (row[column], end)
+}
+
+/// Returns the edit distance between two strings
+pub fn edit_distance(first: &str, second: &str) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    slice_edit_distance(&first, &second)
+}
+
+/// Returns a similarity between 0.0 and 1.0 from the edit distance between two strings,
+/// which is 1.0 minus the distance divided by the number of characters of the longer string (1.0 if both are empty)
+pub fn similarity(first: &str, second: &str) -> f64 {
+    let len = first.chars().count().max(second.chars().count());
+    if len == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(first, second) as f64 / len as f64
+}
+
+/// Returns a similarity between 0.0 and 1.0 from the prefix edit distance between two strings,
+/// which is 1.0 minus the distance divided by the number of characters of `first` (1.0 if it's empty)
+pub fn prefix_similarity(first: &str, second: &str) -> f64 {
+    let len = first.chars().count();
+    if len == 0 {
+        return 1.0;
+    }
+    // the prefix edit distance is at most the length of `first`
+    1.0 - prefix_edit_distance(first, second) as f64 / len as f64
+}
+
+/// Returns the number of characters of the longest common subsequence of two strings,
+/// which is the most characters that can be kept when only inserting and deleting
+pub fn lcs_length(first: &str, second: &str) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    slice_lcs_length(&first, &second)
+}
+
+/// Same as `lcs_length`, but between sequences of any elements, such as words, syllables or bytes
+pub fn slice_lcs_length<T: Eq>(first: &[T], second: &[T]) -> usize {
+    let mut prev_row = vec![0; second.len() + 1];
+    let mut current_row = prev_row.clone();
+    for first_element in first {
+        for column in 1..=second.len() {
+            current_row[column] = if *first_element == second[column - 1] {
+                prev_row[column - 1] + 1
+            } else {
+                max(prev_row[column], current_row[column - 1])
+            };
         }
+        std::mem::swap(&mut prev_row, &mut current_row);
     }
+    prev_row[second.len()]
 }
 
-/// Returns the edit distance between two char slices
-pub fn edit_distance(first: &str, second: &str) -> usize {
+/// Returns a similarity between 0.0 and 1.0 from the longest common subsequence of two strings,
+/// which is twice its length divided by the total number of characters (1.0 if both are empty)
+///
+/// A substitution loses a character from both strings, so it lowers the similarity more than an insertion or deletion
+pub fn lcs_similarity(first: &str, second: &str) -> f64 {
+    let len = first.chars().count() + second.chars().count();
+    if len == 0 {
+        return 1.0;
+    }
+    2.0 * lcs_length(first, second) as f64 / len as f64
+}
+
+/// Returns the Jaro similarity between two strings, between 0.0 and 1.0, from the characters they have in common
+/// within half the length of the longer string of each other and the number of those that are transposed
+pub fn jaro(first: &str, second: &str) -> f64 {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    if first.is_empty() && second.is_empty() {
+        return 1.0;
+    }
+    let window = (max(first.len(), second.len()) / 2).saturating_sub(1);
+    let mut second_matched = vec![false; second.len()];
+    let mut first_matches = Vec::new();
+    for (i, &character) in first.iter().enumerate() {
+        let end = min(i + window + 1, second.len());
+        for j in i.saturating_sub(window)..end {
+            if !second_matched[j] && second[j] == character {
+                second_matched[j] = true;
+                first_matches.push(character);
+                break;
+            }
+        }
+    }
+    if first_matches.is_empty() {
+        return 0.0;
+    }
+    let second_matches = second
+        .iter()
+        .zip(&second_matched)
+        .filter_map(|(character, &matched)| matched.then_some(character));
+    // matches in a different order count as half a transposition each
+    let transpositions = first_matches
+        .iter()
+        .zip(second_matches)
+        .filter(|(a, b)| a != b)
+        .count() as f64
+        / 2.0;
+    let matches = first_matches.len() as f64;
+    (matches / first.len() as f64
+        + matches / second.len() as f64
+        + (matches - transpositions) / matches)
+        / 3.0
+}
+
+/// Returns the Jaro-Winkler similarity between two strings, between 0.0 and 1.0,
+/// which is the Jaro similarity raised for strings with a common prefix of up to 4 characters
+pub fn jaro_winkler(first: &str, second: &str) -> f64 {
+    let jaro = jaro(first, second);
+    let prefix = first
+        .chars()
+        .zip(second.chars())
+        .take_while(|(a, b)| a == b)
+        .take(4)
+        .count();
+    jaro + prefix as f64 * 0.1 * (1.0 - jaro)
+}
+
+/// Same as `final_lev_row`, but swapping two adjacent characters is also a single edit
+/// (the optimal string alignment distance, where no substring is edited more than once)
+fn damerau_lev_row(first: &[char], second: &[char]) -> Vec<usize> {
+    let mut before_prev_row = vec![0; second.len() + 1];
+    let mut prev_row: Vec<usize> = (0..=second.len()).collect();
+    let mut current_row = vec![0; second.len() + 1];
+    for row in 1..=first.len() {
+        current_row[0] = row;
+        for column in 1..=second.len() {
+            let diff = (first[row - 1] != second[column - 1]) as usize;
+
+            let replace_dist = prev_row[column - 1] + diff;
+            let insert_dist = prev_row[column] + 1;
+            let erase_dist = current_row[column - 1] + 1;
+
+            let mut dist = min(replace_dist, min(insert_dist, erase_dist));
+            if row > 1
+                && column > 1
+                && first[row - 1] == second[column - 2]
+                && first[row - 2] == second[column - 1]
+            {
+                dist = min(dist, before_prev_row[column - 2] + 1);
+            }
+            current_row[column] = dist;
+        }
+        std::mem::swap(&mut before_prev_row, &mut prev_row);
+        std::mem::swap(&mut prev_row, &mut current_row);
+    }
+    prev_row
+}
+
+/// Same as `edit_distance`, but swapping two adjacent characters is also a single edit
+pub fn damerau_edit_distance(first: &str, second: &str) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    *damerau_lev_row(&first, &second).last().unwrap()
+}
+
+/// Same as `damerau_edit_distance`, but characters between swapped ones can also be edited,
+/// such as "ca" to "abc" in two edits (the unrestricted Damerau-Levenshtein distance, which satisfies the triangle inequality)
+pub fn unrestricted_damerau_edit_distance(first: &str, second: &str) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    // the algorithm of Lowrance and Wagner, where the matrix has an extra row and column that bound transpositions
+    let width = second.len() + 2;
+    let bound = first.len() + second.len();
+    let mut matrix = vec![0; (first.len() + 2) * width];
+    matrix[0] = bound;
+    for row in 0..=first.len() {
+        matrix[(row + 1) * width] = bound;
+        matrix[(row + 1) * width + 1] = row;
+    }
+    for column in 0..=second.len() {
+        matrix[column + 1] = bound;
+        matrix[width + column + 1] = column;
+    }
+    // last row where each character of `first` was seen
+    let mut last_rows = HashMap::<char, usize>::new();
+    for row in 1..=first.len() {
+        // last column of this row where the characters matched
+        let mut last_column = 0;
+        for column in 1..=second.len() {
+            let swapped_row = last_rows.get(&second[column - 1]).copied().unwrap_or(0);
+            let swapped_column = last_column;
+            let diff = if first[row - 1] == second[column - 1] {
+                last_column = column;
+                0
+            } else {
+                1
+            };
+            let replace_dist = matrix[row * width + column] + diff;
+            let insert_dist = matrix[(row + 1) * width + column] + 1;
+            let erase_dist = matrix[row * width + column + 1] + 1;
+            // the characters between the swapped ones are erased or inserted
+            let swap_dist = matrix[swapped_row * width + swapped_column]
+                + (row - swapped_row - 1)
+                + 1
+                + (column - swapped_column - 1);
+            matrix[(row + 1) * width + column + 1] =
+                min(min(replace_dist, insert_dist), min(erase_dist, swap_dist));
+        }
+        last_rows.insert(first[row - 1], row);
+    }
+    matrix[(first.len() + 1) * width + second.len() + 1]
+}
+
+/// Same as `prefix_edit_distance`, but swapping two adjacent characters is also a single edit
+pub fn damerau_prefix_edit_distance(first: &str, second: &str) -> usize {
     let first: Vec<char> = to_char_vec(first);
     let second: Vec<char> = to_char_vec(second);
-    match final_lev_row(&first[..], &second[..]).last() {
-        Some(&distance) => distance,
-        None => {
-            debug_assert!(second.is_empty());
-            first.len()
+    damerau_lev_row(&first, &second).into_iter().min().unwrap()
+}
+
+/// Distance from a query to a stored string that ranks results, where smaller is better
+///
+/// Implemented for closures that take the query and the stored string
+pub trait Metric: Send + Sync {
+    /// Returns the distance from `query` to `string`
+    fn distance(&self, query: &str, string: &str) -> f64;
+}
+
+impl<F: Fn(&str, &str) -> f64 + Send + Sync> Metric for F {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        self(query, string)
+    }
+}
+
+/// Metric of the prefix edit distance, which is how results are ranked by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PrefixEditDistance;
+
+impl Metric for PrefixEditDistance {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        prefix_edit_distance(query, string) as f64
+    }
+}
+
+/// Metric of the prefix edit distance where swapping two adjacent characters is a single edit
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DamerauPrefixEditDistance;
+
+impl Metric for DamerauPrefixEditDistance {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        damerau_prefix_edit_distance(query, string) as f64
+    }
+}
+
+/// Metric of the weighted prefix edit distance with the costs of its edits
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WeightedPrefixEditDistance<C>(pub C);
+
+impl<C: EditCosts> Metric for WeightedPrefixEditDistance<C> {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        weighted_prefix_edit_distance(query, string, &self.0) as f64
+    }
+}
+
+/// Metric of the edit distance between the query and the whole string
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EditDistance;
+
+impl Metric for EditDistance {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        edit_distance(query, string) as f64
+    }
+}
+
+/// Metric of the edit distance between the query and the whole string where swapping two adjacent characters is a single edit
+///
+/// This is the optimal string alignment distance, which doesn't always satisfy the triangle inequality,
+/// so `UnrestrictedDamerauEditDistance` should be used for `BKTree`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DamerauEditDistance;
+
+impl Metric for DamerauEditDistance {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        damerau_edit_distance(query, string) as f64
+    }
+}
+
+/// Metric of the unrestricted Damerau-Levenshtein distance between the query and the whole string,
+/// which satisfies the triangle inequality unlike `DamerauEditDistance`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnrestrictedDamerauEditDistance;
+
+impl Metric for UnrestrictedDamerauEditDistance {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        unrestricted_damerau_edit_distance(query, string) as f64
+    }
+}
+
+/// Metric of the weighted edit distance between the query and the whole string with the costs of its edits
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WeightedEditDistance<C>(pub C);
+
+impl<C: EditCosts> Metric for WeightedEditDistance<C> {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        weighted_edit_distance(query, string, &self.0) as f64
+    }
+}
+
+/// Metric of 1.0 minus the Jaro-Winkler similarity between the query and the whole string
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JaroWinklerDistance;
+
+impl Metric for JaroWinklerDistance {
+    fn distance(&self, query: &str, string: &str) -> f64 {
+        1.0 - jaro_winkler(query, string)
+    }
+}
+
+/// Same as `prefix_edit_distance`, but between sequences of any elements, such as words, syllables or bytes
+pub fn slice_prefix_edit_distance<T: Eq>(first: &[T], second: &[T]) -> usize {
+    // the row always has the distance from the empty prefix of `second`
+    final_lev_row(first, second).into_iter().min().unwrap()
+}
+
+/// Same as `edit_distance`, but between sequences of any elements, such as words, syllables or bytes
+pub fn slice_edit_distance<T: Eq>(first: &[T], second: &[T]) -> usize {
+    *final_lev_row(first, second).last().unwrap()
+}
+
+/// Same as `final_lev_row`, but only computes the distances within `k` of the diagonal,
+/// which are enough to know the distances that are at most `k` (the others are replaced with `k + 1`)
+///
+/// Returns None as soon as every distance in a row exceeds `k`, since the distances of later rows can't be smaller
+fn banded_lev_row<T: Eq>(first: &[T], second: &[T], k: usize) -> Option<Vec<usize>> {
+    // no distance exceeds the longer length, so larger bands only risk overflowing
+    let k = min(k, max(first.len(), second.len()));
+    let limit = k + 1;
+    let mut prev_row: Vec<usize> = (0..=second.len())
+        .map(|column| min(column, limit))
+        .collect();
+    let mut current_row = vec![limit; second.len() + 1];
+
+    for row in 1..=first.len() {
+        let start = max(row.saturating_sub(k), 1);
+        let end = min(second.len(), row + k);
+        current_row[0] = min(row, limit);
+        // the cells just outside the band are read by the cells at its edges
+        if start > 1 {
+            current_row[start - 1] = limit;
+        }
+        if end < second.len() {
+            current_row[end + 1] = limit;
+        }
+        let mut row_min = current_row[0];
+        for column in start..=end {
+            let diff = (first[row - 1] != second[column - 1]) as usize;
+
+            let replace_dist = prev_row[column - 1] + diff;
+            let insert_dist = prev_row[column] + 1;
+            let erase_dist = current_row[column - 1] + 1;
+
+            current_row[column] = min(min(replace_dist, min(insert_dist, erase_dist)), limit);
+            row_min = min(row_min, current_row[column]);
+        }
+        if row_min > k {
+            return None;
         }
+        std::mem::swap(&mut prev_row, &mut current_row);
+    }
+    Some(prev_row)
+}
+
+/// Returns the edit distance between two strings if it's at most `k`, which is faster than `edit_distance` for small `k`
+///
+/// Any `k` can be given, including `usize::MAX` for no threshold, since the band is never wider than the longer string
+pub fn distance_within(first: &str, second: &str, k: usize) -> Option<usize> {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    // each edit changes the length by at most one
+    if first.len().abs_diff(second.len()) > k {
+        return None;
+    }
+    // strings of the same length that differ by at most one substitution can't be fewer edits apart,
+    // which is common for codes and IDs
+    if let Some(substitutions) = slice_hamming(&first, &second)
+        && substitutions <= 1
+    {
+        return (substitutions <= k).then_some(substitutions);
     }
+    let distance = *banded_lev_row(&first, &second, k)?.last().unwrap();
+    (distance <= k).then_some(distance)
+}
+
+/// Returns the number of positions where two strings with the same number of characters differ,
+/// or None if their numbers of characters differ
+pub fn hamming(first: &str, second: &str) -> Option<usize> {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    slice_hamming(&first, &second)
+}
+
+/// Same as `hamming`, but between sequences of any elements, such as words, syllables or bytes
+pub fn slice_hamming<T: Eq>(first: &[T], second: &[T]) -> Option<usize> {
+    (first.len() == second.len()).then(|| first.iter().zip(second).filter(|(a, b)| a != b).count())
+}
+
+/// Returns the prefix edit distance between two strings if it's at most `k`, which is faster than `prefix_edit_distance` for small `k`
+pub fn prefix_distance_within(first: &str, second: &str, k: usize) -> Option<usize> {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    let distance = banded_lev_row(&first, &second, k)?
+        .into_iter()
+        .min()
+        .unwrap();
+    (distance <= k).then_some(distance)
+}
+
+/// Same as `prefix_edit_distance`, but counts extended grapheme clusters instead of characters
+#[cfg(feature = "unicode-segmentation")]
+pub fn grapheme_prefix_edit_distance(first: &str, second: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+    let first: Vec<&str> = first.graphemes(true).collect();
+    let second: Vec<&str> = second.graphemes(true).collect();
+    slice_prefix_edit_distance(&first, &second)
+}
+
+/// Same as `edit_distance`, but counts extended grapheme clusters instead of characters
+#[cfg(feature = "unicode-segmentation")]
+pub fn grapheme_edit_distance(first: &str, second: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+    let first: Vec<&str> = first.graphemes(true).collect();
+    let second: Vec<&str> = second.graphemes(true).collect();
+    slice_edit_distance(&first, &second)
+}
+
+/// Costs of edit operations for weighted edit distances, in units chosen by the implementation
+///
+/// Edits transform the first string (such as a query) into the second (such as a stored string)
+pub trait EditCosts: Send + Sync {
+    /// Returns the cost of inserting `character` of the second string
+    fn insert(&self, character: char) -> usize;
+    /// Returns the cost of deleting `character` of the first string
+    fn delete(&self, character: char) -> usize;
+    /// Returns the cost of substituting `from` of the first string with a different character `to` of the second string
+    fn substitute(&self, from: char, to: char) -> usize;
+    /// Returns the cost of substituting the sequence `from` of the first string with the sequence `to` of the second string
+    /// as a whole, where one of them has more than one character, or None if they can't be substituted
+    fn substitute_sequence(&self, _from: &[char], _to: &[char]) -> Option<usize> {
+        None
+    }
+    /// Returns the number of characters of the longest sequence that `substitute_sequence` can substitute
+    fn max_sequence_len(&self) -> usize {
+        1
+    }
+    /// Returns a lower bound of the cost per edit of any edits, where substituting sequences counts as
+    /// as many edits as the longer sequence has characters (or 0 if there isn't a positive bound)
+    fn min_cost(&self) -> usize;
+}
+
+/// Edit costs that depend on the kind of edit, where substituting a letter with the same letter in another case
+/// can cost less than other substitutions
+///
+/// The default costs are all 1, which gives the Levenshtein distance
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperationCosts {
+    pub insert: usize,
+    pub delete: usize,
+    pub substitute: usize,
+    /// Cost of substituting a character with the same character in another case
+    pub case_substitute: usize,
+}
+
+impl Default for OperationCosts {
+    fn default() -> Self {
+        Self {
+            insert: 1,
+            delete: 1,
+            substitute: 1,
+            case_substitute: 1,
+        }
+    }
+}
+
+impl EditCosts for OperationCosts {
+    fn insert(&self, _character: char) -> usize {
+        self.insert
+    }
+    fn delete(&self, _character: char) -> usize {
+        self.delete
+    }
+    fn substitute(&self, from: char, to: char) -> usize {
+        if from.to_lowercase().eq(to.to_lowercase()) {
+            self.case_substitute
+        } else {
+            self.substitute
+        }
+    }
+    fn min_cost(&self) -> usize {
+        [
+            self.insert,
+            self.delete,
+            self.substitute,
+            self.case_substitute,
+        ]
+        .into_iter()
+        .min()
+        .unwrap()
+    }
+}
+
+/// Physical keyboard layout, where each key is identified by the character it types without modifiers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyboardLayout {
+    Qwerty,
+    Qwertz,
+    Azerty,
+    Dvorak,
+}
+
+impl KeyboardLayout {
+    /// Returns the rows of keys from the top, where each row is offset to the right of the one above it
+    fn rows(&self) -> [&'static str; 4] {
+        match self {
+            Self::Qwerty => ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"],
+            Self::Qwertz => ["1234567890ß", "qwertzuiopü+", "asdfghjklöä#", "yxcvbnm,.-"],
+            Self::Azerty => ["1234567890)=", "azertyuiop^$", "qsdfghjklmù*", "wxcvbn,;:!"],
+            Self::Dvorak => ["1234567890[]", "',.pyfgcrl/=", "aoeuidhtns-", ";qjkxbmwvz"],
+        }
+    }
+    /// Returns the row and column of the key that types `character` regardless of case, if any
+    fn position(&self, character: char) -> Option<(usize, usize)> {
+        let character = character.to_lowercase().next()?;
+        self.rows().iter().enumerate().find_map(|(row, keys)| {
+            keys.chars()
+                .position(|key| key == character)
+                .map(|column| (row, column))
+        })
+    }
+    /// Returns whether the keys that type `first` and `second` are next to each other
+    pub fn adjacent(&self, first: char, second: char) -> bool {
+        let (Some((row, column)), Some((other_row, other_column))) =
+            (self.position(first), self.position(second))
+        else {
+            return false;
+        };
+        if row == other_row {
+            column.abs_diff(other_column) == 1
+        } else if row + 1 == other_row {
+            // the row below is offset to the right, so its keys are below-left and below-right
+            column == other_column || column == other_column + 1
+        } else if other_row + 1 == row {
+            other_column == column || other_column == column + 1
+        } else {
+            false
+        }
+    }
+}
+
+/// Edit costs for typing mistakes, where substituting the character of an adjacent key costs less than other edits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyboardCosts {
+    pub layout: KeyboardLayout,
+    /// Cost of substituting the character of an adjacent key
+    pub adjacent: usize,
+    /// Cost of any other edit
+    pub edit: usize,
+}
+
+impl KeyboardCosts {
+    /// Returns costs for `layout` where substituting an adjacent key costs half of any other edit
+    pub fn new(layout: KeyboardLayout) -> Self {
+        Self {
+            layout,
+            adjacent: 1,
+            edit: 2,
+        }
+    }
+}
+
+impl EditCosts for KeyboardCosts {
+    fn insert(&self, _character: char) -> usize {
+        self.edit
+    }
+    fn delete(&self, _character: char) -> usize {
+        self.edit
+    }
+    fn substitute(&self, from: char, to: char) -> usize {
+        if self.layout.adjacent(from, to) {
+            self.adjacent
+        } else {
+            self.edit
+        }
+    }
+    fn min_cost(&self) -> usize {
+        min(self.adjacent, self.edit)
+    }
+}
+
+/// Edit costs for recognized text, such as from OCR, where substituting characters or sequences
+/// that are often confused with each other (like "0" and "O", or "rn" and "m") costs less than other edits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfusionCosts {
+    /// Pairs of confused sequences |-> cost of substituting the first with the second
+    confusions: HashMap<(Vec<char>, Vec<char>), usize>,
+    /// Number of characters of the longest confused sequence
+    max_len: usize,
+    /// Cost of any other edit
+    edit: usize,
+}
+
+impl ConfusionCosts {
+    /// Returns costs without any confusions, where every edit costs `edit`
+    pub fn new(edit: usize) -> Self {
+        Self {
+            confusions: Default::default(),
+            max_len: 1,
+            edit,
+        }
+    }
+    /// Returns costs for confusions that are common in OCR, which cost half of any other edit
+    /// (or a quarter per character for sequences like "rn" and "m")
+    pub fn ocr() -> Self {
+        [
+            ("0", "O"),
+            ("0", "o"),
+            ("O", "o"),
+            ("1", "l"),
+            ("1", "I"),
+            ("l", "I"),
+            ("2", "Z"),
+            ("5", "S"),
+            ("8", "B"),
+            ("rn", "m"),
+            ("cl", "d"),
+            ("vv", "w"),
+        ]
+        .into_iter()
+        .fold(Self::new(4), |costs, (first, second)| {
+            costs.confuse(first, second, 2)
+        })
+    }
+    /// Adds a confusion between the sequences `first` and `second` in both directions, substituting them for `cost`
+    pub fn confuse(mut self, first: &str, second: &str, cost: usize) -> Self {
+        let first: Vec<char> = to_char_vec(first);
+        let second: Vec<char> = to_char_vec(second);
+        self.max_len = self.max_len.max(first.len()).max(second.len());
+        self.confusions
+            .insert((second.clone(), first.clone()), cost);
+        self.confusions.insert((first, second), cost);
+        self
+    }
+}
+
+impl EditCosts for ConfusionCosts {
+    fn insert(&self, _character: char) -> usize {
+        self.edit
+    }
+    fn delete(&self, _character: char) -> usize {
+        self.edit
+    }
+    fn substitute(&self, from: char, to: char) -> usize {
+        self.confusions
+            .get(&(vec![from], vec![to]))
+            .copied()
+            .unwrap_or(self.edit)
+    }
+    fn substitute_sequence(&self, from: &[char], to: &[char]) -> Option<usize> {
+        self.confusions.get(&(from.to_vec(), to.to_vec())).copied()
+    }
+    fn max_sequence_len(&self) -> usize {
+        self.max_len
+    }
+    fn min_cost(&self) -> usize {
+        self.confusions
+            .iter()
+            .map(|((first, second), cost)| cost / first.len().max(second.len()).max(1))
+            .fold(self.edit, min)
+    }
+}
+
+/// Same as `final_lev_row`, but with the costs of each edit from `costs`
+fn weighted_lev_row(
+    first: &[char],
+    second: &[char],
+    costs: &(impl EditCosts + ?Sized),
+) -> Vec<usize> {
+    let max_len = costs.max_sequence_len();
+    // substituting sequences refers back to earlier rows, so all of them are kept
+    let mut rows: Vec<Vec<usize>> = Vec::with_capacity(first.len() + 1);
+    let mut first_row = Vec::with_capacity(second.len() + 1);
+    first_row.push(0);
+    for &character in second {
+        first_row.push(first_row.last().unwrap() + costs.insert(character));
+    }
+    rows.push(first_row);
+
+    for row in 1..=first.len() {
+        let first_char = first[row - 1];
+        let prev_row = &rows[row - 1];
+        let mut current_row = Vec::with_capacity(second.len() + 1);
+        current_row.push(prev_row[0] + costs.delete(first_char));
+        for column in 1..=second.len() {
+            let second_char = second[column - 1];
+            let replace_cost = if first_char == second_char {
+                0
+            } else {
+                costs.substitute(first_char, second_char)
+            };
+
+            let replace_dist = prev_row[column - 1] + replace_cost;
+            let delete_dist = prev_row[column] + costs.delete(first_char);
+            let insert_dist = current_row[column - 1] + costs.insert(second_char);
+
+            let mut dist = min(replace_dist, min(delete_dist, insert_dist));
+            for from_len in 1..=min(max_len, row) {
+                for to_len in 1..=min(max_len, column) {
+                    if from_len == 1 && to_len == 1 {
+                        continue;
+                    }
+                    let from = &first[row - from_len..row];
+                    let to = &second[column - to_len..column];
+                    if let Some(cost) = costs.substitute_sequence(from, to) {
+                        dist = min(dist, rows[row - from_len][column - to_len] + cost);
+                    }
+                }
+            }
+            current_row.push(dist);
+        }
+        rows.push(current_row);
+    }
+    rows.pop().unwrap()
+}
+
+/// Same as `prefix_edit_distance`, but sums the costs of the edits from `costs` instead of counting them
+pub fn weighted_prefix_edit_distance(
+    first: &str,
+    second: &str,
+    costs: &(impl EditCosts + ?Sized),
+) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    // the row always has the cost from the empty prefix of `second`
+    weighted_lev_row(&first, &second, costs)
+        .into_iter()
+        .min()
+        .unwrap()
+}
+
+/// Same as `edit_distance`, but sums the costs of the edits from `costs` instead of counting them
+pub fn weighted_edit_distance(
+    first: &str,
+    second: &str,
+    costs: &(impl EditCosts + ?Sized),
+) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    *weighted_lev_row(&first, &second, costs).last().unwrap()
+}
+
+/// Same as `prefix_edit_distance`, but each character of `query` has a confidence between 0.0 and 1.0,
+/// such as from a swipe keyboard or speech recognition, and the cost of substituting it is discounted by 1 - confidence
+/// (so it costs the confidence)
+///
+/// Inserting or deleting a character still costs 1
+pub fn confidence_prefix_edit_distance(query: &[(char, f32)], string: &str) -> f32 {
+    let string: Vec<char> = to_char_vec(string);
+    let mut prev_row: Vec<f32> = (0..=string.len()).map(|column| column as f32).collect();
+    let mut current_row = prev_row.clone();
+    for (row, &(query_char, confidence)) in query.iter().enumerate() {
+        // 1 discounted by 1 - confidence
+        let substitute_cost = confidence.clamp(0.0, 1.0);
+        current_row[0] = (row + 1) as f32;
+        for column in 1..=string.len() {
+            let replace_cost = if query_char == string[column - 1] {
+                0.0
+            } else {
+                substitute_cost
+            };
+            let replace_dist = prev_row[column - 1] + replace_cost;
+            let delete_dist = prev_row[column] + 1.0;
+            let insert_dist = current_row[column - 1] + 1.0;
+            current_row[column] = replace_dist.min(delete_dist).min(insert_dist);
+        }
+        std::mem::swap(&mut prev_row, &mut current_row);
+    }
+    prev_row.into_iter().fold(f32::INFINITY, f32::min)
+}
+
+/// Returns the smallest edit distance between `first` and any substring of `second`
+pub fn substring_edit_distance(first: &str, second: &str) -> usize {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    slice_substring_edit_distance(&first, &second)
+}
+
+/// Same as `substring_edit_distance`, but between sequences of any elements, such as words, syllables or bytes
+pub fn slice_substring_edit_distance<T: Eq>(first: &[T], second: &[T]) -> usize {
+    // the first row is all zeros since a substring can start anywhere in `second`
+    let mut prev_row = vec![0; second.len() + 1];
+    let mut current_row = prev_row.clone();
+    for row in 1..=first.len() {
+        current_row[0] = row;
+        for column in 1..=second.len() {
+            let diff = (first[row - 1] != second[column - 1]) as usize;
+
+            let replace_dist = prev_row[column - 1] + diff;
+            let insert_dist = prev_row[column] + 1;
+            let erase_dist = current_row[column - 1] + 1;
+
+            current_row[column] = min(replace_dist, min(insert_dist, erase_dist));
+        }
+        std::mem::swap(&mut prev_row, &mut current_row);
+    }
+    // and it can end anywhere
+    prev_row.into_iter().min().unwrap_or(first.len())
+}
+
+/// An edit operation on the first of two strings, with positions as indices of characters in the first string
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edit {
+    /// Insert `character` before the character at `position`
+    Insert { position: usize, character: char },
+    /// Delete the character at `position`
+    Delete { position: usize },
+    /// Replace the character at `position` with `character`
+    Substitute { position: usize, character: char },
+}
+
+/// Returns the full Levenshtein edit distance matrix between two char slices,
+/// where `matrix[i][j]` is the edit distance between `first[..i]` and `second[..j]`
+fn lev_matrix(first: &[char], second: &[char]) -> Vec<Vec<usize>> {
+    let mut matrix = vec![vec![0; second.len() + 1]; first.len() + 1];
+    for (row, distances) in matrix.iter_mut().enumerate() {
+        distances[0] = row;
+    }
+    for column in 0..=second.len() {
+        matrix[0][column] = column;
+    }
+    for row in 1..=first.len() {
+        for column in 1..=second.len() {
+            let diff = (first[row - 1] != second[column - 1]) as usize;
+
+            let replace_dist = matrix[row - 1][column - 1] + diff;
+            let erase_dist = matrix[row - 1][column] + 1;
+            let insert_dist = matrix[row][column - 1] + 1;
+
+            matrix[row][column] = min(replace_dist, min(erase_dist, insert_dist));
+        }
+    }
+    matrix
+}
+
+/// Returns the edits that transform `first` into `second[..column]` using the `matrix` from `lev_matrix`
+fn backtrack(matrix: &[Vec<usize>], first: &[char], second: &[char], column: usize) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(matrix[first.len()][column]);
+    let (mut row, mut column) = (first.len(), column);
+    while row > 0 || column > 0 {
+        let distance = matrix[row][column];
+        if row > 0 && column > 0 {
+            let diff = first[row - 1] != second[column - 1];
+            if matrix[row - 1][column - 1] + diff as usize == distance {
+                if diff {
+                    edits.push(Edit::Substitute {
+                        position: row - 1,
+                        character: second[column - 1],
+                    });
+                }
+                row -= 1;
+                column -= 1;
+                continue;
+            }
+        }
+        if row > 0 && matrix[row - 1][column] + 1 == distance {
+            edits.push(Edit::Delete { position: row - 1 });
+            row -= 1;
+        } else {
+            edits.push(Edit::Insert {
+                position: row,
+                character: second[column - 1],
+            });
+            column -= 1;
+        }
+    }
+    // the edits were found from the end of the strings
+    edits.reverse();
+    edits
+}
+
+/// Returns a minimal sequence of edits that transforms `first` into `second`,
+/// ordered by position
+pub fn alignment(first: &str, second: &str) -> Vec<Edit> {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    let matrix = lev_matrix(&first, &second);
+    backtrack(&matrix, &first, &second, second.len())
+}
+
+/// Returns a minimal sequence of edits that transforms `first` into the prefix of `second`
+/// with the smallest edit distance (the number of edits is the prefix edit distance)
+pub fn prefix_alignment(first: &str, second: &str) -> Vec<Edit> {
+    let first: Vec<char> = to_char_vec(first);
+    let second: Vec<char> = to_char_vec(second);
+    let matrix = lev_matrix(&first, &second);
+    let last_row = &matrix[first.len()];
+    // the shortest prefix with the prefix edit distance
+    let column = (0..last_row.len())
+        .min_by_key(|&column| last_row[column])
+        .unwrap();
+    backtrack(&matrix, &first, &second, column)
 }
 
 /// Baseline autocomplete using the PED that doesn't use an index
@@ -98,6 +1055,131 @@ pub fn unindexed_autocomplete(
     best.into_sorted_vec()
 }
 
+/// Returns the substrings of `q` characters of `string` with the number of times each occurs
+pub fn qgrams(string: &str, q: usize) -> HashMap<&str, usize> {
+    let mut profile = HashMap::new();
+    if q == 0 {
+        return profile;
+    }
+    let boundaries: Vec<usize> = string
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain([string.len()])
+        .collect();
+    for window in boundaries.windows(q + 1) {
+        *profile.entry(&string[window[0]..window[q]]).or_insert(0) += 1;
+    }
+    profile
+}
+
+/// Returns the Jaccard similarity between the sets of q-grams of two strings, between 0.0 and 1.0
+/// (1.0 if neither has any q-grams)
+pub fn qgram_jaccard(first: &str, second: &str, q: usize) -> f64 {
+    let first = qgrams(first, q);
+    let second = qgrams(second, q);
+    let common = first
+        .keys()
+        .filter(|qgram| second.contains_key(*qgram))
+        .count();
+    let union = first.len() + second.len() - common;
+    if union == 0 {
+        return 1.0;
+    }
+    common as f64 / union as f64
+}
+
+/// Returns the cosine similarity between the q-gram counts of two strings, between 0.0 and 1.0
+/// (1.0 if neither has any q-grams, and 0.0 if only one of them has none)
+pub fn qgram_cosine(first: &str, second: &str, q: usize) -> f64 {
+    let first = qgrams(first, q);
+    let second = qgrams(second, q);
+    let norm = |profile: &HashMap<&str, usize>| {
+        profile
+            .values()
+            .map(|&count| (count * count) as f64)
+            .sum::<f64>()
+            .sqrt()
+    };
+    let (first_norm, second_norm) = (norm(&first), norm(&second));
+    if first_norm == 0.0 || second_norm == 0.0 {
+        return (first_norm == second_norm) as usize as f64;
+    }
+    let dot: usize = first
+        .iter()
+        .map(|(qgram, count)| count * second.get(qgram).unwrap_or(&0))
+        .sum();
+    dot as f64 / (first_norm * second_norm)
+}
+
+/// Discards strings that can't be within an edit distance of a query by counting the q-grams they have in common,
+/// since each edit can only change `q` of them
+pub struct QGramFilter<'q> {
+    q: usize,
+    /// Number of characters of the query
+    len: usize,
+    profile: HashMap<&'q str, usize>,
+}
+
+impl<'q> QGramFilter<'q> {
+    /// Returns a filter for `query` with q-grams of `q` characters (which should be at least 1)
+    pub fn new(query: &'q str, q: usize) -> Self {
+        Self {
+            q,
+            len: query.chars().count(),
+            profile: qgrams(query, q),
+        }
+    }
+    /// Returns false if `string` is certainly more than `max_distance` edits from the query
+    pub fn may_be_within(&self, string: &str, max_distance: usize) -> bool {
+        let len = string.chars().count();
+        if len.abs_diff(self.len) > max_distance {
+            return false;
+        }
+        // the q-gram lemma: strings within `max_distance` share at least this many q-grams
+        let required = (max(len, self.len) + 1).saturating_sub(self.q + max_distance * self.q);
+        if required == 0 {
+            return true;
+        }
+        let mut common = 0;
+        for (qgram, count) in qgrams(string, self.q) {
+            common += min(count, self.profile.get(qgram).copied().unwrap_or(0));
+        }
+        common >= required
+    }
+}
+
+/// Baseline for finding up to `requested` strings within `max_distance` edits of the whole `query` without an index,
+/// sorted by edit distance and then lexicographical order
+///
+/// If `q` is given, strings are first discarded by a `QGramFilter` with q-grams of that many characters
+pub fn unindexed_correct(
+    query: &str,
+    max_distance: usize,
+    requested: usize,
+    strings: &[TreeString],
+    q: Option<usize>,
+) -> Vec<MeasuredString> {
+    let filter = q.map(|q| QGramFilter::new(query, q));
+    let mut result: Vec<MeasuredString> = strings
+        .iter()
+        .filter(|string| {
+            filter
+                .as_ref()
+                .map_or(true, |filter| filter.may_be_within(string, max_distance))
+        })
+        .filter_map(|string| {
+            distance_within(query, string, max_distance).map(|distance| MeasuredString {
+                string: string.to_string(),
+                distance,
+            })
+        })
+        .collect();
+    result.sort();
+    result.dedup();
+    result.truncate(requested);
+    result
+}
+
 /// Returns a string with a number of random `edits` to `string`
 ///
 /// Does not guarantee that the edits are non-overlapping (edit distance may be less than `edits`)
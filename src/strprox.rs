@@ -1,7 +1,8 @@
 pub mod prefix;
-use std::{cmp::Ordering, fmt::Display};
+use std::{cmp::Ordering, fmt::Display, ops::Range};
 
 pub use prefix::meta::{TreeString, TreeStringT};
+use crate::levenshtein::{self, Edit};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -9,11 +10,12 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-/*
-mod hs_tree;
-use hs_tree::HSTree;
-use hs_tree::Rankings;
-*/
+pub mod bk_tree;
+pub mod hs_tree;
+pub mod minhash;
+pub mod searcher;
+mod stable_hash;
+pub mod symspell;
 
 /// Structure that associates a string with its Levenshtein distance from the query
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -48,6 +50,197 @@ impl Ord for MeasuredPrefix {
             .then_with(|| self.string.cmp(&other.string))
     }
 }
+/// Structure that associates the index of a stored string with its prefix edit distance from the query
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct MeasuredIndex {
+    pub index: u32,
+    pub prefix_distance: usize,
+}
+impl Ord for MeasuredIndex {
+    /// Compare the PEDs and then the indices, which follow the lexicographical order of the stored strings
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.prefix_distance
+            .cmp(&other.prefix_distance)
+            .then_with(|| self.index.cmp(&other.index))
+    }
+}
+impl PartialOrd for MeasuredIndex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Structure that associates a MeasuredPrefix with the part of its string that matched the query
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MatchedPrefix {
+    pub measure: MeasuredPrefix,
+    /// Length in characters of the stored prefix that matched the query
+    pub depth: usize,
+    /// Byte range of the string that matched the query, with the rest of the string being the suggested suffix
+    ///
+    /// The range covers the characters of the original string that were normalized into the matched stored prefix
+    pub span: Range<usize>,
+}
+
+/// Structure that associates a MeasuredPrefix with the edits from the query to the prefix of its string
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlignedPrefix {
+    pub measure: MeasuredPrefix,
+    /// Edits on the query with the fewest operations, which is the PED
+    pub edits: Vec<Edit>,
+}
+
+/// Structure that associates a MeasuredPrefix with a payload indexed alongside its string
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MeasuredEntry<T> {
+    pub measure: MeasuredPrefix,
+    pub payload: T,
+}
+
+/// Structure that associates a MeasuredPrefix with the edit distance between the query and its whole string
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DistancedPrefix {
+    pub measure: MeasuredPrefix,
+    /// Edit distance between the query and the whole string
+    pub distance: usize,
+}
+
+/// Structure that associates a MeasuredPrefix with its distances normalized by the lengths of the strings
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScoredPrefix {
+    pub measure: MeasuredPrefix,
+    /// Similarity between 0.0 and 1.0 from the prefix edit distance, normalized by the length of the query
+    pub prefix_similarity: f64,
+    /// Similarity between 0.0 and 1.0 from the edit distance between the query and the whole string,
+    /// normalized by the length of the longer one
+    pub similarity: f64,
+}
+
+/// Structure that associates a MeasuredPrefix with how many times its string occurred in the source
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CountedPrefix {
+    pub measure: MeasuredPrefix,
+    /// Number of times the string occurred in the source
+    pub count: u32,
+}
+
+/// Structure that associates a MeasuredPrefix with the weighted prefix edit distance from the query
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WeightedPrefix {
+    pub measure: MeasuredPrefix,
+    /// Smallest total cost of the edits from the query to a prefix of the string
+    pub cost: usize,
+}
+
+/// Structure that associates a MeasuredPrefix with the prefix edit distance from a query with confidences
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConfidencePrefix {
+    pub measure: MeasuredPrefix,
+    /// Smallest total cost of the edits from the query to a prefix of the string, where substituting a character costs its confidence
+    pub cost: f32,
+}
+
+/// Structure that associates a MeasuredPrefix with whether its string was found by sound rather than spelling
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PhoneticPrefix {
+    pub measure: MeasuredPrefix,
+    /// Whether the string was only found because its words sound like those of the query
+    pub phonetic: bool,
+}
+
+/// Structure that associates a MeasuredPrefix with its distance from the query by another metric
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MetricPrefix {
+    pub measure: MeasuredPrefix,
+    /// Distance from the query by the metric that ranked the results
+    pub distance: f64,
+}
+
+/// Structure that associates a string with its distance from the query by a `Metric`
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MetricString {
+    pub string: String,
+    pub distance: f64,
+}
+
+/// Structure that associates a MeasuredPrefix with how well the query matched its string as a subsequence
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SubsequencePrefix {
+    pub measure: MeasuredPrefix,
+    /// Score of the best subsequence match, where higher is better
+    pub score: i64,
+    /// Byte offsets of the characters of the string that matched each character of the query
+    pub positions: Vec<usize>,
+}
+
+/// Structure that associates the indices of two strings from a similarity join with the edit distance between them
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimilarPair {
+    /// Index of the string on the left side of the join
+    pub left: usize,
+    /// Index of the string on the right side of the join
+    pub right: usize,
+    pub distance: usize,
+}
+
+/// Structure that holds the indices of a cluster of similar strings from `dedup_clusters`
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cluster {
+    /// Index of the string that best represents the cluster
+    pub representative: usize,
+    /// Indices of all strings in the cluster in ascending order, including the representative
+    pub members: Vec<usize>,
+}
+
+/// Structure that associates a MeasuredPrefix with its score from `autocomplete_hybrid`
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HybridPrefix {
+    pub measure: MeasuredPrefix,
+    /// Weighted sum of the normalized scores, where higher is better
+    pub score: f64,
+    /// Score of the best subsequence match, or None if the string doesn't have the characters of the query in order
+    pub subsequence_score: Option<i64>,
+}
+
+/// Structure that associates a MeasuredPrefix with the key of its string that matched the query best
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyedPrefix {
+    /// The original string with the prefix edit distance from the query to the key
+    pub measure: MeasuredPrefix,
+    /// The string itself or one of its alternate keys
+    pub key: String,
+    /// Whether the key is an alternate key or alias rather than the string itself
+    pub alias: bool,
+}
+
+/// Structure that groups results under the stored prefix they branch from
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PrefixGroup {
+    /// Stored prefix that matched the query extended by the next character of the results,
+    /// which is normalized like the stored strings even though the results have their original spelling
+    pub prefix: String,
+    /// Results with the prefix, sorted the same way as ungrouped results
+    pub results: Vec<MeasuredPrefix>,
+}
 impl PartialOrd for MeasuredString {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -58,6 +251,24 @@ impl PartialOrd for MeasuredPrefix {
         Some(self.cmp(other))
     }
 }
+impl MeasuredPrefix {
+    /// Returns the edit distance between `query` and the whole string rather than its best prefix
+    pub fn full_distance(&self, query: &str) -> usize {
+        levenshtein::edit_distance(query, &self.string)
+    }
+}
+impl From<MeasuredPrefix> for MeasuredString {
+    fn from(value: MeasuredPrefix) -> Self {
+        let MeasuredPrefix {
+            string,
+            prefix_distance,
+        } = value;
+        MeasuredString {
+            string,
+            distance: prefix_distance,
+        }
+    }
+}
 impl Display for MeasuredPrefix {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -72,7 +283,31 @@ impl Display for MeasuredPrefix {
 #[doc(inline)]
 pub use prefix::Autocompleter;
 #[doc(inline)]
+pub use searcher::{Searcher, SimilaritySearcher};
+#[doc(inline)]
+pub use bk_tree::BKTree;
+#[doc(inline)]
+pub use hs_tree::HSTree;
+#[doc(inline)]
+pub use minhash::MinHashIndex;
+#[doc(inline)]
 pub use prefix::fst::FstAutocompleter;
 #[doc(inline)]
+pub use prefix::infix::InfixAutocompleter;
+#[doc(inline)]
+pub use prefix::keyed::KeyedAutocompleter;
+#[doc(inline)]
 pub use prefix::meta::MetaAutocompleter as MetaAutocompleter;
+#[doc(inline)]
+pub use prefix::phonetic::PhoneticAutocompleter;
+#[doc(inline)]
+pub use prefix::reload::ReloadableAutocompleter;
+#[doc(inline)]
+pub use prefix::sharded::ShardedAutocompleter;
+#[doc(inline)]
+pub use prefix::snapshot::SnapshotAutocompleter;
+#[doc(inline)]
+pub use prefix::tokens::TokenAutocompleter;
+#[doc(inline)]
+pub use symspell::{MaxDistanceTooLarge, SymSpell};
 //pub type StringSearcher<'a, U> = HSTree<'a, U>;
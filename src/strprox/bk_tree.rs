@@ -0,0 +1,142 @@
+//! Implementation of the BK-tree (Burkhard and Keller, 1973) for whole-string queries by any `Metric`
+//!
+//! The children of each node are keyed by their distances from it, so by the triangle inequality a query only descends into
+//! the children whose distances from the node differ from the distance between the query and the node by at most the radius.
+//! Results are exact for metrics that are symmetric and satisfy the triangle inequality, such as `EditDistance`,
+//! `UnrestrictedDamerauEditDistance` or `WeightedEditDistance` with symmetric costs,
+//! while `DamerauEditDistance` and the prefix distances may miss strings
+use std::cmp::Ordering;
+
+use crate::{levenshtein::Metric, MetricString};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+#[derive(Clone, Debug)]
+struct BKNode<'stored> {
+    string: &'stored str,
+    /// Indices of the child nodes with their distances from this node
+    children: Vec<(f64, SSS)>,
+}
+
+/// Structure that finds the stored strings closest to a query by `M`, pruning strings with the triangle inequality
+#[derive(Clone, Debug)]
+pub struct BKTree<'stored, M> {
+    /// Nodes in the order their strings were inserted, where the first is the root
+    nodes: Vec<BKNode<'stored>>,
+    metric: M,
+}
+
+impl<'stored, M: Metric> BKTree<'stored, M> {
+    /// Constructs a tree over the strings in `source` measured by `metric` (does not copy strings)
+    pub fn new(source: impl IntoIterator<Item = &'stored str>, metric: M) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            metric,
+        };
+        for string in source {
+            tree.insert(string);
+        }
+        tree
+    }
+    /// Inserts a string into the tree
+    pub fn insert(&mut self, string: &'stored str) {
+        let index = self.nodes.len() as SSS;
+        self.nodes.push(BKNode {
+            string,
+            children: Vec::new(),
+        });
+        if index == 0 {
+            return;
+        }
+        let mut parent = 0;
+        loop {
+            let distance = self.metric.distance(self.nodes[parent].string, string);
+            let children = &mut self.nodes[parent].children;
+            match children.iter().find(|&&(key, _)| key == distance) {
+                Some(&(_, child)) => parent = child as usize,
+                None => {
+                    children.push((distance, index));
+                    return;
+                }
+            }
+        }
+    }
+    /// Returns the number of stored strings
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    /// Returns whether no strings are stored
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    /// Returns the metric that strings are measured by
+    pub fn metric(&self) -> &M {
+        &self.metric
+    }
+    /// Returns the stored strings within a distance of `radius` from `query`,
+    /// sorted by distance and then lexicographical order
+    pub fn query(&self, query: &str, radius: f64) -> Vec<MetricString> {
+        let mut result = Vec::new();
+        self.search(query, radius, |measure| {
+            result.push(measure);
+            radius
+        });
+        result.sort_by(compare);
+        result.dedup();
+        result
+    }
+    /// Returns the top `requested` stored strings closest to `query`, sorted by distance and then lexicographical order
+    pub fn topk(&self, query: &str, requested: usize) -> Vec<MetricString> {
+        if requested == 0 {
+            return vec![];
+        }
+        // the best strings so far in order, where the worst bounds the distances of better ones once there are enough
+        let mut result = Vec::<MetricString>::new();
+        self.search(query, f64::INFINITY, |measure| {
+            if let Err(position) = result.binary_search_by(|other| compare(other, &measure)) {
+                result.insert(position, measure);
+                result.truncate(requested);
+            }
+            if result.len() == requested {
+                result[requested - 1].distance
+            } else {
+                f64::INFINITY
+            }
+        });
+        result
+    }
+    /// Visits the nodes that may be within `radius` of `query`, passing those within it to `found`,
+    /// which returns the radius for the rest of the search
+    fn search(&self, query: &str, mut radius: f64, mut found: impl FnMut(MetricString) -> f64) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            let distance = self.metric.distance(query, node.string);
+            if distance <= radius {
+                radius = found(MetricString {
+                    string: node.string.to_string(),
+                    distance,
+                });
+            }
+            // a child is at least as far from the query as the difference between its distance from the node and the query's
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|&&(key, _)| (key - distance).abs() <= radius)
+                    .map(|&(_, child)| child as usize),
+            );
+        }
+    }
+}
+
+/// Compares the distances and then the strings of MetricStrings
+fn compare(first: &MetricString, second: &MetricString) -> Ordering {
+    first
+        .distance
+        .total_cmp(&second.distance)
+        .then_with(|| first.string.cmp(&second.string))
+}
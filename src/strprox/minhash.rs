@@ -0,0 +1,183 @@
+//! Locality-sensitive hashing of strings with MinHash signatures of their character q-grams, for whole-string similarity search
+//! over corpora too large for the trie or the HS-Tree
+//!
+//! Each string is summarized by the minimum hashes of its q-grams under several hash functions, so that two strings have
+//! the same minimum for a function with probability equal to the Jaccard similarity of their q-grams.
+//! The minimums are split into bands, and strings with the same band are candidates, which are verified with the `levenshtein` module.
+//! Only the band hashes are stored with the strings, but strings that share few q-grams with the query can be missed
+use std::collections::HashSet;
+
+use crate::{
+    levenshtein,
+    strprox::stable_hash::{mix, StableHasher},
+    MeasuredString,
+};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+/// Shape of MinHash signatures, which trades memory and query time for the chance of finding similar strings
+///
+/// Strings whose q-grams have a Jaccard similarity of s are candidates with a probability of 1 - (1 - s^rows)^bands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinHashParams {
+    /// Number of characters of each q-gram
+    pub q: usize,
+    /// Number of bands, each of which can make a string a candidate
+    pub bands: usize,
+    /// Number of minimum hashes in each band, which all have to be the same for the band to match
+    pub rows: usize,
+}
+
+impl Default for MinHashParams {
+    fn default() -> Self {
+        Self {
+            q: 2,
+            bands: 16,
+            rows: 2,
+        }
+    }
+}
+
+/// Structure that finds the stored strings similar to a query among those with a band of their MinHash signatures in common
+#[derive(Clone, Debug)]
+pub struct MinHashIndex<'stored> {
+    /// Stored strings in the order they were inserted
+    strings: Vec<&'stored str>,
+    /// Hash of a band paired with the index of each string, sorted by hash and then index, for each band
+    bands: Vec<Vec<(u64, SSS)>>,
+    params: MinHashParams,
+}
+
+impl<'stored> MinHashIndex<'stored> {
+    /// Constructs an index over the strings in `source` with signatures of the default shape (does not copy strings)
+    pub fn new(source: impl IntoIterator<Item = &'stored str>) -> Self {
+        Self::with_params(source, Default::default())
+    }
+    /// Constructs an index over the strings in `source` with signatures shaped by `params` (does not copy strings)
+    pub fn with_params(
+        source: impl IntoIterator<Item = &'stored str>,
+        params: MinHashParams,
+    ) -> Self {
+        let mut index = Self {
+            strings: Vec::new(),
+            bands: vec![Vec::new(); params.bands],
+            params,
+        };
+        // sorting once is cheaper than keeping the bands sorted while inserting
+        for string in source {
+            let string_index = index.strings.len() as SSS;
+            index.strings.push(string);
+            for (band, hash) in index.band_hashes(string).into_iter().enumerate() {
+                index.bands[band].push((hash, string_index));
+            }
+        }
+        for band in &mut index.bands {
+            band.sort_unstable();
+        }
+        index
+    }
+    /// Inserts a string into the index
+    pub fn insert(&mut self, string: &'stored str) {
+        let index = self.strings.len() as SSS;
+        self.strings.push(string);
+        for (band, hash) in self.band_hashes(string).into_iter().enumerate() {
+            let band = &mut self.bands[band];
+            let position = band.partition_point(|&(other, _)| other <= hash);
+            band.insert(position, (hash, index));
+        }
+    }
+    /// Returns the number of stored strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+    /// Returns whether no strings are stored
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+    /// Returns the shape of the signatures
+    pub fn params(&self) -> MinHashParams {
+        self.params
+    }
+    /// Returns the candidates within an edit distance of `threshold` from `query`,
+    /// sorted by edit distance and then lexicographical order
+    pub fn query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        let mut result: Vec<MeasuredString> = self
+            .candidates(query)
+            .into_iter()
+            .filter_map(|index| {
+                let string = self.strings[index as usize];
+                levenshtein::distance_within(query, string, threshold).map(|distance| {
+                    MeasuredString {
+                        string: string.to_string(),
+                        distance,
+                    }
+                })
+            })
+            .collect();
+        result.sort();
+        result.dedup();
+        result
+    }
+    /// Returns the top `requested` candidates closest to `query`, sorted by edit distance and then lexicographical order
+    pub fn topk(&self, query: &str, requested: usize) -> Vec<MeasuredString> {
+        self.threshold_topk(query, requested, usize::MAX)
+    }
+    /// Same as `topk`, but only returns candidates within an edit distance of `max_threshold` from `query`
+    pub fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        let mut result = self.query(query, max_threshold);
+        result.truncate(requested);
+        result
+    }
+    /// Returns the indices of the stored strings that have a band in common with `query`
+    fn candidates(&self, query: &str) -> HashSet<SSS> {
+        let mut candidates = HashSet::new();
+        for (band, hash) in self.band_hashes(query).into_iter().enumerate() {
+            let band = &self.bands[band];
+            let start = band.partition_point(|&(other, _)| other < hash);
+            candidates.extend(
+                band[start..]
+                    .iter()
+                    .take_while(|&&(other, _)| other == hash)
+                    .map(|&(_, index)| index),
+            );
+        }
+        candidates
+    }
+    /// Returns the hash of each band of the MinHash signature of `string`
+    fn band_hashes(&self, string: &str) -> Vec<u64> {
+        let MinHashParams { q, bands, rows } = self.params;
+        let mut grams: Vec<u64> = levenshtein::qgrams(string, q)
+            .into_keys()
+            .map(hash)
+            .collect();
+        // strings shorter than a q-gram are their only q-gram
+        if grams.is_empty() {
+            grams.push(hash(string));
+        }
+        (0..bands)
+            .map(|band| {
+                let mut hasher = StableHasher::with_seed(band as u64);
+                for row in 0..rows {
+                    // the mixed grams are different hash functions of the q-grams for each row
+                    let seed = mix((band * rows + row) as u64);
+                    let minimum = grams.iter().map(|&gram| mix(gram ^ seed)).min();
+                    hasher.write_u64(minimum.unwrap_or_default());
+                }
+                hasher.finish()
+            })
+            .collect()
+    }
+}
+
+/// Returns the hash of `value`, which is the same across runs, platforms and Rust releases
+fn hash(value: &str) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(value.as_bytes());
+    hasher.finish()
+}
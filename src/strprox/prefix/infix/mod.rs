@@ -0,0 +1,70 @@
+use std::borrow::Cow;
+
+use crate::{levenshtein, MeasuredPrefix};
+
+use super::meta::{Cache, MetaAutocompleter, TreeString};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+/// Searches for the query at any position inside the stored strings instead of only at their start
+pub struct InfixAutocompleter<'stored> {
+    /// Stored strings in the order they were given
+    strings: Vec<TreeString<'stored>>,
+    /// Distinct suffixes of the stored strings with the indices of the strings that end with them as payloads
+    suffixes: MetaAutocompleter<'stored, u8, SSS, SSS>,
+}
+
+/// Returns the non-empty suffixes of `string`, borrowing from it if possible
+fn suffixes<'stored>(string: &TreeString<'stored>) -> Vec<TreeString<'stored>> {
+    match string {
+        Cow::Borrowed(string) => string
+            .char_indices()
+            .map(|(start, _)| Cow::Borrowed(&string[start..]))
+            .collect(),
+        Cow::Owned(string) => string
+            .char_indices()
+            .map(|(start, _)| Cow::Owned(string[start..].to_string()))
+            .collect(),
+    }
+}
+
+impl<'stored> InfixAutocompleter<'stored> {
+    /// Constructs an autocompleter over all suffixes of the strings in `source` (does not copy borrowed strings)
+    ///
+    /// The index has a string for every character of the source, so this is best suited to short strings like file names
+    pub fn new(source: impl IntoIterator<Item = TreeString<'stored>>) -> Self {
+        let strings: Vec<TreeString<'stored>> = source.into_iter().collect();
+        let mut pairs = Vec::new();
+        for (index, string) in strings.iter().enumerate() {
+            for suffix in suffixes(string) {
+                pairs.push((suffix, index as SSS));
+            }
+        }
+        let suffixes = MetaAutocompleter::new_with_payloads(pairs.len(), pairs);
+        Self { strings, suffixes }
+    }
+    /// Returns the top `requested` stored strings by the smallest edit distance between `query` and any of their substrings,
+    /// sorted by that distance and then lexicographical order
+    pub fn autocomplete_infix(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        let indices = self.suffixes.autocomplete_payloads(query, requested, cache);
+        let mut result: Vec<MeasuredPrefix> = indices
+            .into_keys()
+            .map(|index| {
+                let string = &self.strings[index as usize];
+                MeasuredPrefix {
+                    string: string.to_string(),
+                    prefix_distance: levenshtein::substring_edit_distance(query, string),
+                }
+            })
+            .collect();
+        result.sort();
+        result.truncate(requested);
+        result
+    }
+}
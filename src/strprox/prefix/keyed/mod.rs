@@ -0,0 +1,99 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{KeyedPrefix, MeasuredPrefix};
+
+use super::meta::{Cache, MetaAutocompleter, TreeString};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+/// Autocompletes strings that are each indexed under themselves and alternate keys,
+/// such as transliterations (pinyin for Chinese or romaji for Japanese), while results have the original strings
+pub struct KeyedAutocompleter<'stored> {
+    /// Stored strings in the order they were given
+    strings: Vec<TreeString<'stored>>,
+    /// Distinct keys with the indices of the strings they're for as payloads
+    keys: MetaAutocompleter<'stored, u8, SSS, SSS>,
+}
+
+impl<'stored> KeyedAutocompleter<'stored> {
+    /// Constructs an autocompleter over the strings in `source`, which are also indexed under the keys from `keys`
+    /// (does not copy borrowed strings)
+    pub fn new(
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+        keys: impl Fn(&str) -> Vec<String>,
+    ) -> Self {
+        Self::from_keys(source.into_iter().map(|string| {
+            let alternates = keys(&string).into_iter().map(Cow::Owned).collect();
+            (string, alternates)
+        }))
+    }
+    /// Constructs an autocompleter over the strings in `source`, each of which is also indexed under its alternate keys
+    /// (does not copy borrowed strings)
+    pub fn from_keys(
+        source: impl IntoIterator<Item = (TreeString<'stored>, Vec<TreeString<'stored>>)>,
+    ) -> Self {
+        let mut strings = Vec::new();
+        let mut pairs = Vec::new();
+        for (index, (string, alternates)) in source.into_iter().enumerate() {
+            pairs.push((string.clone(), index as SSS));
+            for key in alternates {
+                pairs.push((key, index as SSS));
+            }
+            strings.push(string);
+        }
+        let keys = MetaAutocompleter::new_with_payloads(pairs.len(), pairs);
+        Self { strings, keys }
+    }
+    /// Constructs an autocompleter over the strings in `source`, where each pair of `aliases` is an alias
+    /// and the canonical string that a query matching it returns, such as ("NYC", "New York City")
+    ///
+    /// Canonical strings that aren't in `source` are stored after the strings that are (does not copy borrowed strings)
+    pub fn with_aliases(
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+        aliases: impl IntoIterator<Item = (TreeString<'stored>, TreeString<'stored>)>,
+    ) -> Self {
+        let mut entries: Vec<(TreeString<'stored>, Vec<TreeString<'stored>>)> = source
+            .into_iter()
+            .map(|string| (string, Vec::new()))
+            .collect();
+        // canonical string |-> index of its entry
+        let mut positions: HashMap<TreeString<'stored>, usize> = HashMap::new();
+        for (index, (string, _)) in entries.iter().enumerate() {
+            positions.entry(string.clone()).or_insert(index);
+        }
+        for (alias, canonical) in aliases {
+            let index = *positions.entry(canonical.clone()).or_insert_with(|| {
+                entries.push((canonical, Vec::new()));
+                entries.len() - 1
+            });
+            entries[index].1.push(alias);
+        }
+        Self::from_keys(entries)
+    }
+    /// Returns the top `requested` stored strings by the smallest prefix edit distance from `query` to any of their keys,
+    /// sorted by that distance and then lexicographical order, with the key that matched best
+    pub fn autocomplete_keyed(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<KeyedPrefix> {
+        // string index |-> best key with its prefix edit distance
+        let best = self.keys.autocomplete_payloads(query, requested, cache);
+        let mut result: Vec<KeyedPrefix> = best
+            .into_iter()
+            .map(|(index, key)| KeyedPrefix {
+                measure: MeasuredPrefix {
+                    string: self.strings[index as usize].to_string(),
+                    prefix_distance: key.prefix_distance,
+                },
+                alias: key.string != self.strings[index as usize],
+                key: key.string,
+            })
+            .collect();
+        result.sort_by(|a, b| a.measure.cmp(&b.measure).then_with(|| a.key.cmp(&b.key)));
+        result.truncate(requested);
+        result
+    }
+}
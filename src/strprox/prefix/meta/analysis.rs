@@ -0,0 +1,101 @@
+use std::{borrow::Cow, sync::Arc};
+
+/// Text analysis applied the same way to stored strings and queries, in the order of
+/// `normalize`, filtering characters with `keep`, and then `tokenize`
+///
+/// The tokens are joined with single spaces, so punctuation and repeated whitespace between words don't affect matching
+pub trait Analyzer: Send + Sync {
+    /// Returns `string` normalized, such as by transliterating it
+    fn normalize<'a>(&self, string: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(string)
+    }
+    /// Returns whether `character` is kept after normalization
+    fn keep(&self, _character: char) -> bool {
+        true
+    }
+    /// Returns the tokens of the filtered `string`
+    fn tokenize<'a>(&self, string: &'a str) -> Vec<&'a str> {
+        vec![string]
+    }
+    /// Returns `string` after the whole pipeline, borrowing it if it's unchanged
+    fn analyze<'a>(&self, string: &'a str) -> Cow<'a, str> {
+        let normalized = self.normalize(string);
+        let filtered: String = normalized.chars().filter(|&c| self.keep(c)).collect();
+        let analyzed = self.tokenize(&filtered).join(" ");
+        if analyzed == string {
+            Cow::Borrowed(string)
+        } else {
+            Cow::Owned(analyzed)
+        }
+    }
+}
+
+/// Analyzer composed of steps that run in the order they were added within each stage of the pipeline
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    normalizers: Vec<Arc<dyn Fn(&str) -> String + Send + Sync>>,
+    filters: Vec<Arc<dyn Fn(char) -> bool + Send + Sync>>,
+    /// Characters between tokens, or None to keep the whole string as one token
+    separator: Option<Arc<dyn Fn(char) -> bool + Send + Sync>>,
+}
+
+impl Pipeline {
+    /// Returns a pipeline that leaves strings unchanged
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Adds `normalize` to the normalization stage
+    pub fn normalize(mut self, normalize: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.normalizers.push(Arc::new(normalize));
+        self
+    }
+    /// Removes the characters that `keep` returns false for after normalization
+    pub fn filter(mut self, keep: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+        self.filters.push(Arc::new(keep));
+        self
+    }
+    /// Splits strings into tokens on the characters that `is_separator` returns true for, dropping empty tokens
+    pub fn split_on(mut self, is_separator: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+        self.separator = Some(Arc::new(is_separator));
+        self
+    }
+}
+
+impl Analyzer for Pipeline {
+    fn normalize<'a>(&self, string: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(string);
+        for normalize in &self.normalizers {
+            result = Cow::Owned(normalize(&result));
+        }
+        result
+    }
+    fn keep(&self, character: char) -> bool {
+        self.filters.iter().all(|keep| keep(character))
+    }
+    fn tokenize<'a>(&self, string: &'a str) -> Vec<&'a str> {
+        match &self.separator {
+            Some(is_separator) => string
+                .split(|c| is_separator(c))
+                .filter(|token| !token.is_empty())
+                .collect(),
+            None => vec![string],
+        }
+    }
+}
+
+/// Returns whether `character` isn't punctuation, to remove punctuation with `Pipeline::filter`
+pub fn not_punctuation(character: char) -> bool {
+    !character.is_ascii_punctuation()
+        && !matches!(character, '‘' | '’' | '“' | '”' | '–' | '—' | '…')
+}
+
+/// Returns `string` without accents, such as "cafe" for "café", to use with `Pipeline::normalize`
+#[cfg(feature = "unicode")]
+pub fn strip_accents(string: &str) -> String {
+    use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+    string
+        .nfd()
+        .filter(|&c| !is_combining_mark(c))
+        .nfc()
+        .collect()
+}
@@ -0,0 +1,604 @@
+use std::{borrow::Cow, collections::BTreeMap, fmt, fs, io, mem::size_of, ops::Range, path::Path};
+
+#[cfg(feature = "unicode-segmentation")]
+use super::graphemes::GraphemeTable;
+#[cfg(feature = "unicode")]
+use super::NormalizationForm;
+use super::{
+    next_generation, CaseFolding, CharFilter, InvertedIndex, MetaAutocompleter, Node, Normalizer,
+    ThresholdPolicy, TopStrings, TreeString, Trie, SSS, UUU,
+};
+
+/// First bytes of every archive
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"SPXA";
+/// Version of the archive layout written by `to_archive`, which `from_archive` rejects archives of other versions by
+///
+/// Version 1 archives, which didn't store the normalizer or check that lengths fit, are rejected
+pub const ARCHIVE_VERSION: u32 = 2;
+/// First bytes of a zstd frame, which `load_from` decompresses
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// Number of bytes of the magic, version, widths and checksum before the sections
+const HEADER_LEN: usize = 14;
+
+/// Error from reading an archive that isn't a valid index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// The bytes don't start with `ARCHIVE_MAGIC`
+    BadMagic,
+    /// The archive has a layout version other than `ARCHIVE_VERSION`
+    UnsupportedVersion(u32),
+    /// The archive was written with other widths of the types that bound the length of strings and the number of strings
+    WidthMismatch { uuu: u8, sss: u8 },
+    /// The sections don't have the checksum in the header, so the archive was corrupted
+    ChecksumMismatch,
+    /// The archive ends in the middle of a section
+    Truncated,
+    /// A stored string isn't UTF-8
+    InvalidUtf8,
+    /// A section is inconsistent with the rest of the index, such as a node whose strings are out of bounds
+    Invalid(&'static str),
+    /// A section of the index has more items or bytes than the 32-bit lengths of the layout can count
+    TooLarge(&'static str),
+    /// The normalizer has a custom function or analyzer, which can't be archived
+    CustomNormalizer,
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not an archived index"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported archive version {}", version)
+            }
+            Self::WidthMismatch { uuu, sss } => write!(
+                f,
+                "archive has {}-byte string lengths and {}-byte string indices instead of {} and {}",
+                uuu,
+                sss,
+                size_of::<UUU>(),
+                size_of::<SSS>()
+            ),
+            Self::ChecksumMismatch => write!(f, "archive checksum mismatch"),
+            Self::Truncated => write!(f, "archive is truncated"),
+            Self::InvalidUtf8 => write!(f, "archived string is not UTF-8"),
+            Self::Invalid(section) => write!(f, "invalid archived {}", section),
+            Self::TooLarge(section) => write!(f, "{} too large to archive", section),
+            Self::CustomNormalizer => {
+                write!(f, "custom normalization functions and analyzers can't be archived")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// Error from loading an archived index from a file, which is either unreadable or not a valid archive
+#[derive(Debug)]
+pub enum IndexFileError {
+    Io(io::Error),
+    Archive(ArchiveError),
+}
+
+impl fmt::Display for IndexFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Archive(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for IndexFileError {}
+
+impl From<io::Error> for IndexFileError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ArchiveError> for IndexFileError {
+    fn from(error: ArchiveError) -> Self {
+        Self::Archive(error)
+    }
+}
+
+/// Little-endian writer of archive sections
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    /// Writes a length of `section`, which is rejected if it doesn't fit in a u32
+    fn len(&mut self, len: usize, section: &'static str) -> Result<(), ArchiveError> {
+        self.u32(u32::try_from(len).map_err(|_| ArchiveError::TooLarge(section))?);
+        Ok(())
+    }
+    fn u32s(&mut self, values: &[u32], section: &'static str) -> Result<(), ArchiveError> {
+        self.len(values.len(), section)?;
+        for &value in values {
+            self.u32(value);
+        }
+        Ok(())
+    }
+    fn str(&mut self, string: &str, section: &'static str) -> Result<(), ArchiveError> {
+        self.len(string.len(), section)?;
+        self.0.extend_from_slice(string.as_bytes());
+        Ok(())
+    }
+    /// Writes the end offset of each string followed by all of them, so they can be borrowed without copying
+    fn strings(
+        &mut self,
+        strings: &[TreeString],
+        section: &'static str,
+    ) -> Result<(), ArchiveError> {
+        self.len(strings.len(), section)?;
+        let mut end = 0;
+        for string in strings {
+            end += string.len();
+            self.len(end, section)?;
+        }
+        for string in strings {
+            self.0.extend_from_slice(string.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// Reader of archive sections that borrows strings from the archive
+struct Reader<'stored> {
+    bytes: &'stored [u8],
+    /// Whether the stored strings are checked to be UTF-8, which reads every byte of them
+    validate: bool,
+}
+
+impl<'stored> Reader<'stored> {
+    fn take(&mut self, len: usize) -> Result<&'stored [u8], ArchiveError> {
+        if self.bytes.len() < len {
+            return Err(ArchiveError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn u8(&mut self) -> Result<u8, ArchiveError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32, ArchiveError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64, ArchiveError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn usize(&mut self) -> Result<usize, ArchiveError> {
+        usize::try_from(self.u64()?).map_err(|_| ArchiveError::Invalid("integer"))
+    }
+    /// Reads a length, checking that the archive has at least `item_size` bytes for each item
+    fn len(&mut self, item_size: usize) -> Result<usize, ArchiveError> {
+        let len = self.u32()? as usize;
+        if self.bytes.len() < len.saturating_mul(item_size) {
+            return Err(ArchiveError::Truncated);
+        }
+        Ok(len)
+    }
+    fn u32s(&mut self) -> Result<Vec<u32>, ArchiveError> {
+        let len = self.len(4)?;
+        (0..len).map(|_| self.u32()).collect()
+    }
+    fn str(&mut self) -> Result<&'stored str, ArchiveError> {
+        let len = self.len(1)?;
+        std::str::from_utf8(self.take(len)?).map_err(|_| ArchiveError::InvalidUtf8)
+    }
+    fn strings(&mut self) -> Result<Vec<TreeString<'stored>>, ArchiveError> {
+        let ends = self.u32s()?;
+        let blob = self.take(ends.last().map_or(0, |&end| end as usize))?;
+        if self.validate {
+            // validating the whole blob at once leaves checking that each string starts on a character boundary
+            let blob = std::str::from_utf8(blob).map_err(|_| ArchiveError::InvalidUtf8)?;
+            if ends.iter().any(|&end| !blob.is_char_boundary(end as usize)) {
+                return Err(ArchiveError::InvalidUtf8);
+            }
+        }
+        let mut start = 0;
+        let mut strings = Vec::with_capacity(ends.len());
+        for end in ends {
+            let end = end as usize;
+            if end < start {
+                return Err(ArchiveError::InvalidUtf8);
+            }
+            // SAFETY: the blob was validated above, or the caller of `from_archive_unchecked` guarantees it's UTF-8
+            // with every string starting on a character boundary
+            strings.push(Cow::Borrowed(unsafe {
+                std::str::from_utf8_unchecked(&blob[start..end])
+            }));
+            start = end;
+        }
+        Ok(strings)
+    }
+    fn range(&mut self, bound: usize) -> Result<Range<SSS>, ArchiveError> {
+        let range = self.u32()?..self.u32()?;
+        if range.start > range.end || range.end as usize > bound {
+            return Err(ArchiveError::Invalid("node"));
+        }
+        Ok(range)
+    }
+}
+
+impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
+    /// Returns the index in a binary layout that `from_archive` can load without copying the stored strings
+    ///
+    /// The settings of the normalizer are archived with the index, but indices normalized with a custom function
+    /// or analyzer are rejected, as are indices with more strings or bytes than the layout's 32-bit lengths can count
+    pub fn to_archive(&self) -> Result<Vec<u8>, ArchiveError> {
+        let normalizer = &self.normalizer;
+        if normalizer.custom.is_some() || normalizer.analyzer.is_some() {
+            return Err(ArchiveError::CustomNormalizer);
+        }
+        let mut writer = Writer(Vec::new());
+        writer.0.extend_from_slice(&ARCHIVE_MAGIC);
+        writer.u32(ARCHIVE_VERSION);
+        writer.u8(size_of::<UUU>() as u8);
+        writer.u8(size_of::<SSS>() as u8);
+        // the checksum is filled in after the sections are written
+        writer.u32(0);
+
+        writer.strings(&self.trie.strings, "strings")?;
+        writer.len(self.trie.nodes.len(), "nodes")?;
+        for node in &self.trie.nodes {
+            writer.u32(node.character as u32);
+            writer.u32(node.descendant_range.start);
+            writer.u32(node.descendant_range.end);
+            writer.u32(node.string_range.start);
+            writer.u32(node.string_range.end);
+            writer.u8(node.depth);
+        }
+
+        writer.u32s(&self.weights, "weights")?;
+        writer.u32s(&self.counts, "counts")?;
+        writer.len(self.payloads.len(), "payloads")?;
+        for payloads in &self.payloads {
+            writer.len(payloads.len(), "payloads")?;
+        }
+        writer.strings(&self.originals, "originals")?;
+
+        writer.u64(self.top.k as u64);
+        writer.u32s(&self.top.offsets, "top strings")?;
+        writer.u32s(&self.top.indices, "top strings")?;
+
+        match self.threshold {
+            None => writer.u8(0),
+            Some(ThresholdPolicy::Fixed(edits)) => {
+                writer.u8(1);
+                writer.u64(edits as u64);
+            }
+            Some(ThresholdPolicy::Scaled { per_chars }) => {
+                writer.u8(2);
+                writer.u64(per_chars as u64);
+            }
+        }
+        writer.len(self.pins.len(), "pins")?;
+        for (prefix, pinned) in &self.pins {
+            writer.str(prefix, "pins")?;
+            writer.u32s(pinned, "pins")?;
+        }
+        writer.u64(self.min_fuzzy_len as u64);
+        writer.u64(self.cache_size as u64);
+
+        #[cfg(feature = "unicode")]
+        writer.u8(match normalizer.form {
+            None => 0,
+            Some(NormalizationForm::Nfc) => 1,
+            Some(NormalizationForm::Nfkc) => 2,
+        });
+        #[cfg(not(feature = "unicode"))]
+        writer.u8(0);
+        writer.u8(match normalizer.case_folding {
+            CaseFolding::None => 0,
+            CaseFolding::Ascii => 1,
+            CaseFolding::Unicode => 2,
+        });
+        writer.u8(match normalizer.char_filter {
+            CharFilter::None => 0,
+            CharFilter::Collapse => 1,
+            CharFilter::Remove => 2,
+        });
+
+        // clusters are only written if the strings are compared by them
+        #[cfg(feature = "unicode-segmentation")]
+        let graphemes: Option<Vec<(&String, &char)>> = self
+            .graphemes
+            .as_ref()
+            .map(|table| table.codes.iter().collect());
+        #[cfg(not(feature = "unicode-segmentation"))]
+        let graphemes: Option<Vec<(&String, &char)>> = None;
+        writer.u8(graphemes.is_some() as u8);
+        let graphemes = graphemes.unwrap_or_default();
+        writer.len(graphemes.len(), "graphemes")?;
+        for (cluster, &code) in graphemes {
+            writer.str(cluster, "graphemes")?;
+            writer.u32(code as u32);
+        }
+        let checksum = crc32fast::hash(&writer.0[HEADER_LEN..]);
+        writer.0[HEADER_LEN - 4..HEADER_LEN].copy_from_slice(&checksum.to_le_bytes());
+        Ok(writer.0)
+    }
+    /// Loads an index from `bytes` written by `to_archive`, borrowing the stored strings from it
+    ///
+    /// Every section is validated, so that corrupted archives are rejected instead of panicking in queries,
+    /// and only the inverted index is rebuilt
+    pub fn from_archive(bytes: &'stored [u8]) -> Result<Self, ArchiveError> {
+        Self::from_archive_with(bytes, true)
+    }
+    /// Same as `from_archive`, but doesn't read the stored strings, which aren't checked to be UTF-8 or sorted,
+    /// and doesn't verify the checksum, so that loading only reads the nodes and the smaller sections
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must be an archive written by `to_archive` that hasn't been corrupted since,
+    /// since strings that aren't UTF-8 are undefined behavior
+    pub unsafe fn from_archive_unchecked(bytes: &'stored [u8]) -> Result<Self, ArchiveError> {
+        Self::from_archive_with(bytes, false)
+    }
+    /// Loads an index from `bytes`, validating the stored strings and the checksum if `validate` is true
+    fn from_archive_with(bytes: &'stored [u8], validate: bool) -> Result<Self, ArchiveError> {
+        let mut reader = Reader { bytes, validate };
+        if reader.take(ARCHIVE_MAGIC.len()) != Ok(&ARCHIVE_MAGIC[..]) {
+            return Err(ArchiveError::BadMagic);
+        }
+        let version = reader.u32()?;
+        if version != ARCHIVE_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(version));
+        }
+        let (uuu, sss) = (reader.u8()?, reader.u8()?);
+        if (uuu as usize, sss as usize) != (size_of::<UUU>(), size_of::<SSS>()) {
+            return Err(ArchiveError::WidthMismatch { uuu, sss });
+        }
+        let checksum = reader.u32()?;
+        if validate && checksum != crc32fast::hash(reader.bytes) {
+            return Err(ArchiveError::ChecksumMismatch);
+        }
+
+        let strings = reader.strings()?;
+        if validate && strings.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(ArchiveError::Invalid("strings"));
+        }
+        // each node has a character, 4 range bounds and a depth
+        let node_count = reader.len(21)?;
+        if node_count == 0 {
+            return Err(ArchiveError::Invalid("node"));
+        }
+        let mut nodes: Vec<Node<UUU, SSS>> = Vec::with_capacity(node_count);
+        // ids of the ancestors of the current node, which queries rely on being nested in pre-order
+        let mut ancestors: Vec<usize> = Vec::new();
+        for id in 0..node_count {
+            let character = char::from_u32(reader.u32()?).ok_or(ArchiveError::Invalid("node"))?;
+            let descendant_range = reader.range(node_count)?;
+            let string_range = reader.range(strings.len())?;
+            let depth = reader.u8()?;
+            // descendants follow their node in pre-order
+            if descendant_range.start as usize != id + 1 {
+                return Err(ArchiveError::Invalid("node"));
+            }
+            while ancestors
+                .last()
+                .is_some_and(|&ancestor| nodes[ancestor].descendant_range.end as usize <= id)
+            {
+                ancestors.pop();
+            }
+            let nested = match ancestors.last() {
+                // only the root has no parent, and it contains every node and string
+                None => {
+                    id == 0
+                        && depth == 0
+                        && descendant_range.end as usize == node_count
+                        && string_range == (0..strings.len() as SSS)
+                }
+                Some(&parent) => {
+                    let parent = &nodes[parent];
+                    Some(depth) == parent.depth.checked_add(1)
+                        && descendant_range.end <= parent.descendant_range.end
+                        && parent.string_range.start <= string_range.start
+                        && string_range.end <= parent.string_range.end
+                }
+            };
+            if !nested {
+                return Err(ArchiveError::Invalid("node"));
+            }
+            ancestors.push(id);
+            nodes.push(Node {
+                character,
+                descendant_range,
+                string_range,
+                depth,
+            });
+        }
+
+        let string_count = strings.len();
+        let per_string = |values: Vec<u32>, section| {
+            if values.is_empty() || values.len() == string_count {
+                Ok(values)
+            } else {
+                Err(ArchiveError::Invalid(section))
+            }
+        };
+        let in_bounds = |indices: &[u32], section| {
+            if indices.iter().all(|&index| (index as usize) < string_count) {
+                Ok(())
+            } else {
+                Err(ArchiveError::Invalid(section))
+            }
+        };
+        let weights = per_string(reader.u32s()?, "weights")?;
+        let counts = per_string(reader.u32s()?, "counts")?;
+        let payloads = per_string(reader.u32s()?, "payloads")?
+            .into_iter()
+            .map(|len| vec![(); len as usize])
+            .collect();
+        let originals = reader.strings()?;
+        if !originals.is_empty() && originals.len() != string_count {
+            return Err(ArchiveError::Invalid("originals"));
+        }
+
+        let top = TopStrings {
+            k: reader.usize()?,
+            offsets: reader.u32s()?,
+            indices: reader.u32s()?,
+        };
+        in_bounds(&top.indices, "top strings")?;
+        if !top.offsets.is_empty()
+            && (top.offsets.len() != node_count + 1
+                || top.offsets.windows(2).any(|pair| pair[0] > pair[1])
+                || *top.offsets.last().unwrap() as usize > top.indices.len())
+        {
+            return Err(ArchiveError::Invalid("top strings"));
+        }
+
+        let threshold = match reader.u8()? {
+            0 => None,
+            1 => Some(ThresholdPolicy::Fixed(reader.usize()?)),
+            2 => Some(ThresholdPolicy::Scaled {
+                per_chars: reader.usize()?,
+            }),
+            _ => return Err(ArchiveError::Invalid("threshold")),
+        };
+        let mut pins = BTreeMap::new();
+        for _ in 0..reader.len(8)? {
+            let prefix = reader.str()?.to_string();
+            let pinned = reader.u32s()?;
+            in_bounds(&pinned, "pins")?;
+            pins.insert(prefix, pinned);
+        }
+        let min_fuzzy_len = reader.usize()?;
+        let cache_size = reader.usize()?;
+
+        let form = reader.u8()?;
+        #[cfg(not(feature = "unicode"))]
+        if form != 0 {
+            return Err(ArchiveError::Invalid(
+                "normalizer (requires the unicode feature)",
+            ));
+        }
+        let normalizer = Normalizer {
+            #[cfg(feature = "unicode")]
+            form: match form {
+                0 => None,
+                1 => Some(NormalizationForm::Nfc),
+                2 => Some(NormalizationForm::Nfkc),
+                _ => return Err(ArchiveError::Invalid("normalizer")),
+            },
+            case_folding: match reader.u8()? {
+                0 => CaseFolding::None,
+                1 => CaseFolding::Ascii,
+                2 => CaseFolding::Unicode,
+                _ => return Err(ArchiveError::Invalid("normalizer")),
+            },
+            char_filter: match reader.u8()? {
+                0 => CharFilter::None,
+                1 => CharFilter::Collapse,
+                2 => CharFilter::Remove,
+                _ => return Err(ArchiveError::Invalid("normalizer")),
+            },
+            custom: None,
+            analyzer: None,
+        };
+
+        let by_graphemes = reader.u8()? != 0;
+        #[cfg(not(feature = "unicode-segmentation"))]
+        if by_graphemes {
+            return Err(ArchiveError::Invalid(
+                "graphemes (requires the unicode-segmentation feature)",
+            ));
+        }
+        let grapheme_count = reader.len(8)?;
+        #[cfg(feature = "unicode-segmentation")]
+        let mut codes = std::collections::HashMap::with_capacity(grapheme_count);
+        for _ in 0..grapheme_count {
+            let cluster = reader.str()?;
+            let code = char::from_u32(reader.u32()?).ok_or(ArchiveError::Invalid("graphemes"))?;
+            #[cfg(feature = "unicode-segmentation")]
+            codes.insert(cluster.to_string(), code);
+            #[cfg(not(feature = "unicode-segmentation"))]
+            let _ = (cluster, code);
+        }
+        if !reader.bytes.is_empty() {
+            return Err(ArchiveError::Invalid("trailing bytes"));
+        }
+
+        let trie = Trie { nodes, strings };
+        let inverted_index = InvertedIndex::new(&trie);
+        Ok(Self {
+            trie,
+            inverted_index,
+            payloads,
+            weights,
+            counts,
+            originals,
+            top,
+            threshold,
+            pins,
+            min_fuzzy_len,
+            normalizer,
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: by_graphemes.then_some(GraphemeTable { codes }),
+            cache_size,
+            generation: next_generation(),
+        })
+    }
+    /// Writes the index to the file at `path` in the layout of `to_archive`
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), IndexFileError> {
+        Ok(fs::write(path, self.to_archive()?)?)
+    }
+    /// Same as `save_to`, but compresses the archive into a zstd frame at `level` (1 to 22, or 0 for zstd's default),
+    /// which `load_from` detects and decompresses
+    ///
+    /// Compressed files can't be loaded by `load_mmap`
+    #[cfg(feature = "zstd")]
+    pub fn save_compressed_to(
+        &self,
+        path: impl AsRef<Path>,
+        level: i32,
+    ) -> Result<(), IndexFileError> {
+        let archive = self.to_archive()?;
+        let mut encoder = zstd::Encoder::new(fs::File::create(path)?, level)?;
+        io::Write::write_all(&mut encoder, &archive)?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+impl MetaAutocompleter<'static, UUU, SSS> {
+    /// Loads an index from the file at `path` written by `save_to`, copying its strings out of the file
+    ///
+    /// Files compressed by `save_compressed_to` are decompressed as they're read if the `zstd` feature is enabled.
+    /// Files written by other versions of the layout or with other type widths are rejected with an `ArchiveError`
+    /// instead of being misread
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, IndexFileError> {
+        let bytes = read_archive(path.as_ref())?;
+        Ok(MetaAutocompleter::from_archive(&bytes)?.into_owned())
+    }
+}
+
+/// Reads the archive in the file at `path`, decompressing it while it's read if it's a zstd frame
+fn read_archive(path: &Path) -> io::Result<Vec<u8>> {
+    #[cfg(feature = "zstd")]
+    {
+        use io::{BufRead, Read};
+        let mut file = io::BufReader::new(fs::File::open(path)?);
+        let mut bytes = Vec::new();
+        if file.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+            zstd::Decoder::with_buffer(file)?.read_to_end(&mut bytes)?;
+        } else {
+            file.read_to_end(&mut bytes)?;
+        }
+        Ok(bytes)
+    }
+    #[cfg(not(feature = "zstd"))]
+    fs::read(path)
+}
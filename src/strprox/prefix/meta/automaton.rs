@@ -0,0 +1,128 @@
+use std::{
+    cmp::{min, Reverse},
+    collections::HashMap,
+};
+
+use super::{MetaAutocompleter, NodeID, QueryOptions, SSS, UUU};
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Returns the prefix edit distance from `query` of each stored string within `max_edits` edits,
+    /// with the node of its closest prefix, and whether `exhausted` stopped the search early
+    ///
+    /// This simulates a Levenshtein automaton on the trie by descending it with a row of edit distances per node,
+    /// where `matches` decides whether a query character matches a stored character without an edit,
+    /// and skips the descendants of nodes whose rows all exceed `max_edits`.
+    /// `exhausted` is called before visiting each node below the root
+    pub(super) fn automaton_distances(
+        &self,
+        query: &[char],
+        max_edits: usize,
+        matches: impl Fn(char, char) -> bool,
+        mut exhausted: impl FnMut() -> bool,
+    ) -> (HashMap<SSS, (usize, NodeID)>, bool) {
+        let nodes = &self.trie.nodes;
+        // the prefix edit distance of each string found so far with the node it was found at
+        let mut distances = HashMap::<SSS, (usize, NodeID)>::new();
+        if nodes.is_empty() {
+            return (distances, false);
+        }
+        let mut add = |range: std::ops::Range<SSS>, distance: usize, id: NodeID| {
+            for index in range {
+                let known = distances.entry(index).or_insert((distance, id));
+                if distance < known.0 {
+                    *known = (distance, id);
+                }
+            }
+        };
+        let root_row: Vec<usize> = (0..=query.len()).collect();
+        if query.len() <= max_edits {
+            add(nodes[0].string_range.clone(), query.len(), 0);
+        }
+        // rows of edit distances and the least distance of the whole query to a prefix, for each ancestor by depth
+        let mut rows = vec![root_row];
+        let mut best = vec![query.len()];
+        // nodes are stored in pre-order, so each node comes right after its ancestors
+        let mut id = 1;
+        while id < nodes.len() {
+            if exhausted() {
+                return (distances, true);
+            }
+            let node = &nodes[id];
+            let depth = node.depth as usize;
+            rows.truncate(depth);
+            best.truncate(depth);
+            let previous = &rows[depth - 1];
+            let mut row = Vec::with_capacity(previous.len());
+            row.push(previous[0] + 1);
+            for (i, &query_char) in query.iter().enumerate() {
+                let substitution = previous[i] + !matches(query_char, node.character) as usize;
+                row.push(min(substitution, min(previous[i + 1], row[i]) + 1));
+            }
+            if row.iter().all(|&distance| distance > max_edits) {
+                // no descendant can be closer, so skip them
+                id = node.descendant_range.end as usize;
+                continue;
+            }
+            let distance = row[query.len()];
+            let ancestor_best = best[depth - 1];
+            if distance <= max_edits && distance < ancestor_best {
+                add(node.string_range.clone(), distance, id);
+            }
+            best.push(min(distance, ancestor_best));
+            rows.push(row);
+            id += 1;
+        }
+        (distances, false)
+    }
+    /// Adds the indices of the top `requested` strings for `query` for which `filter` returns true to `indices` with the nodes of their closest prefixes,
+    /// found with `automaton_distances` instead of matching sets, and returns whether the search stopped early
+    ///
+    /// The edit budget is raised one edit at a time until enough strings are found, so that close matches
+    /// don't pay for descending the trie as far as distant ones. Each visited node counts as a matching for `options.max_matchings`
+    pub(super) fn search_automaton(
+        &self,
+        query: &str,
+        requested: usize,
+        mut filter: impl FnMut(&str) -> bool,
+        options: &QueryOptions,
+        indices: &mut HashMap<SSS, NodeID>,
+    ) -> bool {
+        let query: Vec<char> = query.chars().collect();
+        // every string is within the length of the query, since the empty prefix is
+        let max_edits = self
+            .max_prefix_distance(query.len(), options)
+            .map_or(query.len(), |max_edits| min(max_edits, query.len()));
+        let strict_prefix: String = query[..min(options.strict_prefix, query.len())]
+            .iter()
+            .collect();
+        let mut filter = |string: &str| string.starts_with(&strict_prefix) && filter(string);
+
+        let mut visited = 0;
+        let mut found = Vec::new();
+        let mut truncated = false;
+        for budget in 0..=max_edits {
+            let (distances, stopped) = self.automaton_distances(
+                &query,
+                budget,
+                |a, b| a == b,
+                || {
+                    visited += 1;
+                    options.exhausted(visited)
+                },
+            );
+            found = distances
+                .into_iter()
+                .filter(|&(index, _)| filter(self.get(index)))
+                .collect();
+            truncated = stopped;
+            if found.len() >= requested || stopped {
+                break;
+            }
+        }
+        // strings are sorted, so ordering by index is the same as lexicographical order
+        found.sort_by_key(|&(index, (distance, _))| (distance, Reverse(self.weight(index)), index));
+        found.truncate(requested);
+        indices.extend(found.into_iter().map(|(index, (_, id))| (index, id)));
+        truncated
+    }
+}
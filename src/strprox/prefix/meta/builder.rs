@@ -0,0 +1,289 @@
+use std::sync::Arc;
+
+#[cfg(feature = "unicode-segmentation")]
+use super::graphemes::{GraphemeOverflow, GraphemeTable};
+#[cfg(feature = "unicode")]
+use super::NormalizationForm;
+use super::{
+    Analyzer, CaseFolding, CharFilter, MetaAutocompleter, Normalizer, ThresholdPolicy, TreeString,
+    DEFAULT_CACHE_SIZE,
+};
+
+/// Stands in for the table of grapheme clusters when they can't be compared
+#[cfg(not(feature = "unicode-segmentation"))]
+enum GraphemeTable {}
+/// Stands in for the error from encoding too many grapheme clusters when they can't be compared
+#[cfg(not(feature = "unicode-segmentation"))]
+#[derive(Debug)]
+enum GraphemeOverflow {}
+
+/// How strings that occur more than once in the source are stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupPolicy {
+    /// Keep one copy of each string
+    #[default]
+    Dedup,
+    /// Keep one copy of each string with the number of times it occurred
+    Count,
+    /// Same as `Count`, but also use the counts as weights
+    CountAsWeight,
+}
+
+/// Collects the options of a `MetaAutocompleter` in one place before building it
+#[derive(Clone)]
+pub struct MetaAutocompleterBuilder {
+    normalizer: Normalizer,
+    /// Whether results have the strings from before normalization
+    keep_originals: bool,
+    /// Whether strings are compared by extended grapheme clusters instead of characters
+    #[cfg(feature = "unicode-segmentation")]
+    graphemes: bool,
+    dedup: DedupPolicy,
+    threshold: Option<ThresholdPolicy>,
+    min_fuzzy_len: usize,
+    cache_size: usize,
+    /// Number of best strings to precompute per node, or 0 to not precompute them
+    top: usize,
+}
+
+impl Default for MetaAutocompleterBuilder {
+    fn default() -> Self {
+        Self {
+            normalizer: Default::default(),
+            keep_originals: false,
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: false,
+            dedup: Default::default(),
+            threshold: None,
+            min_fuzzy_len: 0,
+            cache_size: DEFAULT_CACHE_SIZE,
+            top: 0,
+        }
+    }
+}
+
+impl MetaAutocompleterBuilder {
+    /// Returns a builder with the same defaults as `MetaAutocompleter::new`
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Lowercases stored strings and queries according to `case_folding` so that they match regardless of case
+    pub fn case_folding(mut self, case_folding: CaseFolding) -> Self {
+        self.normalizer.case_folding = case_folding;
+        self
+    }
+    /// Drops or canonicalizes whitespace, hyphens and punctuation in stored strings and queries according to `filter`,
+    /// so that "e-mail" can match "email"
+    pub fn char_filter(mut self, filter: CharFilter) -> Self {
+        self.normalizer.char_filter = filter;
+        self
+    }
+    /// Converts stored strings and queries to the Unicode normalization `form` before case folding
+    #[cfg(feature = "unicode")]
+    pub fn unicode_normalization(mut self, form: NormalizationForm) -> Self {
+        self.normalizer.form = Some(form);
+        self
+    }
+    /// Compares strings by extended grapheme clusters instead of characters, so that an emoji with modifiers
+    /// or a Hangul syllable made of jamo is a single edit
+    ///
+    /// The trie stores a private use character for each cluster of several characters, so results always have the original strings
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn graphemes(mut self, enabled: bool) -> Self {
+        self.graphemes = enabled;
+        self
+    }
+    /// Applies `analyzer` to stored strings and queries after the other normalization
+    ///
+    /// The analyzer can't be serialized or archived, so neither can the autocompleter
+    pub fn analyzer(mut self, analyzer: impl Analyzer + 'static) -> Self {
+        self.normalizer.analyzer = Some(Arc::new(analyzer));
+        self
+    }
+    /// Keeps the stored strings from before normalization to return them in results,
+    /// such as to display the original casing when folding case
+    ///
+    /// If several strings have the same normalized string, the first of them in the source is kept
+    pub fn keep_originals(mut self, enabled: bool) -> Self {
+        self.keep_originals = enabled;
+        self
+    }
+    /// Applies `normalize` to stored strings and queries after case folding and filtering separators
+    ///
+    /// `normalize` should give the same string when applied again, since queries may be normalized more than once.
+    /// Functions can't be serialized or archived, so neither can the autocompleter
+    pub fn normalization(mut self, normalize: fn(&str) -> String) -> Self {
+        self.normalizer.custom = Some(normalize);
+        self
+    }
+    /// Sets how duplicate strings are stored, which only applies to `build`
+    pub fn dedup_policy(mut self, policy: DedupPolicy) -> Self {
+        self.dedup = policy;
+        self
+    }
+    /// Limits the prefix edit distance of results by `policy`
+    pub fn threshold(mut self, policy: ThresholdPolicy) -> Self {
+        self.threshold = Some(policy);
+        self
+    }
+    /// Only completes queries shorter than `len` characters with strings that have them as an exact prefix
+    pub fn min_fuzzy_len(mut self, len: usize) -> Self {
+        self.min_fuzzy_len = len;
+        self
+    }
+    /// Sets the maximum number of query prefixes that `prune` keeps in a cache without its own capacity
+    pub fn cache_size(mut self, size: usize) -> Self {
+        self.cache_size = size;
+        self
+    }
+    /// Precomputes the best `k` strings of every node (see `MetaAutocompleter::precompute_top`)
+    pub fn precompute_top(mut self, k: usize) -> Self {
+        self.top = k;
+        self
+    }
+    /// Builds an autocompleter over `source` (does not copy strings that are already normalized)
+    ///
+    /// Panics if grapheme clusters are compared and `source` has more distinct clusters than `try_build` can encode
+    pub fn build<'stored>(
+        &self,
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+    ) -> MetaAutocompleter<'stored> {
+        self.build_encoded(source)
+            .expect("too many distinct grapheme clusters to encode")
+    }
+    /// Same as `build`, but returns an error if `source` has more distinct clusters of several characters
+    /// than there are private use characters to encode them
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn try_build<'stored>(
+        &self,
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+    ) -> Result<MetaAutocompleter<'stored>, GraphemeOverflow> {
+        self.build_encoded(source)
+    }
+    fn build_encoded<'stored>(
+        &self,
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+    ) -> Result<MetaAutocompleter<'stored>, GraphemeOverflow> {
+        let mut originals = Vec::new();
+        let mut table = self.grapheme_table();
+        let strings = source
+            .into_iter()
+            .map(|string| self.prepare(string, &mut originals, &mut table))
+            .collect::<Result<Vec<TreeString<'stored>>, _>>()?;
+        let autocompleter = match self.dedup {
+            DedupPolicy::Dedup => MetaAutocompleter::new(strings.len(), strings),
+            DedupPolicy::Count => MetaAutocompleter::new_counted(strings.len(), strings, false),
+            DedupPolicy::CountAsWeight => {
+                MetaAutocompleter::new_counted(strings.len(), strings, true)
+            }
+        };
+        Ok(self.finish(autocompleter, originals, table))
+    }
+    /// Builds an autocompleter over `source` with weights (does not copy strings that are already normalized)
+    ///
+    /// Weights of strings that are the same after normalization are summed regardless of the dedup policy.
+    /// Panics if grapheme clusters are compared and `source` has more distinct clusters than `try_build_weighted` can encode
+    pub fn build_weighted<'stored>(
+        &self,
+        source: impl IntoIterator<Item = (TreeString<'stored>, u32)>,
+    ) -> MetaAutocompleter<'stored> {
+        self.build_weighted_encoded(source)
+            .expect("too many distinct grapheme clusters to encode")
+    }
+    /// Same as `build_weighted`, but returns an error if `source` has more distinct clusters of several characters
+    /// than there are private use characters to encode them
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn try_build_weighted<'stored>(
+        &self,
+        source: impl IntoIterator<Item = (TreeString<'stored>, u32)>,
+    ) -> Result<MetaAutocompleter<'stored>, GraphemeOverflow> {
+        self.build_weighted_encoded(source)
+    }
+    fn build_weighted_encoded<'stored>(
+        &self,
+        source: impl IntoIterator<Item = (TreeString<'stored>, u32)>,
+    ) -> Result<MetaAutocompleter<'stored>, GraphemeOverflow> {
+        let mut originals = Vec::new();
+        let mut table = self.grapheme_table();
+        let pairs = source
+            .into_iter()
+            .map(|(string, weight)| Ok((self.prepare(string, &mut originals, &mut table)?, weight)))
+            .collect::<Result<Vec<(TreeString<'stored>, u32)>, _>>()?;
+        let autocompleter = MetaAutocompleter::new_weighted(pairs.len(), pairs);
+        Ok(self.finish(autocompleter, originals, table))
+    }
+    /// Returns the table to encode grapheme clusters with, if they're compared
+    #[cfg(feature = "unicode-segmentation")]
+    fn grapheme_table(&self) -> Option<GraphemeTable> {
+        self.graphemes.then(GraphemeTable::default)
+    }
+    #[cfg(not(feature = "unicode-segmentation"))]
+    fn grapheme_table(&self) -> Option<GraphemeTable> {
+        None
+    }
+    /// Returns the normalized `string` to store, keeping the original in `originals` if needed
+    fn prepare<'stored>(
+        &self,
+        string: TreeString<'stored>,
+        originals: &mut Vec<TreeString<'stored>>,
+        table: &mut Option<GraphemeTable>,
+    ) -> Result<TreeString<'stored>, GraphemeOverflow> {
+        if self.keep_originals || table.is_some() {
+            originals.push(string.clone());
+        }
+        let string = self.normalizer.apply_stored(string);
+        #[cfg(feature = "unicode-segmentation")]
+        if let Some(table) = table {
+            return table.encode_stored(string);
+        }
+        Ok(string)
+    }
+    /// Sets up the `autocompleter` built from prepared strings
+    fn finish<'stored>(
+        &self,
+        mut autocompleter: MetaAutocompleter<'stored>,
+        originals: Vec<TreeString<'stored>>,
+        table: Option<GraphemeTable>,
+    ) -> MetaAutocompleter<'stored> {
+        #[cfg(feature = "unicode-segmentation")]
+        {
+            autocompleter.graphemes = table;
+        }
+        #[cfg(not(feature = "unicode-segmentation"))]
+        let _ = table;
+        autocompleter.originals = self.originals(&autocompleter, originals);
+        self.configure(autocompleter)
+    }
+    /// Returns the first of the `originals` for each string stored in `autocompleter`
+    fn originals<'stored>(
+        &self,
+        autocompleter: &MetaAutocompleter<'stored>,
+        originals: Vec<TreeString<'stored>>,
+    ) -> Vec<TreeString<'stored>> {
+        if originals.is_empty() {
+            return Vec::new();
+        }
+        let mut first: Vec<Option<TreeString<'stored>>> = vec![None; autocompleter.len()];
+        for original in originals {
+            // the autocompleter doesn't have the normalizer yet, so it's applied here (and the clusters are encoded by `position`)
+            if let Some(index) = autocompleter.position(&self.normalizer.apply(&original)) {
+                first[index as usize].get_or_insert(original);
+            }
+        }
+        first.into_iter().map(Option::unwrap_or_default).collect()
+    }
+    /// Applies the options that don't affect how the index is built
+    fn configure<'stored>(
+        &self,
+        mut autocompleter: MetaAutocompleter<'stored>,
+    ) -> MetaAutocompleter<'stored> {
+        autocompleter.normalizer = self.normalizer.clone();
+        autocompleter.threshold = self.threshold;
+        autocompleter.min_fuzzy_len = self.min_fuzzy_len;
+        autocompleter.cache_size = self.cache_size;
+        if self.top > 0 {
+            autocompleter.precompute_top(self.top);
+        }
+        autocompleter
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{DeserializedAutocompleter, MetaAutocompleter, SSS, UUU};
+
+/// Version of the encoding written by `to_bytes`, which is incremented whenever the serialized fields change
+pub const BYTES_VERSION: u32 = 1;
+
+/// Error from decoding bytes that aren't an autocompleter encoded by `to_bytes`
+#[derive(Debug)]
+pub enum BytesError {
+    Postcard(postcard::Error),
+    /// The bytes were encoded with a version other than `BYTES_VERSION`
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for BytesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Postcard(error) => write!(f, "{}", error),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported encoding version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BytesError {}
+
+impl From<postcard::Error> for BytesError {
+    fn from(error: postcard::Error) -> Self {
+        Self::Postcard(error)
+    }
+}
+
+impl<'stored, P: Serialize> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Returns the autocompleter encoded with the stable wire format of postcard 1.x
+    ///
+    /// The encoding is `BYTES_VERSION` as a varint followed by the serde fields of the autocompleter,
+    /// including the settings of its normalizer, so autocompleters with a custom normalization function or analyzer
+    /// are rejected with `BytesError::Postcard`.
+    /// Entries of hash maps are encoded in no particular order, so the same autocompleter can be encoded to different bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BytesError> {
+        let mut bytes = postcard::to_allocvec(&BYTES_VERSION)?;
+        bytes.extend(postcard::to_allocvec(self)?);
+        Ok(bytes)
+    }
+}
+
+impl<P: DeserializeOwned> MetaAutocompleter<'static, UUU, SSS, P> {
+    /// Decodes an autocompleter encoded by `to_bytes`, which doesn't borrow from `bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BytesError> {
+        let (version, rest) = postcard::take_from_bytes::<u32>(bytes)?;
+        if version != BYTES_VERSION {
+            return Err(BytesError::UnsupportedVersion(version));
+        }
+        Ok(postcard::from_bytes::<DeserializedAutocompleter<P>>(rest)?.into_inner())
+    }
+}
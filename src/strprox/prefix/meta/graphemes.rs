@@ -0,0 +1,126 @@
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::TreeString;
+
+/// Private use character that stands for clusters in queries that aren't in any stored string
+const UNKNOWN: u32 = 0xF0000;
+/// Last private use character
+const LAST: u32 = 0x10FFFD;
+
+/// Error from encoding more distinct grapheme clusters of several characters than there are private use characters after `UNKNOWN`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphemeOverflow;
+
+impl fmt::Display for GraphemeOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "more than {} distinct grapheme clusters to encode",
+            LAST - UNKNOWN
+        )
+    }
+}
+
+impl std::error::Error for GraphemeOverflow {}
+
+/// Encodes each extended grapheme cluster of more than one character as a single private use character,
+/// so that the trie and edit distances count clusters instead of characters
+///
+/// Stored strings shouldn't have characters from the supplementary private use areas, which may be confused with clusters
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct GraphemeTable {
+    /// Cluster |-> its character
+    pub(super) codes: HashMap<String, char>,
+}
+
+impl GraphemeTable {
+    /// Returns whether every cluster of `string` is a single character
+    fn is_single_chars(string: &str) -> bool {
+        string.graphemes(true).count() == string.chars().count()
+    }
+    /// Returns the character of `cluster`, adding it to the table if it's new,
+    /// or an error if every private use character already stands for another cluster
+    fn code(&mut self, cluster: &str) -> Result<char, GraphemeOverflow> {
+        let mut chars = cluster.chars();
+        if let (Some(character), None) = (chars.next(), chars.next()) {
+            return Ok(character);
+        }
+        if let Some(&code) = self.codes.get(cluster) {
+            return Ok(code);
+        }
+        // every character between `UNKNOWN` and `LAST` is valid, including the noncharacters between the two areas
+        let code = UNKNOWN + 1 + self.codes.len() as u32;
+        if code > LAST {
+            return Err(GraphemeOverflow);
+        }
+        let code = char::from_u32(code).unwrap();
+        self.codes.insert(cluster.to_string(), code);
+        Ok(code)
+    }
+    /// Returns the encoded stored `string`, adding its clusters to the table (borrows it if there's nothing to encode)
+    pub(super) fn encode_stored<'stored>(
+        &mut self,
+        string: TreeString<'stored>,
+    ) -> Result<TreeString<'stored>, GraphemeOverflow> {
+        if Self::is_single_chars(&string) {
+            return Ok(string);
+        }
+        let encoded: Result<String, _> = string
+            .graphemes(true)
+            .map(|cluster| self.code(cluster))
+            .collect();
+        Ok(Cow::Owned(encoded?))
+    }
+    /// Returns the clusters of the table by their characters, to decode strings encoded with it
+    pub(super) fn clusters(&self) -> HashMap<char, &str> {
+        self.codes
+            .iter()
+            .map(|(cluster, &code)| (code, cluster.as_str()))
+            .collect()
+    }
+    /// Returns `string` with the characters of `clusters` replaced by the clusters they stand for
+    /// (borrows it if it has none of them)
+    pub(super) fn decode<'a>(
+        clusters: &HashMap<char, &str>,
+        string: TreeString<'a>,
+    ) -> TreeString<'a> {
+        if !string
+            .chars()
+            .any(|character| clusters.contains_key(&character))
+        {
+            return string;
+        }
+        let mut decoded = String::with_capacity(string.len());
+        for character in string.chars() {
+            match clusters.get(&character) {
+                Some(cluster) => decoded.push_str(cluster),
+                None => decoded.push(character),
+            }
+        }
+        Cow::Owned(decoded)
+    }
+    /// Returns the encoded `query` (borrows it if there's nothing to encode)
+    pub(super) fn encode<'q>(&self, query: &'q str) -> Cow<'q, str> {
+        if Self::is_single_chars(query) {
+            return Cow::Borrowed(query);
+        }
+        let unknown = char::from_u32(UNKNOWN).unwrap();
+        Cow::Owned(
+            query
+                .graphemes(true)
+                .map(|cluster| {
+                    let mut chars = cluster.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(character), None) => character,
+                        _ => self.codes.get(cluster).copied().unwrap_or(unknown),
+                    }
+                })
+                .collect(),
+        )
+    }
+}
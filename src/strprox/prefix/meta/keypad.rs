@@ -0,0 +1,61 @@
+use std::cmp::Reverse;
+
+use super::{MetaAutocompleter, SSS, UUU};
+use crate::MeasuredPrefix;
+
+/// Returns the digit of the phone keypad key with `letter` (as in ITU E.161, so "2" has "abc" and "7" has "pqrs"),
+/// or None if `letter` isn't a Latin letter
+pub fn keypad_digit(letter: char) -> Option<char> {
+    match letter.to_ascii_lowercase() {
+        'a'..='c' => Some('2'),
+        'd'..='f' => Some('3'),
+        'g'..='i' => Some('4'),
+        'j'..='l' => Some('5'),
+        'm'..='o' => Some('6'),
+        'p'..='s' => Some('7'),
+        't'..='v' => Some('8'),
+        'w'..='z' => Some('9'),
+        _ => None,
+    }
+}
+
+/// Returns whether the `query` character matches the stored `character`, where digits also match the letters of their keys
+fn keypad_matches(query: char, character: char) -> bool {
+    query == character || keypad_digit(character) == Some(query)
+}
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Returns the top `requested` stored strings within a prefix edit distance of `max_edits` from `query`
+    /// typed on a phone keypad, where each digit from 2 to 9 matches the letters of its key without an edit
+    ///
+    /// Results are sorted by distance, then descending weight and lexicographical order.
+    /// This descends the trie with a row of edit distances per node (see `SearchAlgorithm::Automaton`) instead of using matching sets,
+    /// so it's slower than `autocomplete` and doesn't use a cache
+    pub fn autocomplete_keypad(
+        &self,
+        query: &str,
+        requested: usize,
+        max_edits: usize,
+    ) -> Vec<MeasuredPrefix> {
+        let query: Vec<char> = self.normalized(query).chars().collect();
+        if requested == 0 {
+            return vec![];
+        }
+        let (distances, _) = self.automaton_distances(&query, max_edits, keypad_matches, || false);
+
+        let mut result: Vec<(SSS, usize)> = distances
+            .into_iter()
+            .map(|(index, (distance, _))| (index, distance))
+            .collect();
+        // strings are sorted, so ordering by index is the same as lexicographical order
+        result.sort_by_key(|&(index, distance)| (distance, Reverse(self.weight(index)), index));
+        result.truncate(requested);
+        result
+            .into_iter()
+            .map(|(index, prefix_distance)| MeasuredPrefix {
+                string: self.display(index).to_string(),
+                prefix_distance,
+            })
+            .collect()
+    }
+}
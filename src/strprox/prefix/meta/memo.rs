@@ -0,0 +1,138 @@
+use std::{
+    collections::BTreeMap,
+    collections::HashMap,
+    mem::{size_of, size_of_val},
+};
+
+use crate::MeasuredPrefix;
+
+use super::{Cache, MetaAutocompleter, QueryOptions, SSS, UUU};
+
+/// Normalized query, maximum prefix edit distance, number of requested results
+/// and generation of the autocompleter of a memoized query
+type ResultKey = (String, Option<usize>, usize, u64);
+
+/// Results of whole queries, which repeated queries return without assembling matching sets
+#[derive(Default)]
+pub(super) struct ResultCache {
+    /// Results of each query with the value of the cache's clock at its last lookup
+    results: HashMap<ResultKey, (Vec<MeasuredPrefix>, u64)>,
+    /// Queries by their last lookup
+    prio: BTreeMap<u64, ResultKey>,
+    /// Estimated number of bytes of the queries and their results
+    pub(super) bytes: usize,
+    /// Number of queries answered with memoized results
+    pub(super) hits: u64,
+}
+
+impl ResultCache {
+    /// Returns the number of memoized queries
+    pub(super) fn len(&self) -> usize {
+        self.results.len()
+    }
+    /// Removes the results of every query, keeping the number of hits
+    pub(super) fn clear(&mut self) {
+        self.results.clear();
+        self.prio.clear();
+        self.bytes = 0;
+    }
+    /// Removes the results of the queries that start with `prefix`
+    pub(super) fn invalidate_prefix(&mut self, prefix: &str) {
+        let stale: Vec<ResultKey> = self
+            .results
+            .keys()
+            .filter(|(query, ..)| query.starts_with(prefix))
+            .cloned()
+            .collect();
+        for key in stale {
+            self.remove(&key);
+        }
+    }
+    /// Evicts the least recently looked up queries until there are at most `max` and they take at most `budget` bytes,
+    /// returning the number of evicted queries
+    pub(super) fn evict(&mut self, max: usize, budget: Option<usize>) -> u64 {
+        let mut evicted = 0;
+        while self.results.len() > max || budget.is_some_and(|budget| self.bytes > budget) {
+            let Some((_, key)) = self.prio.pop_first() else {
+                break;
+            };
+            self.remove(&key);
+            evicted += 1;
+        }
+        evicted
+    }
+    fn remove(&mut self, key: &ResultKey) {
+        if let Some((results, visited)) = self.results.remove(key) {
+            self.prio.remove(&visited);
+            self.bytes -= bytes(key, &results);
+        }
+    }
+}
+
+/// Returns the estimated number of bytes of a memoized query and its results
+fn bytes(key: &ResultKey, results: &[MeasuredPrefix]) -> usize {
+    let strings: usize = results.iter().map(|result| result.string.len()).sum();
+    size_of::<ResultKey>() + key.0.len() + size_of_val(results) + strings
+}
+
+impl Cache<'_> {
+    /// Enables or disables memoizing the results of whole queries by `autocomplete`,
+    /// so that repeated queries, such as from several widgets showing the same results, skip assembling matching sets
+    ///
+    /// Memoized results are pruned with the same capacity and byte budget as the cached prefixes,
+    /// and disabling memoization removes them
+    pub fn set_memoize_results(&mut self, enabled: bool) {
+        match (enabled, &self.results) {
+            (true, None) => self.results = Some(Default::default()),
+            (false, Some(_)) => self.results = None,
+            _ => {}
+        }
+    }
+    /// Returns whether the results of whole queries are memoized
+    pub fn memoizes_results(&self) -> bool {
+        self.results.is_some()
+    }
+    /// Returns the memoized results for `key`, marking them as the most recently used
+    fn memoized(&mut self, key: &ResultKey) -> Option<Vec<MeasuredPrefix>> {
+        let memo = self.results.as_mut()?;
+        let (results, visited) = memo.results.get_mut(key)?;
+        self.clock += 1;
+        memo.prio.remove(visited);
+        *visited = self.clock;
+        memo.prio.insert(self.clock, key.clone());
+        memo.hits += 1;
+        Some(results.clone())
+    }
+    /// Memoizes `results` for `key` if memoization is enabled
+    fn memoize(&mut self, key: ResultKey, results: &[MeasuredPrefix]) {
+        let Some(memo) = &mut self.results else {
+            return;
+        };
+        self.clock += 1;
+        memo.remove(&key);
+        memo.bytes += bytes(&key, results);
+        memo.prio.insert(self.clock, key.clone());
+        memo.results.insert(key, (results.to_vec(), self.clock));
+    }
+}
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Returns the memoized results of `autocomplete` for `query` from `cache`, or computes and memoizes them
+    pub(super) fn autocomplete_memoized(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        let normalized = self.normalized(query).into_owned();
+        let options = QueryOptions::default();
+        let max_edits = self.max_prefix_distance(normalized.chars().count(), &options);
+        let key = (normalized, max_edits, requested, self.generation);
+        if let Some(results) = cache.memoized(&key) {
+            return results;
+        }
+        let results = self.autocomplete_with_options(query, requested, |_| true, &options, cache);
+        cache.memoize(key, &results);
+        results
+    }
+}
@@ -0,0 +1,65 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+use yoke::Yoke;
+
+use super::{ArchiveError, IndexFileError, MetaAutocompleter};
+
+/// Autocompleter loaded from a memory-mapped archive, which borrows its stored strings from the mapping
+pub type MappedAutocompleter = Yoke<MetaAutocompleter<'static>, Mmap>;
+
+impl MetaAutocompleter<'static> {
+    /// Maps the file at `path` written from `to_archive` and loads the autocompleter from it,
+    /// so the stored strings are borrowed from the mapping instead of being copied into memory
+    ///
+    /// Loading reads the whole file once to validate it, but afterwards the operating system
+    /// can drop the pages of the strings from memory and read them back when queries touch them.
+    /// `load_mmap_unchecked` skips the validation of trusted files, so their strings aren't read until queries touch them
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the returned autocompleter is alive,
+    /// since its strings would change underneath it or stop being valid UTF-8
+    pub unsafe fn load_mmap(path: impl AsRef<Path>) -> Result<MappedAutocompleter, IndexFileError> {
+        // SAFETY: the caller upholds the requirements of `load_mmap`
+        unsafe {
+            map(path.as_ref(), |bytes| {
+                MetaAutocompleter::from_archive(bytes)
+            })
+        }
+    }
+    /// Same as `load_mmap`, but loads the archive with `from_archive_unchecked`, which only reads the nodes
+    /// and the smaller sections, so the pages of the stored strings are only read once queries touch them
+    ///
+    /// The nodes are still copied out of the file to rebuild the inverted index
+    ///
+    /// # Safety
+    ///
+    /// Same as `load_mmap`, and the file must have been written by `to_archive` (such as by `strprox-build`)
+    /// without being corrupted since, since strings that aren't UTF-8 are undefined behavior
+    pub unsafe fn load_mmap_unchecked(
+        path: impl AsRef<Path>,
+    ) -> Result<MappedAutocompleter, IndexFileError> {
+        // SAFETY: the caller upholds the requirements of `from_archive_unchecked` and `load_mmap`
+        unsafe {
+            map(path.as_ref(), |bytes| {
+                MetaAutocompleter::from_archive_unchecked(bytes)
+            })
+        }
+    }
+}
+
+/// Maps the file at `path` and loads the autocompleter from it with `load`
+///
+/// # Safety
+///
+/// The file must not be modified or truncated while the returned autocompleter is alive
+unsafe fn map(
+    path: &Path,
+    load: impl for<'a> FnOnce(&'a [u8]) -> Result<MetaAutocompleter<'a>, ArchiveError>,
+) -> Result<MappedAutocompleter, IndexFileError> {
+    let file = File::open(path)?;
+    // SAFETY: the caller guarantees the file isn't changed while it's mapped
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(Yoke::try_attach_to_cart(mmap, |bytes: &[u8]| load(bytes))?)
+}
@@ -1,21 +1,30 @@
 use std::{
     borrow::{Borrow, Cow},
-    cmp::{max, min, Ordering},
+    cmp::{max, min, Ordering, Reverse},
     collections::{
         btree_map::{self, Entry},
         hash_map, BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet,
     },
+    hash::Hash,
     marker::PhantomData,
     ops::Range,
-    sync::{Mutex, RwLock},
+    sync::{
+        atomic::{self, AtomicBool, AtomicU64},
+        Arc, RwLock,
+    },
     time::Instant,
 };
 
 use super::{FromStrings, MeasuredPrefix};
+use crate::MeasuredString;
 use crate::{
-    levenshtein::{self, edit_distance},
+    levenshtein::{self, EditCosts, Metric, QGramFilter},
     Autocompleter,
 };
+use crate::{
+    AlignedPrefix, ConfidencePrefix, CountedPrefix, DistancedPrefix, MatchedPrefix, MeasuredEntry,
+    MeasuredIndex, MetricPrefix, PrefixGroup, ScoredPrefix, WeightedPrefix,
+};
 
 use debug_print::debug_println;
 use polonius_the_crab::{polonius, polonius_return};
@@ -25,6 +34,51 @@ use slab::Slab;
 use yoke::{Yoke, Yokeable};
 
 //mod compact_tree;
+mod analysis;
+#[cfg(feature = "archive")]
+mod archive;
+mod automaton;
+mod builder;
+#[cfg(feature = "postcard")]
+mod bytes;
+#[cfg(feature = "unicode-segmentation")]
+mod graphemes;
+mod keypad;
+mod memo;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "serde")]
+mod owned;
+#[cfg(feature = "serde")]
+mod persist;
+mod records;
+mod shared;
+mod subsequence;
+
+#[cfg(feature = "unicode")]
+pub use analysis::strip_accents;
+pub use analysis::{not_punctuation, Analyzer, Pipeline};
+#[cfg(feature = "archive")]
+pub use archive::{ArchiveError, IndexFileError, ARCHIVE_MAGIC, ARCHIVE_VERSION};
+pub use builder::{DedupPolicy, MetaAutocompleterBuilder};
+#[cfg(feature = "postcard")]
+pub use bytes::{BytesError, BYTES_VERSION};
+#[cfg(feature = "unicode-segmentation")]
+pub use graphemes::GraphemeOverflow;
+pub use keypad::keypad_digit;
+#[cfg(feature = "mmap")]
+pub use mmap::MappedAutocompleter;
+#[cfg(feature = "serde")]
+pub use owned::DeserializedAutocompleter;
+#[cfg(feature = "serde")]
+pub use persist::CacheSnapshot;
+#[cfg(feature = "csv")]
+pub use records::load_csv;
+#[cfg(feature = "jsonl")]
+pub use records::load_jsonl;
+pub use records::{load_lines, LoadError, Record};
+pub use shared::{SharedCache, DEFAULT_SHARD_COUNT};
+pub use subsequence::{HybridWeights, SubsequenceScoring};
 
 /// Implements "Matching-Based Method for Error-Tolerant Autocompletion" (META) from https://doi.org/10.14778/2977797.2977808
 
@@ -61,6 +115,18 @@ impl Node<UUU, SSS> {
     fn first_descendant_id(&self) -> usize {
         self.descendant_range.start as usize
     }
+    /// Returns the last character of the node's prefix ('\0' for the root)
+    pub fn character(&self) -> char {
+        self.character
+    }
+    /// Returns the length of the node's prefix in characters
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+    /// Returns the number of stored strings with the node's prefix
+    pub fn string_count(&self) -> usize {
+        self.string_range.len()
+    }
 }
 
 pub type TreeString<'stored> = Cow<'stored, str>;
@@ -107,14 +173,76 @@ impl<'stored> Trie<'stored, UUU, SSS> {
         // this shouldn't be able to panic from the public API
         self.nodes.first().unwrap()
     }
+    /// Returns the prefix associated with `node`
+    fn prefix(&self, node: &Node<UUU, SSS>) -> &str {
+        if node.string_range.is_empty() {
+            return "";
+        }
+        // all strings in the node's range share the prefix
+        let string: &str = &self.strings[node.string_range.start as usize];
+        let end = string
+            .char_indices()
+            .nth(node.depth as usize)
+            .map_or(string.len(), |(end, _)| end);
+        &string[..end]
+    }
+    /// Returns an iterator over the children of `node` in lexicographical order
+    fn children<'t>(&'t self, node: &Node<UUU, SSS>) -> impl Iterator<Item = &'t Node<UUU, SSS>> {
+        let mut id = node.first_descendant_id();
+        let end = node.descendant_range.end as usize;
+        std::iter::from_fn(move || {
+            if id < end {
+                let child = &self.nodes[id];
+                // the next sibling is after all descendants of the child
+                id = child.descendant_range.end as usize;
+                Some(child)
+            } else {
+                None
+            }
+        })
+    }
+    /// Returns the node whose prefix is exactly `prefix`, if any
+    fn descend(&self, prefix: &str) -> Option<&Node<UUU, SSS>> {
+        let mut node = self.nodes.first()?;
+        for character in prefix.chars() {
+            node = self
+                .children(node)
+                .find(|child| child.character == character)?;
+        }
+        Some(node)
+    }
+    /// Calls `visitor` with each node whose prefix starts with `prefix` and that prefix, in lexicographical order
+    pub fn visit_prefix(&self, prefix: &str, mut visitor: impl FnMut(&Node<UUU, SSS>, &str)) {
+        let Some(node) = self.descend(prefix) else {
+            return;
+        };
+        // descendants are stored after the node in pre-order
+        for descendant in &self.nodes[node.id()..node.descendant_range.end as usize] {
+            visitor(descendant, self.prefix(descendant));
+        }
+    }
+    /// Returns an iterator over the stored strings that start with `prefix` in lexicographical order
+    pub fn iter_strings_with_prefix<'t>(&'t self, prefix: &str) -> impl Iterator<Item = &'t str> {
+        self.descend(prefix)
+            .map_or(0..0, |node| node.string_range.clone())
+            .map(move |index| &*self.strings[index as usize])
+    }
+    /// Adds the strings at `indices` (with the prefix from `node`) that pass `filter` to `result` until it has `limit` strings,
+    /// returning whether the limit was reached
+    ///
+    /// `result` maps the index of each string to the id of the first node it was added from
     fn fill_results(
         &self,
         node: &Node<UUU, SSS>,
-        result: &mut HashSet<TreeString<'stored>>,
+        indices: impl IntoIterator<Item = SSS>,
+        result: &mut HashMap<SSS, NodeID>,
         limit: usize,
+        filter: &mut impl FnMut(&str) -> bool,
     ) -> bool {
-        for string_index in node.string_range.clone() {
-            result.insert(self.strings[string_index as usize].clone());
+        for string_index in indices {
+            if filter(&self.strings[string_index as usize]) {
+                result.entry(string_index).or_insert(node.id());
+            }
             if result.len() >= limit {
                 return true;
             }
@@ -257,6 +385,25 @@ impl<'stored> Trie<'stored, UUU, SSS> {
     }
 }
 
+/// Maximum number of strings of a node that `correct_with_qgrams` verifies one at a time instead of descending further
+const QGRAM_SCAN_STRINGS: usize = 16;
+
+/// Returns the row of edit distances between the prefixes of `query` and a string extended by `character`,
+/// given the `row` for the string
+fn next_lev_row(row: &[usize], query: &[char], character: char) -> Vec<usize> {
+    let mut next = Vec::with_capacity(row.len());
+    next.push(row[0] + 1);
+    for column in 1..=query.len() {
+        let diff = (query[column - 1] != character) as usize;
+        let distance = min(
+            row[column - 1] + diff,
+            min(row[column] + 1, next[column - 1] + 1),
+        );
+        next.push(distance);
+    }
+    next
+}
+
 /// Inverted index from META
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct InvertedIndex<UUU, SSS> {
@@ -313,48 +460,720 @@ use ptrie::Trie as PTrie;
 /// Structure that allows for autocompletion based on a string dataset
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Yokeable)]
-pub struct MetaAutocompleter<'stored, UUU = u8, SSS = u32> {
+pub struct MetaAutocompleter<'stored, UUU = u8, SSS = u32, P = ()> {
     #[cfg_attr(feature = "serde", serde(borrow))]
     pub trie: Trie<'stored, UUU, SSS>,
     inverted_index: InvertedIndex<UUU, SSS>,
+    /// Payloads of each stored string by its index, empty if the autocompleter was constructed without payloads
+    payloads: Vec<Vec<P>>,
+    /// Weights of each stored string by its index, empty if the autocompleter was constructed without weights
+    weights: Vec<u32>,
+    /// Number of times each stored string occurred in the source by its index, empty unless duplicates were counted
+    counts: Vec<u32>,
+    /// Strings before normalization by the index of their normalized string, empty unless the originals were kept
+    originals: Vec<TreeString<'stored>>,
+    /// Best strings of each node, empty unless precomputed
+    top: TopStrings<SSS>,
+    /// Limit on the prefix edit distance of results, unlimited if None
+    threshold: Option<ThresholdPolicy>,
+    /// Query prefix |-> indices of the strings pinned to the top of its results, in the order they were pinned
+    pins: BTreeMap<String, Vec<SSS>>,
+    /// Queries with fewer characters are only completed with strings that have them as an exact prefix
+    min_fuzzy_len: usize,
+    /// Normalization applied to queries, which the stored strings were built with
+    normalizer: Normalizer,
+    /// Characters that stand for extended grapheme clusters in the stored strings, if they're compared by clusters
+    #[cfg(feature = "unicode-segmentation")]
+    graphemes: Option<graphemes::GraphemeTable>,
+    /// Maximum number of query prefixes kept by `prune` in caches without their own capacity
+    cache_size: usize,
+    /// Identifies the strings and settings the autocompleter ranks with, changed by every mutation,
+    /// so that caches don't return results memoized before the change
+    #[cfg_attr(feature = "serde", serde(skip, default = "next_generation"))]
+    generation: u64,
+}
+
+/// Returns a generation that no autocompleter had before
+fn next_generation() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, atomic::Ordering::Relaxed)
+}
+
+/// Default maximum number of query prefixes kept in a cache by `prune`
+const DEFAULT_CACHE_SIZE: usize = 1000;
+
+/// How strings are lowercased so that they match regardless of case
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CaseFolding {
+    /// Strings are matched with their case
+    #[default]
+    None,
+    /// Only ASCII letters are lowercased
+    Ascii,
+    /// All letters are folded by Unicode full case folding, so "Straße" matches "STRASSE"
+    Unicode,
+}
+
+/// How whitespace, hyphens and other punctuation are treated so that differences in them don't use up the edit distance
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CharFilter {
+    /// Strings are matched with their separators
+    #[default]
+    None,
+    /// Each run of separators becomes a single space and leading or trailing separators are removed,
+    /// so "foo  bar" and "foo-bar" match "foo bar"
+    Collapse,
+    /// Separators are removed, so "e-mail" and "e mail" match "email"
+    Remove,
+}
+
+impl CharFilter {
+    /// Returns `string` with its separators filtered, borrowing it if it's unchanged
+    pub fn apply<'a>(&self, string: &'a str) -> Cow<'a, str> {
+        let is_separator = |c: char| c.is_whitespace() || !not_punctuation(c);
+        let filtered: String = match self {
+            Self::None => return Cow::Borrowed(string),
+            Self::Collapse => {
+                let words: Vec<&str> = string
+                    .split(is_separator)
+                    .filter(|word| !word.is_empty())
+                    .collect();
+                words.join(" ")
+            }
+            Self::Remove => string.chars().filter(|&c| !is_separator(c)).collect(),
+        };
+        if filtered == string {
+            Cow::Borrowed(string)
+        } else {
+            Cow::Owned(filtered)
+        }
+    }
+}
+
+/// Unicode normalization form, so that equivalent sequences of characters match
+/// (like "é" as one character or as "e" followed by a combining accent)
+#[cfg(feature = "unicode")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition
+    Nfc,
+    /// Compatibility composition, which also unifies characters like "ﬁ" and "fi"
+    Nfkc,
+}
+
+#[cfg(feature = "unicode")]
+impl NormalizationForm {
+    /// Returns `string` in this form, borrowing it if it's already in this form
+    pub fn apply<'a>(&self, string: &'a str) -> Cow<'a, str> {
+        use unicode_normalization::{is_nfc, is_nfkc, UnicodeNormalization};
+        match self {
+            Self::Nfc if !is_nfc(string) => Cow::Owned(string.nfc().collect()),
+            Self::Nfkc if !is_nfkc(string) => Cow::Owned(string.nfkc().collect()),
+            _ => Cow::Borrowed(string),
+        }
+    }
+}
+
+/// Normalization applied to stored strings when they're built and to queries before they're searched
+#[derive(Clone, Default)]
+pub struct Normalizer {
+    /// Unicode normalization form applied before case folding
+    #[cfg(feature = "unicode")]
+    pub form: Option<NormalizationForm>,
+    pub case_folding: CaseFolding,
+    /// Filtering of separators applied after case folding
+    pub char_filter: CharFilter,
+    /// Normalization applied after filtering separators, which should give the same string when applied again
+    pub custom: Option<fn(&str) -> String>,
+    /// Analysis applied last, which should also give the same string when applied again
+    pub analyzer: Option<Arc<dyn Analyzer>>,
+}
+
+impl Normalizer {
+    /// Returns the normalized `string`, borrowing it if it's already normalized
+    pub fn apply<'a>(&self, string: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(string);
+        #[cfg(feature = "unicode")]
+        if let Some(form) = self.form {
+            result = form.apply(string);
+        }
+        let lowercase = match self.case_folding {
+            CaseFolding::None => None,
+            CaseFolding::Ascii => Some(result.to_ascii_lowercase()),
+            CaseFolding::Unicode => Some(caseless::default_case_fold_str(&result)),
+        };
+        if let Some(lowercase) = lowercase
+            && lowercase != result
+        {
+            result = Cow::Owned(lowercase);
+        }
+        let filtered = match self.char_filter.apply(&result) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(filtered) => Some(filtered),
+        };
+        if let Some(filtered) = filtered {
+            result = Cow::Owned(filtered);
+        }
+        if let Some(custom) = self.custom {
+            let normalized = custom(&result);
+            if normalized != result {
+                result = Cow::Owned(normalized);
+            }
+        }
+        if let Some(analyzer) = &self.analyzer {
+            let analyzed = match analyzer.analyze(&result) {
+                Cow::Borrowed(_) => None,
+                Cow::Owned(analyzed) => Some(analyzed),
+            };
+            if let Some(analyzed) = analyzed {
+                result = Cow::Owned(analyzed);
+            }
+        }
+        result
+    }
+    /// Returns the normalized stored `string`, which stays borrowed if it's already normalized
+    fn apply_stored<'stored>(&self, string: TreeString<'stored>) -> TreeString<'stored> {
+        let normalized = match self.apply(&string) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(normalized) => Some(normalized),
+        };
+        normalized.map_or(string, Cow::Owned)
+    }
+}
+
+/// Settings of a `Normalizer` as they're serialized, which are the same with or without the unicode feature
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct NormalizerSettings {
+    /// 0 without a normalization form, 1 for NFC and 2 for NFKC
+    form: u8,
+    case_folding: CaseFolding,
+    char_filter: CharFilter,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Normalizer {
+    /// Serializes the built-in settings, failing if there's a custom function or analyzer, which can't be serialized
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+        if self.custom.is_some() || self.analyzer.is_some() {
+            return Err(S::Error::custom(
+                "normalizers with a custom function or analyzer can't be serialized",
+            ));
+        }
+        #[cfg(feature = "unicode")]
+        let form = match self.form {
+            None => 0,
+            Some(NormalizationForm::Nfc) => 1,
+            Some(NormalizationForm::Nfkc) => 2,
+        };
+        #[cfg(not(feature = "unicode"))]
+        let form = 0;
+        NormalizerSettings {
+            form,
+            case_folding: self.case_folding,
+            char_filter: self.char_filter,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Normalizer {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let settings = NormalizerSettings::deserialize(deserializer)?;
+        #[cfg(not(feature = "unicode"))]
+        if settings.form != 0 {
+            return Err(D::Error::custom(
+                "normalization forms require the unicode feature",
+            ));
+        }
+        Ok(Normalizer {
+            #[cfg(feature = "unicode")]
+            form: match settings.form {
+                0 => None,
+                1 => Some(NormalizationForm::Nfc),
+                2 => Some(NormalizationForm::Nfkc),
+                form => {
+                    return Err(D::Error::custom(format_args!(
+                        "unknown normalization form {}",
+                        form
+                    )))
+                }
+            },
+            case_folding: settings.case_folding,
+            char_filter: settings.char_filter,
+            custom: None,
+            analyzer: None,
+        })
+    }
+}
+
+/// The best strings of every node by descending weight and then lexicographical order
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct TopStrings<SSS> {
+    /// Maximum number of strings kept per node
+    k: usize,
+    /// Node id |-> start of its strings in `indices` (with an extra end offset)
+    offsets: Vec<SSS>,
+    /// Indices of the strings of all nodes, ordered by node id
+    indices: Vec<SSS>,
+}
+
+impl TopStrings<SSS> {
+    /// Returns the best strings of the node with `id`, which is empty if they weren't precomputed
+    fn get(&self, id: NodeID) -> &[SSS] {
+        if id + 1 < self.offsets.len() {
+            &self.indices[self.offsets[id] as usize..self.offsets[id + 1] as usize]
+        } else {
+            &[]
+        }
+    }
+}
+
+/// Returns the strings from `pairs` sorted and deduplicated, with the associated values of duplicates combined by `merge`
+fn dedup_pairs<'stored, T>(
+    len: usize,
+    source: impl IntoIterator<Item = (TreeString<'stored>, T)>,
+    mut merge: impl FnMut(&mut T, T),
+) -> (TrieStrings<'stored>, Vec<T>) {
+    let mut pairs: Vec<(TreeString<'stored>, T)> = Vec::with_capacity(len);
+    pairs.extend(source);
+    // stable sort so that duplicate strings are merged in the order they were given
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut strings = TrieStrings::<'stored>::with_capacity(pairs.len());
+    let mut values = Vec::<T>::with_capacity(pairs.len());
+    for (string, value) in pairs {
+        if strings.last() == Some(&string) {
+            merge(values.last_mut().unwrap(), value);
+        } else {
+            strings.push(string);
+            values.push(value);
+        }
+    }
+    (strings, values)
+}
+
+/// Options that change how a query is searched
+#[derive(Clone, Debug, Default)]
+pub struct QueryOptions {
+    /// Returns strings with the query as an exact prefix without error-tolerant matching if there are enough of them
+    pub exact_first: bool,
+    /// Number of leading characters of the query that must match exactly, which excludes all other strings from the search
+    pub strict_prefix: usize,
+    /// Strings with any of these prefixes are skipped
+    pub exclude_prefixes: Vec<String>,
+    /// Strings that are skipped
+    pub deny: HashSet<String>,
+    /// Time after which the search stops and returns the best results found so far
+    pub deadline: Option<Instant>,
+    /// Number of matchings after which the search stops and returns the best results found so far
+    pub max_matchings: Option<usize>,
+    /// Token that stops the search when cancelled, returning the best results found so far
+    pub cancel: CancelToken,
+    /// Maximum prefix edit distance of results, which also limits the search (combined with the threshold policy)
+    pub max_prefix_distance: Option<usize>,
+    /// How the Jaro-Winkler similarity between the query and each whole string ranks the results
+    pub jaro_winkler: JaroWinklerRanking,
+    /// How the trie is searched for the strings closest to the query
+    pub algorithm: SearchAlgorithm,
+}
+
+/// How results are ranked by the Jaro-Winkler similarity between the query and their strings,
+/// which emphasizes common prefixes and suits short strings like names of people
+///
+/// Only the strings found by the search are ranked, which are the ones with the smallest prefix edit distances
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JaroWinklerRanking {
+    /// Results aren't ranked by it
+    #[default]
+    None,
+    /// Results with the same prefix edit distance are ranked by descending similarity before weight
+    Tiebreaker,
+    /// Results are ranked by descending similarity before prefix edit distance
+    Primary,
+}
+
+/// How the trie is searched for the strings with the smallest prefix edit distances
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchAlgorithm {
+    /// Matching sets are deduced from those of shorter query prefixes (which can be cached) as in META
+    #[default]
+    Deduction,
+    /// The trie is descended with a Levenshtein automaton for the query, raising the edit budget one edit at a time,
+    /// which does a predictable amount of work per node and doesn't use the cache,
+    /// and suits large edit budgets or checking the results of `Deduction`
+    ///
+    /// Each visited node counts as a matching for `max_matchings`
+    Automaton,
+}
+
+/// Buffers reused across queries by `autocomplete_into`, so that repeated queries don't allocate
+/// for the strings they collect or measure once the buffers have grown
+#[derive(Clone, Debug, Default)]
+pub struct QueryScratch {
+    /// Indices of the collected strings mapped to the id of the node each was taken from
+    indices: HashMap<SSS, NodeID>,
+    /// Collected strings with their prefix edit distances
+    measures: Vec<MeasuredIndex>,
+    /// Buffers for the prefix edit distances
+    distances: levenshtein::Scratch,
+}
+
+impl QueryScratch {
+    /// Returns empty buffers, which grow to the largest queries they're used for
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+/// Handle for cancelling queries from another thread, such as when the user has typed the next character
+///
+/// Clones share the same state
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Returns a token that hasn't been cancelled
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Cancels the queries searched with this token
+    pub fn cancel(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+    /// Returns whether the token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(atomic::Ordering::Relaxed)
+    }
+}
+
+impl QueryOptions {
+    /// Returns whether a search that has found `matchings` matchings has to stop
+    fn exhausted(&self, matchings: usize) -> bool {
+        self.cancel.is_cancelled()
+            || self.max_matchings.is_some_and(|max| matchings >= max)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+    /// Returns whether `string` is excluded from results
+    fn excludes(&self, string: &str) -> bool {
+        self.deny.contains(string)
+            || self
+                .exclude_prefixes
+                .iter()
+                .any(|prefix| string.starts_with(prefix.as_str()))
+    }
+}
+
+/// Maximum prefix edit distance of results depending on the length of the query
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ThresholdPolicy {
+    /// The same maximum for every query
+    Fixed(usize),
+    /// One more edit for every `per_chars` characters, `(|q| + 1) / per_chars` in total,
+    /// so `per_chars: 4` allows 0 edits for up to 2 characters, 1 for up to 6, 2 for up to 10 and so on
+    Scaled { per_chars: usize },
+}
+
+impl ThresholdPolicy {
+    /// Returns the maximum prefix edit distance for a query of `query_len` characters
+    pub fn max_edits(&self, query_len: usize) -> usize {
+        match *self {
+            Self::Fixed(edits) => edits,
+            Self::Scaled { per_chars } => (query_len + 1) / max(per_chars, 1),
+        }
+    }
 }
 
 #[derive(Default)]
 /// Separate this it out entirely to avoid lifetime conflicts
 pub struct Cache<'stored> {
-    cached_prefix: PTrie<char, PState>,
+    /// Index in the slab of the state of each cached prefix
+    cached_prefix: PTrie<char, usize>,
     lru: CacheMap<'stored>,
+    /// Characters of the last assembled query
+    trail_query: Vec<char>,
+    /// P(i,0) for each prefix of the last assembled query, so that a query sharing a prefix with it
+    /// (such as after a backspace) starts from the shared prefix instead of combining the cached sets again
+    trail: Vec<MatchingSet<UUU>>,
+    /// Maximum number of query prefixes kept by `prune`, or None to use the autocompleter's cache size
+    capacity: Option<usize>,
+    /// Maximum estimated number of bytes kept by `prune`, or None to only limit the number of query prefixes
+    byte_budget: Option<usize>,
+    /// Number of queries visited so far, which orders the cached prefixes by recency
+    clock: u64,
+    /// Estimated number of bytes of the cached prefixes and their matching sets
+    bytes: usize,
+    /// Number of matching sets taken from the cache instead of being deduced
+    hits: u64,
+    /// Number of matching sets that had to be deduced because they weren't cached
+    misses: u64,
+    /// Number of cached prefixes removed by pruning
+    evictions: u64,
+    /// Memoized results of whole queries, if enabled
+    results: Option<memo::ResultCache>,
+    /// Generation of the autocompleter that the cached matching sets were deduced for, whose node ids they hold
+    generation: Option<u64>,
+}
+
+/// Counters of a `Cache` for checking whether it helps, such as when tuning its capacity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CacheStats {
+    /// Number of cached query prefixes
+    pub entries: usize,
+    /// Number of matching sets taken from the cache instead of being deduced
+    pub hits: u64,
+    /// Number of matching sets that had to be deduced because they weren't cached
+    pub misses: u64,
+    /// Estimated number of bytes of the cached prefixes and their matching sets
+    pub bytes: usize,
+    /// Number of cached prefixes and memoized queries removed by pruning
+    pub evictions: u64,
+    /// Number of queries with memoized results
+    pub results: usize,
+    /// Number of queries answered with memoized results
+    pub result_hits: u64,
 }
 
 impl<'x> Cache<'x> {
+    /// Returns an empty cache that `prune` limits to `capacity` query prefixes regardless of the autocompleter's cache size
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Default::default()
+        }
+    }
+    /// Returns the maximum number of query prefixes kept by `prune`, or None if the autocompleter's cache size is used
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+    /// Limits the cache to `capacity` query prefixes from the next `prune` on, or to the autocompleter's cache size if None
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+    }
+    /// Returns an empty cache that `prune` limits to an estimated `bytes` of prefixes and matching sets,
+    /// in addition to the autocompleter's cache size
+    pub fn with_byte_budget(bytes: usize) -> Self {
+        Self {
+            byte_budget: Some(bytes),
+            ..Default::default()
+        }
+    }
+    /// Returns the maximum estimated number of bytes kept by `prune`, if any
+    pub fn byte_budget(&self) -> Option<usize> {
+        self.byte_budget
+    }
+    /// Limits the cache to an estimated `bytes` of prefixes and matching sets from the next `prune` on, or removes the limit if None
+    ///
+    /// Queries with many matchings can take more memory than many short ones, so this bounds memory where the capacity can't
+    pub fn set_byte_budget(&mut self, bytes: Option<usize>) {
+        self.byte_budget = bytes;
+    }
+    /// Returns the number of cached query prefixes, which is what the capacity limits
+    ///
+    /// Every prefix of a cached query is cached too, so a query of n characters adds up to n of them
+    pub fn len(&self) -> usize {
+        self.lru.slab.len()
+    }
+    /// Returns whether no query prefixes are cached
+    pub fn is_empty(&self) -> bool {
+        self.lru.slab.is_empty()
+    }
+    /// Returns the number of cached prefixes, hits, misses, estimated bytes and evictions so far
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.len(),
+            hits: self.hits,
+            misses: self.misses,
+            bytes: self.bytes,
+            evictions: self.evictions,
+            results: self.results.as_ref().map_or(0, |results| results.len()),
+            result_hits: self.results.as_ref().map_or(0, |results| results.hits),
+        }
+    }
+    /// Removes every cached prefix and memoized result, keeping the capacity, the byte budget and the counters of hits, misses and evictions
+    pub fn clear(&mut self) {
+        self.clear_prefixes();
+        if let Some(results) = &mut self.results {
+            results.clear();
+        }
+    }
+    /// Removes every cached prefix, keeping the memoized results
+    fn clear_prefixes(&mut self) {
+        self.cached_prefix = Default::default();
+        self.lru = Default::default();
+        self.trail_query.clear();
+        self.trail.clear();
+        self.bytes = 0;
+    }
+    /// Removes the cached prefixes if they were deduced for another autocompleter or before a mutation,
+    /// since their matching sets refer to the nodes of that trie
+    ///
+    /// Memoized results are keyed by the generation already, so the stale ones are left for pruning to evict
+    fn use_generation(&mut self, generation: u64) {
+        if self.generation != Some(generation) {
+            self.clear_prefixes();
+            self.generation = Some(generation);
+        }
+    }
+    /// Precomputes the matching sets of `prefixes` for `autocompleter`, such as the most popular queries from logs,
+    /// for prefix edit distances up to `max_edits` (of which at most 2 are cached), so that their first keystrokes are already cached
+    ///
+    /// Prefixes are normalized like queries and warmed one character at a time like they're typed,
+    /// and the cache is pruned as it fills like it is by queries, so the most popular prefixes should come last
+    /// if there are more than the capacity
+    pub fn warm<'q, P>(
+        &mut self,
+        autocompleter: &MetaAutocompleter<'_, UUU, SSS, P>,
+        prefixes: impl IntoIterator<Item = &'q str>,
+        max_edits: usize,
+    ) {
+        let options = QueryOptions {
+            max_prefix_distance: Some(max_edits),
+            ..Default::default()
+        };
+        for prefix in prefixes {
+            let prefix = autocompleter.normalized(prefix);
+            // each keystroke is a query, which needs the sets of its last character for every edit
+            for (end, character) in prefix.char_indices() {
+                let keystroke = &prefix[..end + character.len_utf8()];
+                autocompleter.assemble_bounded(keystroke.into(), self, &options);
+            }
+        }
+    }
+    /// Removes every cached prefix and memoized result, same as `clear`
+    ///
+    /// Queries already remove the cached prefixes when the cache was filled by another autocompleter or before a mutation,
+    /// so this is only needed when the strings that the cached states depend on changed in some other way
+    pub fn invalidate_all(&mut self) {
+        self.clear();
+    }
+    /// Removes the cached states of `prefix` and of every cached query that starts with it,
+    /// such as after changing the strings that queries starting with `prefix` should match
+    ///
+    /// `prefix` is normalized like the queries of `autocompleter`, whose cached prefixes are normalized.
+    /// Matching sets of other queries can also match the changed strings with edits, so `invalidate_all` is needed
+    /// when the changes could affect any query within the threshold
+    pub fn invalidate_prefix<P>(
+        &mut self,
+        autocompleter: &MetaAutocompleter<'_, UUU, SSS, P>,
+        prefix: &str,
+    ) {
+        let prefix: &str = &autocompleter.normalized(prefix);
+        if let Some(results) = &mut self.results {
+            results.invalidate_prefix(prefix);
+        }
+        if prefix.is_empty() {
+            self.clear();
+            return;
+        }
+        self.cached_prefix.remove_subtree(prefix.chars());
+        let mut removed = HashSet::new();
+        let bytes = &mut self.bytes;
+        self.lru.slab.retain(|ix, entry| {
+            let stale = entry.prefix.starts_with(prefix);
+            if stale {
+                *bytes -= entry.bytes();
+                removed.insert(ix);
+            }
+            !stale
+        });
+        for set in self.lru.prio.values_mut() {
+            set.retain(|ix| !removed.contains(ix));
+        }
+        self.lru.prio.retain(|_, set| !set.is_empty());
+        // the sets for the prefixes of the last query from `prefix` on are stale too
+        let len = prefix.chars().count();
+        if self
+            .trail_query
+            .iter()
+            .copied()
+            .take(len)
+            .eq(prefix.chars())
+        {
+            self.trail.truncate(len - 1);
+            self.trail_query.truncate(len - 1);
+        }
+    }
+    /// Adds `bytes` of matching sets to the estimated size of the cached prefix at `ix`
+    fn add_set_bytes(&mut self, ix: usize, bytes: usize) {
+        if let Some(entry) = self.lru.slab.get_mut(ix) {
+            entry.set_bytes += bytes;
+            self.bytes += bytes;
+        }
+    }
+    /// Evicts the least recently visited prefixes until the estimated size is within `budget`
+    fn evict_to_budget(&mut self, budget: usize) {
+        while self.bytes > budget && self.evict_oldest() {}
+    }
+    /// Evicts the least recently visited prefix, returning false if the cache is empty
+    ///
+    /// Visiting a prefix visits all of its own prefixes too, so the cached descendants of the evicted prefix
+    /// were visited in the same query at the latest, and the longest prefix of the oldest visit has none
+    fn evict_oldest(&mut self) -> bool {
+        let Some(mut oldest) = self.lru.prio.first_entry() else {
+            return false;
+        };
+        let set = oldest.get_mut();
+        let slab = &self.lru.slab;
+        let ix = *set
+            .iter()
+            .max_by_key(|&&ix| slab.get(ix).map_or(0, |entry| entry.prefix.len()))
+            .unwrap();
+        set.remove(&ix);
+        if set.is_empty() {
+            oldest.remove();
+        }
+        if let Some(entry) = self.lru.slab.try_remove(ix) {
+            self.cached_prefix.remove_subtree(entry.prefix.chars());
+            self.bytes -= entry.bytes();
+            self.evictions += 1;
+        }
+        true
+    }
     pub fn visit<'t, 'q>(
         &'t mut self,
         query: TreeString<'q>,
         mut cb: impl FnMut(usize, &mut PState),
     ) {
-        println!("cache query {}", &query);
+        let chars: Vec<char> = query.chars().collect();
+        // every visit is later than the previous ones, even within the same clock tick
+        self.clock += 1;
+        let now = self.clock;
         let ptree = &mut self.cached_prefix;
-        ptree.insert(query.chars(), |ps, i| {
+        ptree.insert(chars.iter().copied(), |node, i| {
             if let Some(i) = i {
-                if let Some(ref mut ps) = ps.value {
-                    cb(i, ps);
-                    let mut lock = ps.prio.lock().unwrap();
-                    self.lru.prio.rm(&lock, &ps.ix);
-                    if i == query.len() - 1 {
-                        *lock = Instant::now();
-                        self.lru.prio.add(*lock, ps.ix)
-                    }
-                } else {
-                    ps.value = Some(PState {
-                        sets: Default::default(),
-                        prio: Instant::now().into(),
-                        ix: self
-                            .lru
-                            .slab
-                            .insert(TreeStringT::from_owned(query.to_string())),
+                let ix = *node.value.get_or_insert_with(|| {
+                    let prefix: TreeString = TreeStringT::from_owned(chars[..=i].iter().collect());
+                    self.bytes += prefix.len();
+                    let entry = self.lru.slab.vacant_entry();
+                    let ix = entry.key();
+                    entry.insert(CachedPrefix {
+                        prefix,
+                        set_bytes: 0,
+                        state: PState {
+                            sets: Default::default(),
+                            prio: now,
+                            ix,
+                        },
                     });
-                    cb(i, ps.value.as_mut().unwrap());
-                }
+                    ix
+                });
+                let ps = &mut self.lru.slab[ix].state;
+                cb(i, ps);
+                // every visited prefix is moved to the newest visit, so that each can be evicted
+                self.lru.prio.rm(&ps.prio, &ps.ix);
+                ps.prio = now;
+                self.lru.prio.add(now, ps.ix);
             }
         });
     }
@@ -363,36 +1182,58 @@ impl<'x> Cache<'x> {
 #[derive(Debug)]
 pub struct PState {
     /// vec index as key, b -> P(i,b) delta
+    ///
+    /// Index 0 is the first-deducing delta from P(i-1,0), which every visited prefix has.
+    /// Index b >= 1 is the second-deducing delta from P(i,b-1), which prefixes have once they were the last of a query
+    /// with a budget of at least b, so queries with larger budgets only deduce the deltas that are missing
     sets: Vec<MatchingSet<UUU>>,
-    /// last visit
-    prio: Mutex<Instant>,
+    /// Value of the cache's clock at the last visit
+    prio: u64,
     ix: usize,
 }
 
+/// Prefix of a node of the cache with its state and the estimated number of bytes of its matching sets
+struct CachedPrefix<'s> {
+    prefix: TreeString<'s>,
+    set_bytes: usize,
+    state: PState,
+}
+
+impl CachedPrefix<'_> {
+    /// Returns the estimated number of bytes of the prefix and its matching sets
+    fn bytes(&self) -> usize {
+        self.prefix.len() + self.set_bytes
+    }
+}
+
 /// Reverse index
 #[derive(Default)]
 pub struct CacheMap<'s> {
-    slab: Slab<TreeString<'s>>,
+    slab: Slab<CachedPrefix<'s>>,
     /// Priority --> Set: prefix
     /// Ascending, old to new
-    prio: BTreeMap<Instant, BTreeSet<usize>>,
+    prio: BTreeMap<u64, BTreeSet<usize>>,
 }
 
 pub trait PrioMap {
-    fn rm(&mut self, t: &Instant, k: &usize) -> bool;
-    fn add(&mut self, t: Instant, k: usize);
+    fn rm(&mut self, t: &u64, k: &usize) -> bool;
+    fn add(&mut self, t: u64, k: usize);
 }
 
-impl PrioMap for BTreeMap<Instant, BTreeSet<usize>> {
-    fn rm(&mut self, t: &Instant, k: &usize) -> bool {
+impl PrioMap for BTreeMap<u64, BTreeSet<usize>> {
+    fn rm(&mut self, t: &u64, k: &usize) -> bool {
         if let Some(set) = self.get_mut(t) {
             set.remove(k);
+            // empty priorities would count as prefixes when pruning
+            if set.is_empty() {
+                self.remove(t);
+            }
             true
         } else {
             false
         }
     }
-    fn add(&mut self, t: Instant, k: usize) {
+    fn add(&mut self, t: u64, k: usize) {
         match self.entry(t) {
             Entry::Occupied(mut oc) => {
                 oc.get_mut().insert(k);
@@ -404,11 +1245,6 @@ impl PrioMap for BTreeMap<Instant, BTreeSet<usize>> {
     }
 }
 
-#[test]
-pub fn edtest() {
-    dbg!(edit_distance("quer", "qzer"));
-}
-
 impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
     /// Constructs an Autocompleter given the string dataset `source` (does not copy strings)
     pub fn new(len: usize, source: impl IntoIterator<Item = TreeString<'stored>>) -> Self {
@@ -417,90 +1253,648 @@ impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
         Self {
             trie,
             inverted_index,
+            payloads: Vec::new(),
+            weights: Vec::new(),
+            counts: Vec::new(),
+            originals: Vec::new(),
+            top: Default::default(),
+            threshold: None,
+            pins: Default::default(),
+            min_fuzzy_len: 0,
+            normalizer: Default::default(),
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+            generation: next_generation(),
         }
     }
-    pub fn len(&self) -> usize {
-        self.trie.strings.len()
-    }
+    /// Constructs an Autocompleter given the `source` of strings and their weights (does not copy strings)
+    ///
+    /// Results with the same prefix edit distance are ranked by descending weight,
+    /// and the weights of strings that occur more than once are summed
+    pub fn new_weighted(
+        len: usize,
+        source: impl IntoIterator<Item = (TreeString<'stored>, u32)>,
+    ) -> Self {
+        let (strings, weights) = dedup_pairs(len, source, |weight, other| {
+            *weight = weight.saturating_add(other)
+        });
 
-    pub fn prune(&mut self, cache: &'stored mut Cache<'stored>) {
-        let max = 1000;
-        // oldest element ---- cutoff ----- newest element
-        let cutoff = *if cache.lru.prio.len() < max {
-            return;
-        } else {
-            cache.lru.prio.keys().nth_back(max).unwrap()
-        };
-        for (_k, set) in cache.lru.prio.range(..cutoff).rev() {
-            // prune all the tail after each node, cuz every marker node after it must be older/smaller
-            for ix in set {
-                let prefix = &cache.lru.slab[*ix];
-                cache.cached_prefix.remove_subtree(prefix.chars())
-            }
+        let trie = Trie::<'stored, UUU, SSS>::new(strings.len(), strings);
+        debug_assert_eq!(trie.strings.len(), weights.len());
+        let inverted_index = InvertedIndex::<UUU, SSS>::new(&trie);
+        Self {
+            trie,
+            inverted_index,
+            payloads: Vec::new(),
+            weights,
+            counts: Vec::new(),
+            originals: Vec::new(),
+            top: Default::default(),
+            threshold: None,
+            pins: Default::default(),
+            min_fuzzy_len: 0,
+            normalizer: Default::default(),
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+            generation: next_generation(),
         }
-        cache.lru.prio = cache.lru.prio.split_off(&cutoff);
     }
-    /// P(|q|,b)
-    pub fn assemble<'q>(&self, q: TreeString<'q>, cache: &mut Cache<'_>) -> MatchingSet<UUU> {
-        let use_cache = true;
-        let query_chars: Vec<char> = q.chars().collect();
-        // -0-0- .... -0-|
-        //               | 1
-        //               | 2
-        let mut acc = MatchingSet::new_trie(&self.trie);
-        cache.visit(q.clone(), |ix, ps| {
-            if let Some(k) = ps.sets.get(0)
-                && use_cache
-            {
-                println!("|{}| add matchings {}", ix, k.matchings.len());
-                acc.extend(k);
-            } else {
-                println!("|{}| 1st-deduce set len={}", ix, acc.matchings.len());
-                let delta = self.first_deducing(&acc, query_chars[ix], ix + 1, 0);
-                acc.extend(&delta);
-                ps.sets = vec![delta];
-            }
-            if ix == q.len() - 1 && q.len() > 0 {
-                for t in 1..=2 {
-                    if let Some(cached) = ps.sets.get(t)
-                        && use_cache
-                    {
-                        println!("|{}| add matchings {}", ix, cached.matchings.len());
-                        acc.extend(cached);
-                    } else {
-                        let new = self.second_deducing(&acc, &query_chars, query_chars.len(), t);
-                        println!(
-                            "|{}| 2nd-deduce {} set-len={}",
-                            ix,
-                            query_chars.len(),
-                            new.matchings.len()
-                        );
-                        acc.extend(&new);
-                        assert!(ps.sets.len() - 1 == t - 1);
-                        ps.sets.push(new);
-                    }
-                }
-            }
-        });
+    /// Constructs an Autocompleter given the string dataset `source` that keeps how many times each string occurs
+    /// instead of silently removing duplicates (does not copy strings)
+    ///
+    /// If `rank_by_count` is true, the counts are also used as weights so that frequent strings are ranked first
+    /// among strings with the same prefix edit distance
+    pub fn new_counted(
+        len: usize,
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+        rank_by_count: bool,
+    ) -> Self {
+        let (strings, counts) = dedup_pairs(
+            len,
+            source.into_iter().map(|string| (string, 1u32)),
+            |count, other| *count = count.saturating_add(other),
+        );
 
-        acc
+        let trie = Trie::<'stored, UUU, SSS>::new(strings.len(), strings);
+        debug_assert_eq!(trie.strings.len(), counts.len());
+        let inverted_index = InvertedIndex::<UUU, SSS>::new(&trie);
+        Self {
+            trie,
+            inverted_index,
+            payloads: Vec::new(),
+            weights: if rank_by_count {
+                counts.clone()
+            } else {
+                Vec::new()
+            },
+            counts,
+            originals: Vec::new(),
+            top: Default::default(),
+            threshold: None,
+            pins: Default::default(),
+            min_fuzzy_len: 0,
+            normalizer: Default::default(),
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+            generation: next_generation(),
+        }
     }
 }
 
-#[test]
-fn try_range() {
-    println!("{:?}", (2..=2).into_iter().collect::<Vec<_>>());
-}
-
-#[derive(Clone, Copy)]
-struct Matching<UUU>
-where
-    UUU: Clone,
-{
-    query_prefix_len: UUU,
-    node: NodeID,
-    edit_distance: UUU,
-}
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Constructs an Autocompleter given the `source` of strings and their payloads (does not copy strings)
+    ///
+    /// Strings that occur more than once keep all of their payloads
+    pub fn new_with_payloads(
+        len: usize,
+        source: impl IntoIterator<Item = (TreeString<'stored>, P)>,
+    ) -> Self {
+        let (strings, payloads) = dedup_pairs(
+            len,
+            source
+                .into_iter()
+                .map(|(string, payload)| (string, vec![payload])),
+            Vec::extend,
+        );
+
+        let trie = Trie::<'stored, UUU, SSS>::new(strings.len(), strings);
+        debug_assert_eq!(trie.strings.len(), payloads.len());
+        let inverted_index = InvertedIndex::<UUU, SSS>::new(&trie);
+        Self {
+            trie,
+            inverted_index,
+            payloads,
+            weights: Vec::new(),
+            counts: Vec::new(),
+            originals: Vec::new(),
+            top: Default::default(),
+            threshold: None,
+            pins: Default::default(),
+            min_fuzzy_len: 0,
+            normalizer: Default::default(),
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+            generation: next_generation(),
+        }
+    }
+    /// Returns the payloads of the stored `string`, which is empty if it isn't stored or has none
+    pub fn payloads(&self, string: &str) -> &[P] {
+        match self.position(string) {
+            Some(index) => self.payloads.get(index as usize).map_or(&[], Vec::as_slice),
+            None => &[],
+        }
+    }
+    /// Returns `query` normalized the same way as the stored strings
+    pub(crate) fn normalized<'q>(&self, query: &'q str) -> Cow<'q, str> {
+        let normalized = self.normalizer.apply(query);
+        #[cfg(feature = "unicode-segmentation")]
+        if let Some(graphemes) = &self.graphemes {
+            let encoded = match graphemes.encode(&normalized) {
+                Cow::Borrowed(_) => None,
+                Cow::Owned(encoded) => Some(encoded),
+            };
+            return encoded.map_or(normalized, Cow::Owned);
+        }
+        normalized
+    }
+    /// Returns the index of the stored `string` for `get`, or None if it isn't stored
+    pub fn position(&self, string: &str) -> Option<SSS> {
+        let string: &str = &self.normalized(string);
+        self.trie
+            .strings
+            .binary_search_by(|stored| TreeStringT::to_str(stored).cmp(string))
+            .ok()
+            .map(|index| index as SSS)
+    }
+    /// Returns whether `string` is stored
+    pub fn contains(&self, string: &str) -> bool {
+        self.position(string).is_some()
+    }
+    /// Returns the stored strings with indices in `range` in lexicographical order (panics if `range` is out of bounds)
+    pub fn range(&self, range: Range<SSS>) -> impl Iterator<Item = &str> {
+        self.trie.strings[range.start as usize..range.end as usize]
+            .iter()
+            .map(TreeStringT::to_str)
+    }
+    /// Returns the stored strings that are at least `start` and less than `end` in lexicographical order
+    pub fn strings_between<'a>(&'a self, start: &str, end: &str) -> impl Iterator<Item = &'a str> {
+        let strings = &self.trie.strings;
+        let first = strings.partition_point(|string| TreeStringT::to_str(string) < start);
+        let last = strings.partition_point(|string| TreeStringT::to_str(string) < end);
+        self.range(first as SSS..max(first, last) as SSS)
+    }
+    /// Returns an iterator over all stored strings in lexicographical order
+    pub fn iter_strings(&self) -> impl Iterator<Item = &str> {
+        self.trie.strings.iter().map(TreeStringT::to_str)
+    }
+    /// Consumes the autocompleter and returns its stored strings in lexicographical order without duplicates
+    pub fn into_strings(self) -> Vec<TreeString<'stored>> {
+        self.trie.strings
+    }
+    /// Consumes the autocompleter and returns it with copies of the strings it borrows,
+    /// so it can outlive the buffer it was loaded from
+    pub fn into_owned(self) -> MetaAutocompleter<'static, UUU, SSS, P> {
+        let owned = |strings: TrieStrings<'stored>| -> TrieStrings<'static> {
+            strings
+                .into_iter()
+                .map(|string| Cow::Owned(string.into_owned()))
+                .collect()
+        };
+        MetaAutocompleter {
+            trie: Trie {
+                nodes: self.trie.nodes,
+                strings: owned(self.trie.strings),
+            },
+            inverted_index: self.inverted_index,
+            payloads: self.payloads,
+            weights: self.weights,
+            counts: self.counts,
+            originals: owned(self.originals),
+            top: self.top,
+            threshold: self.threshold,
+            pins: self.pins,
+            min_fuzzy_len: self.min_fuzzy_len,
+            normalizer: self.normalizer,
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: self.graphemes,
+            cache_size: self.cache_size,
+            generation: self.generation,
+        }
+    }
+    /// Merges the strings of `self` and `other` into one autocompleter that keeps the settings of `self`,
+    /// so both should have been built with the same normalization
+    ///
+    /// Strings stored in both keep the payloads of both and the sums of their weights and counts,
+    /// and pins of `self` take priority over those of `other` for the same prefix
+    ///
+    /// If `self` compares grapheme clusters, the clusters of `other` are encoded with its table,
+    /// which panics if there are more distinct clusters than private use characters to encode them
+    pub fn merge(self, other: Self) -> Self {
+        let weighted = !self.weights.is_empty() || !other.weights.is_empty();
+        let counted = !self.counts.is_empty() || !other.counts.is_empty();
+        let with_payloads = !self.payloads.is_empty() || !other.payloads.is_empty();
+        let with_originals = !self.originals.is_empty() || !other.originals.is_empty();
+        // strings of `other` are decoded into their clusters, which `pin` and `recode` encode again for `self`
+        #[cfg(feature = "unicode-segmentation")]
+        let clusters = other
+            .graphemes
+            .as_ref()
+            .map(graphemes::GraphemeTable::clusters);
+        let decode = |string: TreeString<'stored>| -> TreeString<'stored> {
+            #[cfg(feature = "unicode-segmentation")]
+            if let Some(clusters) = &clusters {
+                return graphemes::GraphemeTable::decode(clusters, string);
+            }
+            string
+        };
+        let mut pins = Vec::<(String, String)>::new();
+        for (prefix, pinned) in &self.pins {
+            for &index in pinned {
+                pins.push((prefix.clone(), self.get(index).to_string()));
+            }
+        }
+        for (prefix, pinned) in &other.pins {
+            for &index in pinned {
+                let string = decode(other.get(index).to_string().into());
+                pins.push((
+                    decode(prefix.clone().into()).into_owned(),
+                    string.into_owned(),
+                ));
+            }
+        }
+        let len = self.len() + other.len();
+        let Self {
+            trie,
+            payloads,
+            weights,
+            counts,
+            originals,
+            top,
+            threshold,
+            min_fuzzy_len,
+            normalizer,
+            #[cfg(feature = "unicode-segmentation")]
+            mut graphemes,
+            cache_size,
+            ..
+        } = self;
+        // the strings of `other` are kept as originals if they're encoded for the first time
+        #[cfg(feature = "unicode-segmentation")]
+        let encoded = graphemes.is_some() && other.graphemes.is_none();
+        #[cfg(not(feature = "unicode-segmentation"))]
+        let encoded = false;
+        let mut other_originals = Vec::new();
+        let other_strings: Vec<TreeString<'stored>> = other
+            .trie
+            .strings
+            .into_iter()
+            .map(|string| {
+                let string = decode(string);
+                if encoded && other.originals.is_empty() {
+                    other_originals.push(string.clone());
+                }
+                #[cfg(feature = "unicode-segmentation")]
+                if let Some(table) = &mut graphemes {
+                    return table
+                        .encode_stored(string)
+                        .expect("too many distinct grapheme clusters to encode");
+                }
+                string
+            })
+            .collect();
+        if other_originals.is_empty() {
+            other_originals = other.originals;
+        }
+        let entries = |strings: Vec<TreeString<'stored>>,
+                       payloads: Vec<Vec<P>>,
+                       weights: Vec<u32>,
+                       counts: Vec<u32>,
+                       originals: Vec<TreeString<'stored>>| {
+            let mut payloads = payloads.into_iter();
+            let mut originals = originals.into_iter();
+            strings.into_iter().enumerate().map(move |(index, string)| {
+                let weight = weights.get(index).copied().unwrap_or(0);
+                let count = counts.get(index).copied().unwrap_or(1);
+                let payloads = payloads.next().unwrap_or_default();
+                (string, (weight, count, originals.next(), payloads))
+            })
+        };
+        // both inputs are sorted unless the clusters of `other` were encoded again, so the stable sort usually only merges two runs
+        let (strings, values) = dedup_pairs(
+            len,
+            entries(trie.strings, payloads, weights, counts, originals).chain(entries(
+                other_strings,
+                other.payloads,
+                other.weights,
+                other.counts,
+                other_originals,
+            )),
+            |(weight, count, original, payloads),
+             (other_weight, other_count, other, other_payloads)| {
+                *weight = weight.saturating_add(other_weight);
+                *count = count.saturating_add(other_count);
+                if original.is_none() {
+                    *original = other;
+                }
+                payloads.extend(other_payloads);
+            },
+        );
+        let mut weights = Vec::with_capacity(values.len());
+        let mut counts = Vec::with_capacity(values.len());
+        let mut originals = Vec::with_capacity(values.len());
+        let mut payloads = Vec::with_capacity(values.len());
+        for (string, (weight, count, original, string_payloads)) in strings.iter().zip(values) {
+            weights.push(weight);
+            counts.push(count);
+            originals.push(original.unwrap_or_else(|| string.clone()));
+            payloads.push(string_payloads);
+        }
+
+        let trie = Trie::<'stored, UUU, SSS>::new(strings.len(), strings);
+        let inverted_index = InvertedIndex::<UUU, SSS>::new(&trie);
+        let mut merged = Self {
+            trie,
+            inverted_index,
+            payloads: if with_payloads { payloads } else { Vec::new() },
+            weights: if weighted { weights } else { Vec::new() },
+            counts: if counted { counts } else { Vec::new() },
+            originals: if with_originals {
+                originals
+            } else {
+                Vec::new()
+            },
+            top: Default::default(),
+            threshold,
+            pins: Default::default(),
+            min_fuzzy_len,
+            normalizer,
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes,
+            cache_size,
+            generation: next_generation(),
+        };
+        for (prefix, string) in pins {
+            merged.pin(&prefix, &string);
+        }
+        if top.k > 0 {
+            merged.precompute_top(top.k);
+        }
+        merged
+    }
+    /// Pins the stored `string` to the top of the results of queries that start with `prefix`
+    /// as long as it's within their threshold, returning false if `string` isn't stored
+    ///
+    /// Strings pinned to longer prefixes come first, and then in the order they were pinned
+    pub fn pin(&mut self, prefix: &str, string: &str) -> bool {
+        let Some(index) = self.position(string) else {
+            return false;
+        };
+        let prefix = self.normalized(prefix).into_owned();
+        let pinned = self.pins.entry(prefix).or_default();
+        if !pinned.contains(&index) {
+            pinned.push(index);
+        }
+        self.generation = next_generation();
+        true
+    }
+    /// Removes the strings pinned to `prefix`
+    pub fn unpin(&mut self, prefix: &str) {
+        let prefix = self.normalized(prefix).into_owned();
+        self.pins.remove(&prefix);
+        self.generation = next_generation();
+    }
+    /// Returns the indices of the strings pinned for `query` in order of priority
+    fn pins_for(&self, query: &str) -> Vec<SSS> {
+        let mut indices = Vec::new();
+        // prefixes of the query in lexicographical order are from shortest to longest
+        for (_, pinned) in self
+            .pins
+            .iter()
+            .rev()
+            .filter(|(prefix, _)| query.starts_with(prefix.as_str()))
+        {
+            for &index in pinned {
+                if !indices.contains(&index) {
+                    indices.push(index);
+                }
+            }
+        }
+        indices
+    }
+    pub fn len(&self) -> usize {
+        self.trie.strings.len()
+    }
+    /// Only completes queries shorter than `len` characters with strings that have them as an exact prefix,
+    /// so that tiny queries don't match most of the dataset with a few edits
+    pub fn set_min_fuzzy_len(&mut self, len: usize) {
+        self.min_fuzzy_len = len;
+        self.generation = next_generation();
+    }
+    /// Limits the prefix edit distance of results by `policy`, or removes the limit if None
+    pub fn set_threshold(&mut self, policy: Option<ThresholdPolicy>) {
+        self.threshold = policy;
+        self.generation = next_generation();
+    }
+    /// Returns the maximum prefix edit distance of results for a query of `query_len` characters searched with `options`,
+    /// which is the smaller of the threshold policy and the cutoff of `options`, or None if unlimited
+    fn max_prefix_distance(&self, query_len: usize, options: &QueryOptions) -> Option<usize> {
+        let policy = self.threshold.map(|policy| policy.max_edits(query_len));
+        match (policy, options.max_prefix_distance) {
+            (Some(policy), Some(cutoff)) => Some(min(policy, cutoff)),
+            (policy, cutoff) => policy.or(cutoff),
+        }
+    }
+    /// Returns the maximum edit distance of matchings from second deducing for a query of `query_len` characters
+    fn deduced_edits(&self, query_len: usize, options: &QueryOptions) -> usize {
+        min(2, self.max_prefix_distance(query_len, options).unwrap_or(2))
+    }
+
+    /// Evicts the least recently visited query prefixes until `cache` has at most its capacity and its byte budget, if any,
+    /// or the cache size of the autocompleter if the cache doesn't have one
+    ///
+    /// Queries prune their cache automatically once it exceeds the capacity by half, so this only needs to be called
+    /// to shrink a cache right away, such as after lowering its capacity
+    pub fn prune(&self, cache: &mut Cache<'_>) {
+        let max = self.cache_capacity(cache);
+        while cache.len() > max && cache.evict_oldest() {}
+        if let Some(budget) = cache.byte_budget {
+            cache.evict_to_budget(budget);
+        }
+        let budget = cache.byte_budget;
+        if let Some(results) = &mut cache.results {
+            cache.evictions += results.evict(max, budget);
+        }
+    }
+    /// Returns the maximum number of query prefixes kept in `cache`
+    fn cache_capacity(&self, cache: &Cache<'_>) -> usize {
+        cache.capacity.unwrap_or(self.cache_size)
+    }
+    /// Prunes `cache` once it exceeds its capacity or its byte budget by half, so that finding the prefixes to evict
+    /// is amortized over the queries that added them
+    fn prune_amortized(&self, cache: &mut Cache<'_>) {
+        let max = self.cache_capacity(cache);
+        let over = |len: usize, bytes: usize| {
+            len > max + max / 2
+                || cache
+                    .byte_budget
+                    .is_some_and(|budget| bytes > budget + budget / 2)
+        };
+        let results_over = cache
+            .results
+            .as_ref()
+            .is_some_and(|results| over(results.len(), results.bytes));
+        if over(cache.len(), cache.bytes) || results_over {
+            self.prune(cache);
+        }
+    }
+    /// P(|q|,b)
+    pub fn assemble<'q>(&self, q: TreeString<'q>, cache: &mut Cache<'_>) -> MatchingSet<UUU> {
+        self.assemble_bounded(q, cache, &Default::default()).0
+    }
+    /// Same as `assemble`, but stops deducing once the work limits of `options` are exceeded,
+    /// returning the matchings found so far and whether it stopped early
+    fn assemble_bounded<'q>(
+        &self,
+        q: TreeString<'q>,
+        cache: &mut Cache<'_>,
+        options: &QueryOptions,
+    ) -> (MatchingSet<UUU>, bool) {
+        cache.use_generation(self.generation);
+        let use_cache = true;
+        let mut truncated = false;
+        let query_chars: Vec<char> = q.chars().collect();
+        // -0-0- .... -0-|
+        //               | 1
+        //               | 2
+        let mut trail = std::mem::take(&mut cache.trail);
+        if use_cache {
+            let shared = query_chars
+                .iter()
+                .zip(&cache.trail_query)
+                .take_while(|(a, b)| a == b)
+                .count();
+            trail.truncate(shared);
+        } else {
+            trail.clear();
+        }
+        let shared = trail.len();
+        let mut acc = match trail.last() {
+            Some(set) => set.clone(),
+            None => MatchingSet::new_trie(&self.trie),
+        };
+        // the cache is borrowed by visit, so these are added to it afterwards
+        let (mut hits, mut misses) = (0, 0);
+        let mut stored = Vec::<(usize, usize)>::new();
+        cache.visit(q.clone(), |ix, ps| {
+            if truncated {
+                return;
+            }
+            // the first-deducing sets of the shared prefix are already in acc
+            if ix < shared {
+                if ps.sets.is_empty() {
+                    // the prefix was pruned, but P(i,0) is a valid replacement for its delta
+                    ps.sets = vec![trail[ix].clone()];
+                    stored.push((ps.ix, ps.sets[0].bytes()));
+                }
+            } else {
+                if let Some(k) = ps.sets.get(0)
+                    && use_cache
+                {
+                    acc.extend(k);
+                    hits += 1;
+                } else if options.exhausted(acc.matchings.len()) {
+                    truncated = true;
+                    return;
+                } else {
+                    let delta =
+                        self.first_deducing(&acc, query_chars[ix], ix + 1, 0, &options.cancel);
+                    acc.extend(&delta);
+                    if options.cancel.is_cancelled() {
+                        // the delta may be incomplete, so it isn't cached
+                        truncated = true;
+                        return;
+                    }
+                    misses += 1;
+                    stored.push((ps.ix, delta.bytes()));
+                    ps.sets = vec![delta];
+                }
+                trail.push(acc.clone());
+            }
+            // the index is of characters, so this is the last prefix even if the query has multibyte characters
+            if ix + 1 == query_chars.len() {
+                for t in 1..=self.deduced_edits(query_chars.len(), options) {
+                    if let Some(cached) = ps.sets.get(t)
+                        && use_cache
+                    {
+                        acc.extend(cached);
+                        hits += 1;
+                    } else if options.exhausted(acc.matchings.len()) {
+                        truncated = true;
+                        return;
+                    } else {
+                        let new = self.second_deducing(
+                            &acc,
+                            &query_chars,
+                            query_chars.len(),
+                            t,
+                            &options.cancel,
+                        );
+                        acc.extend(&new);
+                        if options.cancel.is_cancelled() {
+                            truncated = true;
+                            return;
+                        }
+                        assert!(ps.sets.len() - 1 == t - 1);
+                        misses += 1;
+                        stored.push((ps.ix, new.bytes()));
+                        ps.sets.push(new);
+                    }
+                }
+            }
+        });
+        cache.trail = trail;
+        cache.trail_query = query_chars;
+        cache.hits += hits;
+        cache.misses += misses;
+        for (ix, bytes) in stored {
+            cache.add_set_bytes(ix, bytes);
+        }
+        self.prune_amortized(cache);
+
+        (acc, truncated)
+    }
+    /// P(|q|,b) restricted to descendants of `node`, whose prefix is exactly the first `node.depth` characters of `q`,
+    /// with whether it stopped early like `assemble_bounded`
+    ///
+    /// This doesn't use the cache, which holds matchings from the root
+    fn assemble_pinned(
+        &self,
+        node: &Node<UUU, SSS>,
+        q: &str,
+        options: &QueryOptions,
+    ) -> (MatchingSet<UUU>, bool) {
+        let query_chars: Vec<char> = q.chars().collect();
+        let pinned = node.depth as usize;
+        let mut acc = MatchingSet::default();
+        acc.insert(Matching {
+            query_prefix_len: pinned as UUU,
+            node: node.id(),
+            edit_distance: 0,
+        });
+        for ix in pinned..query_chars.len() {
+            if options.exhausted(acc.matchings.len()) {
+                return (acc, true);
+            }
+            let delta = self.first_deducing(&acc, query_chars[ix], ix + 1, 0, &options.cancel);
+            acc.extend(&delta);
+        }
+        if query_chars.len() > pinned {
+            for t in 1..=self.deduced_edits(query_chars.len(), options) {
+                if options.exhausted(acc.matchings.len()) {
+                    return (acc, true);
+                }
+                let new =
+                    self.second_deducing(&acc, &query_chars, query_chars.len(), t, &options.cancel);
+                acc.extend(&new);
+            }
+        }
+        (acc, options.cancel.is_cancelled())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Matching<UUU>
+where
+    UUU: Clone,
+{
+    query_prefix_len: UUU,
+    node: NodeID,
+    edit_distance: UUU,
+}
 
 impl<'stored> Matching<UUU> {
     /// Returns an upper bound on the edit distance between the query and a prefix of length `stored_len` that intersects
@@ -550,6 +1944,10 @@ impl MatchingSet<UUU> {
             iter: self.matchings.iter(),
         }
     }
+    /// Returns the estimated number of bytes of the matchings
+    fn bytes(&self) -> usize {
+        self.matchings.len() * std::mem::size_of::<((UUU, NodeID), UUU)>()
+    }
     /// Returns whether there is a matching for `query_prefix_len` and `node`
     fn contains(&self, query_prefix_len: UUU, node: NodeID) -> bool {
         self.matchings.contains_key(&(query_prefix_len, node))
@@ -653,14 +2051,1043 @@ impl MatchingRankKey {
     }
 }
 
-impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
     /// Returns the top `requested` number of strings with the best prefix distance from the query
     /// sorted by prefix edit distance and then lexicographical order,
     /// or all strings available if `requested` is larger than the number stored
     ///
+    /// The results only depend on the stored strings and the query (not on hash map iteration order),
+    /// so they are the same across runs
+    ///
+    /// Repeated queries return memoized results if `cache` memoizes them (see `Cache::set_memoize_results`)
+    ///
     /// Assumes `query`'s length in Unicode characters is bounded by UUU; will truncate to UUU::MAX characters otherwise
-    pub fn autocomplete(&'_ self, query: &str, cache: &mut Cache<'_>) -> Vec<MeasuredPrefix> {
+    pub fn autocomplete(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        if cache.memoizes_results() {
+            return self.autocomplete_memoized(query, requested, cache);
+        }
+        self.autocomplete_filtered(query, requested, |_| true, cache)
+    }
+    /// Same as `autocomplete`, but only strings for which `filter` returns true can occupy the `requested` slots
+    pub fn autocomplete_filtered(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        filter: impl FnMut(&str) -> bool,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        self.autocomplete_with_options(query, requested, filter, &Default::default(), cache)
+    }
+    /// Returns the stored string closest to `query` for each of the `requested` payloads whose strings are the closest,
+    /// or for every payload if there are fewer
+    ///
+    /// A payload can be found through a worse string before a better one, so strings aren't filtered by the payloads seen so far.
+    /// More strings are fetched instead until there are `requested` distinct payloads, keeping the closest string of each
+    pub(crate) fn autocomplete_payloads(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> HashMap<P, MeasuredPrefix>
+    where
+        P: Copy + Eq + Hash,
+    {
+        let mut best = HashMap::<P, MeasuredPrefix>::new();
+        let mut fetched = requested;
+        while fetched > 0 {
+            let matched = self.autocomplete_filtered(query, fetched, |_| true, cache);
+            best.clear();
+            for measure in &matched {
+                for &payload in self.payloads(&measure.string) {
+                    match best.entry(payload) {
+                        hash_map::Entry::Occupied(mut entry) => {
+                            if *measure < *entry.get() {
+                                entry.insert(measure.clone());
+                            }
+                        }
+                        hash_map::Entry::Vacant(entry) => {
+                            entry.insert(measure.clone());
+                        }
+                    }
+                }
+            }
+            // fewer strings than fetched means that there are no more to fetch
+            if best.len() >= requested || matched.len() < fetched {
+                break;
+            }
+            fetched = fetched.saturating_mul(2);
+        }
+        best
+    }
+    /// Returns the results of `autocomplete` for each of the `queries` in the same order, sharing a cache between them
+    pub fn autocomplete_batch(
+        &'_ self,
+        queries: &[&str],
+        requested: usize,
+    ) -> Vec<Vec<MeasuredPrefix>> {
+        let mut cache = Cache::default();
+        queries
+            .iter()
+            .map(|query| self.autocomplete(query, requested, &mut cache))
+            .collect()
+    }
+    /// Same as `autocomplete_batch`, but runs the queries in parallel with a cache for each thread,
+    /// which requires the payloads to be shared between threads
+    #[cfg(feature = "rayon")]
+    pub fn par_autocomplete_batch(
+        &'_ self,
+        queries: &[&str],
+        requested: usize,
+    ) -> Vec<Vec<MeasuredPrefix>>
+    where
+        P: Sync,
+    {
+        use rayon::prelude::*;
+        queries
+            .par_iter()
+            .map_init(Cache::default, |cache, query| {
+                self.autocomplete(query, requested, cache)
+            })
+            .collect()
+    }
+    /// Same as `autocomplete_filtered`, but searches according to `options`
+    pub fn autocomplete_with_options(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        filter: impl FnMut(&str) -> bool,
+        options: &QueryOptions,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        self.autocomplete_bounded(query, requested, filter, options, cache)
+            .0
+    }
+    /// Same as `autocomplete_with_options`, but also returns whether the search stopped early
+    /// because of the `deadline` or `max_matchings` of `options`, in which case the results may be worse
+    pub fn autocomplete_bounded(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        filter: impl FnMut(&str) -> bool,
+        options: &QueryOptions,
+        cache: &mut Cache<'_>,
+    ) -> (Vec<MeasuredPrefix>, bool) {
+        let query: &str = &self.normalized(query);
+        let (indices, truncated) = self.collect(query, requested, filter, options, cache);
+        let mut measures = self.measure(indices.into_keys(), query);
+        self.rank_jaro_winkler(&mut measures, query, options.jaro_winkler);
+        let result = measures
+            .into_iter()
+            .map(|measure| MeasuredPrefix {
+                string: self.display(measure.index).to_string(),
+                prefix_distance: measure.prefix_distance,
+            })
+            .collect();
+        (result, truncated)
+    }
+    /// Same as `autocomplete`, but replaces the contents of `out` with the results,
+    /// reusing its allocation and those of its strings, and collects them in the buffers of `scratch`
+    pub fn autocomplete_into(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+        scratch: &mut QueryScratch,
+        out: &mut Vec<MeasuredPrefix>,
+    ) {
+        let query: &str = &self.normalized(query);
+        let QueryScratch {
+            indices,
+            measures,
+            distances,
+        } = scratch;
+        self.collect_into(
+            query,
+            requested,
+            |_| true,
+            &Default::default(),
+            cache,
+            indices,
+        );
+        self.measure_into(
+            indices.drain().map(|(index, _)| index),
+            query,
+            distances,
+            measures,
+        );
+        out.truncate(measures.len());
+        for (i, measure) in measures.iter().enumerate() {
+            let string = self.display(measure.index);
+            if let Some(reused) = out.get_mut(i) {
+                reused.string.clear();
+                reused.string.push_str(string);
+                reused.prefix_distance = measure.prefix_distance;
+            } else {
+                out.push(MeasuredPrefix {
+                    string: string.to_string(),
+                    prefix_distance: measure.prefix_distance,
+                });
+            }
+        }
+    }
+    /// Same as `autocomplete`, but returns indices of the stored strings instead of copying them
+    ///
+    /// The strings can be retrieved with `get`
+    pub fn autocomplete_indices(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredIndex> {
+        self.autocomplete_normalized(&self.normalized(query), requested, cache)
+    }
+    /// Same as `autocomplete_indices`, but `query` is already normalized
+    fn autocomplete_normalized(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredIndex> {
+        let (indices, _) = self.collect(query, requested, |_| true, &Default::default(), cache);
+        self.measure(indices.into_keys(), query)
+    }
+    /// Same as `autocomplete`, but each result also has the part of its string that matched the query
+    pub fn autocomplete_matched(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MatchedPrefix> {
+        let query: &str = &self.normalized(query);
+        let (indices, _) = self.collect(query, requested, |_| true, &Default::default(), cache);
+        self.measure(indices.keys().copied(), query)
+            .into_iter()
+            .map(|measure| {
+                let node = &self.trie.nodes[indices[&measure.index]];
+                let depth = node.depth as usize;
+                MatchedPrefix {
+                    measure: MeasuredPrefix {
+                        string: self.display(measure.index).to_string(),
+                        prefix_distance: measure.prefix_distance,
+                    },
+                    depth,
+                    span: 0..self.display_prefix_len(measure.index, depth),
+                }
+            })
+            .collect()
+    }
+    /// Same as `autocomplete`, but groups the results by the branch of the trie below the stored prefix that matched the query,
+    /// so "appl" may have groups for "apple", "appli" and "appla"
+    ///
+    /// Groups are ordered by their best result
+    pub fn autocomplete_grouped(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<PrefixGroup> {
+        let query: &str = &self.normalized(query);
+        let (indices, _) = self.collect(query, requested, |_| true, &Default::default(), cache);
+        let mut groups = Vec::<PrefixGroup>::new();
+        for measure in self.measure(indices.keys().copied(), query) {
+            let string = self.get(measure.index);
+            // the string is from the range of the node, so it has the node's prefix
+            let matched = self
+                .trie
+                .prefix(&self.trie.nodes[indices[&measure.index]])
+                .len();
+            let end = string[matched..]
+                .chars()
+                .next()
+                .map_or(string.len(), |next| matched + next.len_utf8());
+            let result = MeasuredPrefix {
+                string: self.display(measure.index).to_string(),
+                prefix_distance: measure.prefix_distance,
+            };
+            match groups
+                .iter_mut()
+                .find(|group| group.prefix == string[..end])
+            {
+                Some(group) => group.results.push(result),
+                None => groups.push(PrefixGroup {
+                    prefix: string[..end].to_string(),
+                    results: vec![result],
+                }),
+            }
+        }
+        groups
+    }
+    /// Precomputes the best `k` strings with the prefix of every trie node by descending weight and then lexicographical order,
+    /// so that `complete` and `autocomplete` with an empty query take O(`requested`) time for `requested` up to `k`
+    pub fn precompute_top(&mut self, k: usize) {
+        let nodes = &self.trie.nodes;
+        let mut lists: Vec<Vec<SSS>> = vec![Vec::new(); nodes.len()];
+        let rank = |&index: &SSS| (Reverse(self.weight(index)), index);
+        // descendants come after their ancestors in the trie, so children are visited before their parents
+        for id in (0..nodes.len()).rev() {
+            let node = &nodes[id];
+            let mut candidates = Vec::<SSS>::new();
+            // a string equal to the prefix has no child node, and it's the first with the prefix
+            if let Some(first) = node.string_range.clone().next() {
+                if self.trie.strings[first as usize].len() == self.trie.prefix(node).len() {
+                    candidates.push(first);
+                }
+            }
+            for child in self.trie.children(node) {
+                candidates.extend_from_slice(&lists[child.id()]);
+            }
+            candidates.sort_by_key(rank);
+            candidates.truncate(k);
+            lists[id] = candidates;
+        }
+
+        let mut offsets = Vec::<SSS>::with_capacity(nodes.len() + 1);
+        let mut indices = Vec::<SSS>::new();
+        for list in lists {
+            offsets.push(indices.len() as SSS);
+            indices.extend(list);
+        }
+        offsets.push(indices.len() as SSS);
+        self.top = TopStrings {
+            k,
+            offsets,
+            indices,
+        };
+    }
+    /// Returns up to `requested` stored strings that have `prefix`, sorted by descending weight and then lexicographical order
+    pub fn complete(&self, prefix: &str, requested: usize) -> Vec<MeasuredPrefix> {
+        let Some(node) = self.trie.descend(&self.normalized(prefix)) else {
+            return vec![];
+        };
+        let indices: Vec<SSS> = if requested <= self.top.k {
+            self.top
+                .get(node.id())
+                .iter()
+                .copied()
+                .take(requested)
+                .collect()
+        } else if self.weights.is_empty() {
+            // strings are sorted, so these are the best when all weights are equal
+            node.string_range.clone().take(requested).collect()
+        } else {
+            let mut indices: Vec<SSS> = node.string_range.clone().collect();
+            indices.sort_by_key(|&index| (Reverse(self.weight(index)), index));
+            indices.truncate(requested);
+            indices
+        };
+        indices
+            .into_iter()
+            .map(|index| MeasuredPrefix {
+                string: self.display(index).to_string(),
+                prefix_distance: 0,
+            })
+            .collect()
+    }
+    /// Returns the longest prefix shared by all stored strings that start with `query`, which extends `query` like tab-completion,
+    /// or the longest stored prefix of `query` if no strings start with it
+    pub fn longest_common_prefix(&self, query: &str) -> &str {
+        let mut node = self.trie.root();
+        for character in self.normalized(query).chars() {
+            match self
+                .trie
+                .children(node)
+                .find(|child| child.character == character)
+            {
+                Some(child) => node = child,
+                None => return self.trie.prefix(node),
+            }
+        }
+        loop {
+            let mut children = self.trie.children(node);
+            match (children.next(), children.next()) {
+                // no string ends at the node, and they all continue with the same character
+                (Some(child), None) if child.string_range == node.string_range => node = child,
+                _ => return self.trie.prefix(node),
+            }
+        }
+    }
+    /// Returns up to `requested` stored strings within `max_distance` edits of the whole `query`,
+    /// sorted by edit distance and then lexicographical order
+    pub fn correct(
+        &self,
+        query: &str,
+        max_distance: usize,
+        requested: usize,
+    ) -> Vec<MeasuredString> {
+        self.correct_filtered(&self.normalized(query), max_distance, requested, None)
+    }
+    /// Same as `correct`, but once the descent reaches a node with at most `QGRAM_SCAN_STRINGS` strings,
+    /// they're discarded by a `QGramFilter` with q-grams of `q` characters (if `q` isn't 0) before being verified one at a time,
+    /// which skips most of the strings of long queries that share a prefix with them but little else
+    pub fn correct_with_qgrams(
+        &self,
+        query: &str,
+        max_distance: usize,
+        requested: usize,
+        q: usize,
+    ) -> Vec<MeasuredString> {
+        let query = self.normalized(query);
+        let filter = (q > 0).then(|| QGramFilter::new(&query, q));
+        self.correct_filtered(&query, max_distance, requested, filter.as_ref())
+    }
+    /// Same as `correct` for the normalized `query`, but verifies the strings of small nodes one at a time
+    /// after discarding them with `filter`, if any
+    fn correct_filtered(
+        &self,
+        query: &str,
+        max_distance: usize,
+        requested: usize,
+        filter: Option<&QGramFilter>,
+    ) -> Vec<MeasuredString> {
+        let query: Vec<char> = query.chars().collect();
+        let mut result = Vec::<MeasuredString>::new();
+        // each node has the row of edit distances between its prefix and the prefixes of the query
+        let mut stack = vec![(self.trie.root(), (0..=query.len()).collect::<Vec<usize>>())];
+        while let Some((node, row)) = stack.pop() {
+            if let Some(filter) = filter
+                && node.string_range.len() <= QGRAM_SCAN_STRINGS
+            {
+                let prefix_len = self.trie.prefix(node).len();
+                for index in node.string_range.clone() {
+                    let string: &str = &self.trie.strings[index as usize];
+                    if !filter.may_be_within(string, max_distance) {
+                        continue;
+                    }
+                    // the rest of the string extends the node's row like the descendants would
+                    let mut string_row = row.clone();
+                    for character in string[prefix_len..].chars() {
+                        if string_row.iter().all(|&distance| distance > max_distance) {
+                            break;
+                        }
+                        string_row = next_lev_row(&string_row, &query, character);
+                    }
+                    if string_row[query.len()] <= max_distance {
+                        result.push(MeasuredString {
+                            string: self.display(index).to_string(),
+                            distance: string_row[query.len()],
+                        });
+                    }
+                }
+                continue;
+            }
+            // a string equal to the prefix is the first with the prefix
+            if row[query.len()] <= max_distance
+                && let Some(first) = node.string_range.clone().next()
+                && self.trie.strings[first as usize].len() == self.trie.prefix(node).len()
+            {
+                result.push(MeasuredString {
+                    string: self.display(first).to_string(),
+                    distance: row[query.len()],
+                });
+            }
+            for child in self.trie.children(node) {
+                let child_row = next_lev_row(&row, &query, child.character);
+                // the edit distance of longer strings can't be less than the minimum of the row
+                if child_row
+                    .iter()
+                    .min()
+                    .is_some_and(|&distance| distance <= max_distance)
+                {
+                    stack.push((child, child_row));
+                }
+            }
+        }
+        result.sort();
+        result.truncate(requested);
+        result
+    }
+    /// Returns the number of stored strings with a prefix edit distance of at most `b` from `query` without measuring them
+    pub fn count_within(&self, query: &str, b: usize) -> usize {
+        let query: Vec<char> = self.normalized(query).chars().collect();
+        let mut count = 0;
+        let mut stack = vec![(self.trie.root(), (0..=query.len()).collect::<Vec<usize>>())];
+        while let Some((node, row)) = stack.pop() {
+            if row[query.len()] <= b {
+                // every string with the prefix is within `b`, and none of them are counted by another node
+                count += node.string_range.len();
+                continue;
+            }
+            for child in self.trie.children(node) {
+                let child_row = next_lev_row(&row, &query, child.character);
+                if child_row
+                    .iter()
+                    .min()
+                    .is_some_and(|&distance| distance <= b)
+                {
+                    stack.push((child, child_row));
+                }
+            }
+        }
+        count
+    }
+    /// Returns the indices of strings with the prefix of `node`, starting with its precomputed best strings if any
+    ///
+    /// May repeat the precomputed strings
+    fn ranked_strings<'a>(&'a self, node: &Node<UUU, SSS>) -> impl Iterator<Item = SSS> + 'a {
+        let top = self.top.get(node.id());
+        let rest = if top.len() < self.top.k {
+            // the precomputed strings are all of the node's strings
+            0..0
+        } else {
+            node.string_range.clone()
+        };
+        top.iter().copied().chain(rest)
+    }
+    /// Returns the weight of the stored string at `index`, which is 0 if the autocompleter is unweighted
+    pub fn weight(&self, index: SSS) -> u32 {
+        self.weights.get(index as usize).copied().unwrap_or(0)
+    }
+    /// Returns how many times the stored string at `index` occurred in the source, which is 1 unless duplicates were counted
+    pub fn count(&self, index: SSS) -> u32 {
+        self.counts.get(index as usize).copied().unwrap_or(1)
+    }
+    /// Returns the strings at `indices` with their prefix edit distances from `query`,
+    /// sorted with the strings pinned for `query` first, then by prefix edit distance, descending weight,
+    /// and lexicographical order
+    ///
+    /// `query` must already be normalized, like for the other internal steps of a search, since normalizing twice may not
+    /// give the same string
+    fn measure(&self, indices: impl IntoIterator<Item = SSS>, query: &str) -> Vec<MeasuredIndex> {
+        let mut result = Vec::new();
+        self.measure_into(
+            indices,
+            query,
+            &mut levenshtein::Scratch::new(),
+            &mut result,
+        );
+        result
+    }
+    /// Same as `measure`, but replaces the contents of `result` and computes the distances in `scratch`
+    fn measure_into(
+        &self,
+        indices: impl IntoIterator<Item = SSS>,
+        query: &str,
+        scratch: &mut levenshtein::Scratch,
+        result: &mut Vec<MeasuredIndex>,
+    ) {
+        result.clear();
+        result.extend(indices.into_iter().map(|index| MeasuredIndex {
+            index,
+            prefix_distance: levenshtein::prefix_distance_with(scratch, query, self.get(index)),
+        }));
+
+        let pins = self.pins_for(query);
+        // strings are sorted, so ordering by index is the same as lexicographical order
+        result.sort_by_key(|measure| {
+            (
+                pins.iter()
+                    .position(|&index| index == measure.index)
+                    .unwrap_or(usize::MAX),
+                measure.prefix_distance,
+                Reverse(self.weight(measure.index)),
+                measure.index,
+            )
+        });
+    }
+    /// Sorts `measures` from `measure` for the normalized `query` again according to `ranking`, keeping the pinned strings first
+    fn rank_jaro_winkler(
+        &self,
+        measures: &mut [MeasuredIndex],
+        query: &str,
+        ranking: JaroWinklerRanking,
+    ) {
+        if ranking == JaroWinklerRanking::None {
+            return;
+        }
+        let pins = self.pins_for(query);
+        let mut keyed: Vec<(usize, usize, f64, MeasuredIndex)> = measures
+            .iter()
+            .map(|&measure| {
+                let pin = pins
+                    .iter()
+                    .position(|&index| index == measure.index)
+                    .unwrap_or(usize::MAX);
+                let similarity = levenshtein::jaro_winkler(query, self.get(measure.index));
+                (pin, measure.prefix_distance, similarity, measure)
+            })
+            .collect();
+        // stable, so the order of `measure` breaks ties
+        keyed.sort_by(
+            |(pin, distance, similarity, _), (other_pin, other_distance, other, _)| {
+                let by_similarity = other.total_cmp(similarity);
+                pin.cmp(other_pin).then(match ranking {
+                    JaroWinklerRanking::Primary => by_similarity.then(distance.cmp(other_distance)),
+                    _ => distance.cmp(other_distance).then(by_similarity),
+                })
+            },
+        );
+        for (measure, (_, _, _, ranked)) in measures.iter_mut().zip(keyed) {
+            *measure = ranked;
+        }
+    }
+    /// Returns the stored string at `index` (panics if `index` is out of bounds)
+    pub fn get(&self, index: SSS) -> &str {
+        &self.trie.strings[index as usize]
+    }
+    /// Returns the stored string at `index` as it was before normalization, which is used in results
+    /// and is the same as `get` unless the originals were kept (panics if `index` is out of bounds)
+    pub fn display(&self, index: SSS) -> &str {
+        match self.originals.get(index as usize) {
+            Some(original) => original,
+            None => self.get(index),
+        }
+    }
+    /// Returns the length in bytes of the shortest prefix of the string at `index` as displayed
+    /// whose normalization has at least `chars` characters, which covers the first `chars` characters of the stored string
+    fn display_prefix_len(&self, index: SSS, chars: usize) -> usize {
+        let stored = self.get(index);
+        let display = self.display(index);
+        if display == stored {
+            return stored
+                .char_indices()
+                .nth(chars)
+                .map_or(stored.len(), |(end, _)| end);
+        }
+        display
+            .char_indices()
+            .map(|(end, _)| end)
+            .chain([display.len()])
+            .find(|&end| self.normalized(&display[..end]).chars().count() >= chars)
+            .unwrap_or(display.len())
+    }
+    /// Same as `autocomplete`, but each result also has the edits from `query` to the best matching prefix of its string
+    pub fn autocomplete_with_alignments(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<AlignedPrefix> {
+        let query: &str = &self.normalized(query);
+        self.autocomplete_normalized(query, requested, cache)
+            .into_iter()
+            .map(|measure| AlignedPrefix {
+                edits: levenshtein::prefix_alignment(query, self.get(measure.index)),
+                measure: MeasuredPrefix {
+                    string: self.display(measure.index).to_string(),
+                    prefix_distance: measure.prefix_distance,
+                },
+            })
+            .collect()
+    }
+    /// Same as `autocomplete`, but each result also has the edit distance between `query` and its whole string
+    pub fn autocomplete_with_distances(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<DistancedPrefix> {
+        let query: &str = &self.normalized(query);
+        self.autocomplete_normalized(query, requested, cache)
+            .into_iter()
+            .map(|measure| DistancedPrefix {
+                distance: levenshtein::edit_distance(query, self.get(measure.index)),
+                measure: MeasuredPrefix {
+                    string: self.display(measure.index).to_string(),
+                    prefix_distance: measure.prefix_distance,
+                },
+            })
+            .collect()
+    }
+    /// Same as `autocomplete`, but each result also has similarities between 0.0 and 1.0 normalized by the lengths of the strings,
+    /// to combine with other signals in downstream ranking
+    pub fn autocomplete_scored(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<ScoredPrefix> {
+        let query: &str = &self.normalized(query);
+        let query_len = query.chars().count();
+        self.autocomplete_normalized(query, requested, cache)
+            .into_iter()
+            .map(|measure| {
+                let prefix_similarity = if query_len == 0 {
+                    1.0
+                } else {
+                    1.0 - measure.prefix_distance as f64 / query_len as f64
+                };
+                ScoredPrefix {
+                    prefix_similarity,
+                    similarity: levenshtein::similarity(query, self.get(measure.index)),
+                    measure: MeasuredPrefix {
+                        string: self.display(measure.index).to_string(),
+                        prefix_distance: measure.prefix_distance,
+                    },
+                }
+            })
+            .collect()
+    }
+    /// Same as `autocomplete`, but each result also has how many times its string occurred in the source
+    pub fn autocomplete_counted(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<CountedPrefix> {
+        self.autocomplete_indices(query, requested, cache)
+            .into_iter()
+            .map(|measure| CountedPrefix {
+                measure: MeasuredPrefix {
+                    string: self.display(measure.index).to_string(),
+                    prefix_distance: measure.prefix_distance,
+                },
+                count: self.count(measure.index),
+            })
+            .collect()
+    }
+    /// Same as `autocomplete`, but ranks the results by their weighted prefix edit distances with `costs`,
+    /// such as `KeyboardCosts` to prefer typos of adjacent keys
+    ///
+    /// Only the strings with the fewest edits are ranked, so a string with more but cheaper edits may be missed
+    /// (see `autocomplete_weighted_exact`)
+    pub fn autocomplete_weighted(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        costs: &impl EditCosts,
+        cache: &mut Cache<'_>,
+    ) -> Vec<WeightedPrefix> {
+        let query: &str = &self.normalized(query);
+        let (indices, _) = self.collect(query, requested, |_| true, &Default::default(), cache);
+        self.measure_weighted(indices.into_keys(), query, costs)
+    }
+    /// Same as `autocomplete_weighted`, but also ranks every string with few enough edits
+    /// to cost less than the worst result, using `EditCosts::min_cost` as the cost of each edit,
+    /// so that no string with a lower cost is missed (every string is ranked if the minimum cost is 0)
+    pub fn autocomplete_weighted_exact(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        costs: &impl EditCosts,
+        cache: &mut Cache<'_>,
+    ) -> Vec<WeightedPrefix> {
+        let result = self.autocomplete_weighted(query, requested, costs, cache);
+        // with fewer results than requested, every string was ranked
+        if result.len() < requested {
+            return result;
+        }
+        let within = match costs.min_cost() {
+            0 => self.len(),
+            min_cost => {
+                let max_edits = result.last().map_or(0, |worst| worst.cost / min_cost);
+                self.count_within(query, max_edits)
+            }
+        };
+        if within <= requested {
+            return result;
+        }
+        let query: &str = &self.normalized(query);
+        let (indices, _) = self.collect(query, within, |_| true, &Default::default(), cache);
+        let mut result = self.measure_weighted(indices.into_keys(), query, costs);
+        result.truncate(requested);
+        result
+    }
+    /// Same as `autocomplete`, but ranks the results by their distances from `query` by `metric`,
+    /// such as `DamerauPrefixEditDistance` or `JaroWinklerDistance`, while the strings are still found by the prefix edit distance
+    ///
+    /// Only the strings with the fewest edits are ranked, so `requested` may need to be larger than the number of results used
+    pub fn autocomplete_with_metric(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        metric: &impl Metric,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MetricPrefix> {
+        let query: &str = &self.normalized(query);
+        let (indices, _) = self.collect(query, requested, |_| true, &Default::default(), cache);
+        let pins = self.pins_for(query);
+        let mut result: Vec<(usize, MetricPrefix)> = self
+            .measure(indices.into_keys(), query)
+            .into_iter()
+            .map(|measure| {
+                let pin = pins
+                    .iter()
+                    .position(|&index| index == measure.index)
+                    .unwrap_or(usize::MAX);
+                let ranked = MetricPrefix {
+                    distance: metric.distance(query, self.get(measure.index)),
+                    measure: MeasuredPrefix {
+                        string: self.display(measure.index).to_string(),
+                        prefix_distance: measure.prefix_distance,
+                    },
+                };
+                (pin, ranked)
+            })
+            .collect();
+        // stable, so strings with the same distance stay in the order of `measure`
+        result.sort_by(|(pin, ranked), (other_pin, other)| {
+            pin.cmp(other_pin)
+                .then(ranked.distance.total_cmp(&other.distance))
+        });
+        result.into_iter().map(|(_, ranked)| ranked).collect()
+    }
+    /// Same as `autocomplete`, but each character of `query` has a confidence between 0.0 and 1.0, such as from a swipe keyboard
+    /// or speech recognition, and results are ranked by their prefix edit distances where the cost of substituting a character
+    /// is discounted by 1 - confidence
+    ///
+    /// Only the strings with the fewest edits are ranked, so a string that substitutes more low-confidence characters may be missed
+    pub fn autocomplete_confident(
+        &'_ self,
+        query: &[(char, f32)],
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<ConfidencePrefix> {
+        // each character is normalized on its own to keep its confidence
+        let query: Vec<(char, f32)> = query
+            .iter()
+            .flat_map(|&(character, confidence)| {
+                let normalized: Vec<char> =
+                    self.normalized(&character.to_string()).chars().collect();
+                normalized
+                    .into_iter()
+                    .map(move |character| (character, confidence))
+            })
+            .collect();
+        let string: String = query.iter().map(|&(character, _)| character).collect();
+        let (indices, _) = self.collect(&string, requested, |_| true, &Default::default(), cache);
+        let pins = self.pins_for(&string);
+        let mut result: Vec<(usize, ConfidencePrefix)> = self
+            .measure(indices.into_keys(), &string)
+            .into_iter()
+            .map(|measure| {
+                let pin = pins
+                    .iter()
+                    .position(|&index| index == measure.index)
+                    .unwrap_or(usize::MAX);
+                let ranked = ConfidencePrefix {
+                    cost: levenshtein::confidence_prefix_edit_distance(
+                        &query,
+                        self.get(measure.index),
+                    ),
+                    measure: MeasuredPrefix {
+                        string: self.display(measure.index).to_string(),
+                        prefix_distance: measure.prefix_distance,
+                    },
+                };
+                (pin, ranked)
+            })
+            .collect();
+        // stable, so strings with the same cost stay in the order of `measure`
+        result.sort_by(|(pin, ranked), (other_pin, other)| {
+            pin.cmp(other_pin).then(ranked.cost.total_cmp(&other.cost))
+        });
+        result.into_iter().map(|(_, ranked)| ranked).collect()
+    }
+    /// Same as `measure`, but ranks the strings by their weighted prefix edit distances from `query` before the number of edits
+    fn measure_weighted(
+        &self,
+        indices: impl IntoIterator<Item = SSS>,
+        query: &str,
+        costs: &impl EditCosts,
+    ) -> Vec<WeightedPrefix> {
+        let pins = self.pins_for(query);
+        let mut result: Vec<(SSS, WeightedPrefix)> = self
+            .measure(indices, query)
+            .into_iter()
+            .map(|measure| {
+                let cost = levenshtein::weighted_prefix_edit_distance(
+                    query,
+                    self.get(measure.index),
+                    costs,
+                );
+                let weighted = WeightedPrefix {
+                    measure: MeasuredPrefix {
+                        string: self.display(measure.index).to_string(),
+                        prefix_distance: measure.prefix_distance,
+                    },
+                    cost,
+                };
+                (measure.index, weighted)
+            })
+            .collect();
+        // stable, so strings with the same cost stay in the order of `measure`
+        result.sort_by_key(|(index, weighted)| {
+            (
+                pins.iter()
+                    .position(|pin| pin == index)
+                    .unwrap_or(usize::MAX),
+                weighted.cost,
+            )
+        });
+        result.into_iter().map(|(_, weighted)| weighted).collect()
+    }
+    /// Same as `autocomplete`, but also returns the stored prefix that best matched the whole query,
+    /// which differs from the query if it was corrected (or None if no prefix matched it)
+    pub fn autocomplete_corrected(
+        &'_ self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> (Option<String>, Vec<MeasuredPrefix>) {
+        let query: &str = &self.normalized(query);
         let set = self.assemble(query.into(), cache);
+        let ranked = self.rank(&set, query);
+        let query_len = query.chars().count();
+        let corrected = ranked
+            .iter()
+            .find(|(key, _)| key.query_prefix_len as usize == query_len)
+            .and_then(|(_, nodes)| nodes.first())
+            .map(|&id| self.trie.prefix(&self.trie.nodes[id]).to_string());
+        let mut indices = HashMap::new();
+        self.fill(
+            ranked,
+            requested,
+            self.within_threshold(query, &Default::default(), |_| true),
+            &mut indices,
+        );
+        let result = self
+            .measure(indices.into_keys(), query)
+            .into_iter()
+            .map(|measure| MeasuredPrefix {
+                string: self.display(measure.index).to_string(),
+                prefix_distance: measure.prefix_distance,
+            })
+            .collect();
+        (corrected, result)
+    }
+    /// Returns the indices of up to `requested` strings that pass `filter`, taken from the best matchings for the normalized `query`,
+    /// mapped to the id of the matching node each string was taken from, and whether the search stopped early
+    fn collect(
+        &self,
+        query: &str,
+        requested: usize,
+        filter: impl FnMut(&str) -> bool,
+        options: &QueryOptions,
+        cache: &mut Cache<'_>,
+    ) -> (HashMap<SSS, NodeID>, bool) {
+        let mut indices = HashMap::new();
+        let truncated = self.collect_into(query, requested, filter, options, cache, &mut indices);
+        (indices, truncated)
+    }
+    /// Same as `collect`, but replaces the contents of `indices` and returns whether the search stopped early
+    fn collect_into(
+        &self,
+        query: &str,
+        requested: usize,
+        mut filter: impl FnMut(&str) -> bool,
+        options: &QueryOptions,
+        cache: &mut Cache<'_>,
+        indices: &mut HashMap<SSS, NodeID>,
+    ) -> bool {
+        indices.clear();
+        if requested == 0 {
+            return false;
+        }
+        // excluded strings don't take any of the requested slots
+        let mut filter = |string: &str| !options.excludes(string) && filter(string);
+
+        let max_distance = self.max_prefix_distance(query.chars().count(), options);
+        let mut pinned = Vec::<SSS>::new();
+        for index in self.pins_for(query) {
+            let string = self.get(index);
+            if pinned.len() < requested
+                && max_distance.map_or(true, |max_distance| {
+                    levenshtein::prefix_distance_within(query, string, max_distance).is_some()
+                })
+                && filter(string)
+            {
+                pinned.push(index);
+            }
+        }
+        let truncated = if pinned.len() < requested {
+            self.search(
+                query,
+                requested - pinned.len(),
+                |string| !pinned.iter().any(|&index| self.get(index) == string) && filter(string),
+                options,
+                cache,
+                indices,
+            )
+        } else {
+            false
+        };
+        for index in pinned {
+            indices.insert(index, self.trie.root().id());
+        }
+        truncated
+    }
+    /// Same as `collect_into`, but without the pinned strings, and adds to `indices` without clearing them
+    fn search(
+        &self,
+        query: &str,
+        requested: usize,
+        mut filter: impl FnMut(&str) -> bool,
+        options: &QueryOptions,
+        cache: &mut Cache<'_>,
+        indices: &mut HashMap<SSS, NodeID>,
+    ) -> bool {
+        let exact_only = query.chars().count() < self.min_fuzzy_len;
+        if query.is_empty() || options.exact_first || exact_only {
+            // strings with the query as a prefix have PEDs of 0, so there's no need to search if there are enough
+            // (every string matches an empty query)
+            if let Some(node) = self.trie.descend(query) {
+                let filled = self.trie.fill_results(
+                    node,
+                    self.ranked_strings(node),
+                    indices,
+                    requested,
+                    &mut filter,
+                );
+                if filled || query.is_empty() {
+                    return false;
+                }
+            }
+            if exact_only {
+                return false;
+            }
+            // the search below finds the exact matches again
+            indices.clear();
+        }
+        if options.algorithm == SearchAlgorithm::Automaton {
+            let filter = self.within_threshold(query, options, filter);
+            return self.search_automaton(query, requested, filter, options, indices);
+        }
+        let (set, truncated) = if options.strict_prefix > 0 {
+            let pinned_end = query
+                .char_indices()
+                .nth(options.strict_prefix)
+                .map_or(query.len(), |(end, _)| end);
+            match self.trie.descend(&query[..pinned_end]) {
+                Some(node) => self.assemble_pinned(node, query, options),
+                None => return false,
+            }
+        } else {
+            self.assemble_bounded(query.into(), cache, options)
+        };
+        let ranked = self.rank(&set, query);
+        self.fill(
+            ranked,
+            requested,
+            self.within_threshold(query, options, filter),
+            indices,
+        );
+        truncated
+    }
+    /// Returns `filter` restricted to strings within the maximum prefix edit distance from `query` searched with `options`
+    fn within_threshold<'a>(
+        &self,
+        query: &'a str,
+        options: &QueryOptions,
+        mut filter: impl FnMut(&str) -> bool + 'a,
+    ) -> impl FnMut(&str) -> bool + 'a {
+        let max_distance = self.max_prefix_distance(query.chars().count(), options);
+        move |string| {
+            max_distance.map_or(true, |max_distance| {
+                levenshtein::prefix_distance_within(query, string, max_distance).is_some()
+            }) && filter(string)
+        }
+    }
+    /// Groups the nodes of the matchings in `set` by how well they match `query`, from best to worst
+    fn rank(
+        &self,
+        set: &MatchingSet<UUU>,
+        query: &str,
+    ) -> BTreeMap<MatchingRankKey, BTreeSet<NodeID>> {
         let mut map: BTreeMap<MatchingRankKey, BTreeSet<NodeID>> = BTreeMap::new();
         for m in set.iter() {
             match map.entry(MatchingRankKey::from_matching(m, &self.trie.nodes, query)) {
@@ -672,18 +3099,61 @@ impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
                 }
             }
         }
-        let mut strs: HashSet<Cow<'_, str>> = Default::default();
-        for (ix, (k, set)) in map.into_iter().enumerate() {
-            println!("{:?} set-len={}", k, set.len());
-            if ix < 4 {
-                for id in set {
-                    let x = strs.len();
-                    self.trie
-                        .fill_results(&self.trie.nodes[id], &mut strs, x + 3);
+        map
+    }
+    /// Adds the indices of up to `requested` strings that pass `filter` from the `ranked` nodes to `indices`,
+    /// mapped to the id of the node each string was taken from
+    fn fill(
+        &self,
+        ranked: BTreeMap<MatchingRankKey, BTreeSet<NodeID>>,
+        requested: usize,
+        mut filter: impl FnMut(&str) -> bool,
+        indices: &mut HashMap<SSS, NodeID>,
+    ) {
+        if requested == 0 {
+            return;
+        }
+        'fill: for set in ranked.into_values() {
+            for id in set {
+                let node = &self.trie.nodes[id];
+                if self.trie.fill_results(
+                    node,
+                    self.ranked_strings(node),
+                    indices,
+                    requested,
+                    &mut filter,
+                ) {
+                    break 'fill;
                 }
             }
         } // zorepinephrine
-        measure_results(strs, query)
+    }
+    /// Same as `autocomplete`, but each result is paired with a payload of its string
+    ///
+    /// A string with multiple payloads has an entry for each payload, and a string without any has none
+    pub fn autocomplete_entries<'a>(
+        &'a self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredEntry<&'a P>> {
+        let mut entries = Vec::new();
+        for measure in self.autocomplete_indices(query, requested, cache) {
+            let payloads = self
+                .payloads
+                .get(measure.index as usize)
+                .map_or(&[][..], Vec::as_slice);
+            for payload in payloads {
+                entries.push(MeasuredEntry {
+                    measure: MeasuredPrefix {
+                        string: self.display(measure.index).to_string(),
+                        prefix_distance: measure.prefix_distance,
+                    },
+                    payload,
+                });
+            }
+        }
+        entries
     }
     /// Applies the `visitor` function to all descendants in the inverted index at `depth` and `character` of `matching.node`
     fn traverse_inverted_index<'a, VisitorFn>(
@@ -720,10 +3190,14 @@ impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
         character: char,
         query_len: usize, // i
         b: usize,
+        cancel: &CancelToken,
     ) -> MatchingSet<u8> {
         let mut delta = MatchingSet::default();
         let mut edit_distances = HashMap::<usize, UUU>::new(); // Node ID to ED(q,n)
         for m1 in set.iter() {
+            if cancel.is_cancelled() {
+                break;
+            }
             if m1.edit_distance <= b as UUU
                 && m1.query_prefix_len >= (query_len.saturating_sub(1 + b)) as UUU
                 && m1.query_prefix_len <= (query_len.saturating_sub(1)) as UUU
@@ -779,6 +3253,7 @@ impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
         query: &[char],
         query_len: usize,
         b: usize,
+        cancel: &CancelToken,
     ) -> MatchingSet<UUU>
     where
         'stored: 'b,
@@ -852,6 +3327,9 @@ impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
 
         // Filter the input set to P(i,b-1)
         for m in set.iter() {
+            if cancel.is_cancelled() {
+                break;
+            }
             if m.edit_distance <= b as UUU - 1 && m.query_prefix_len <= query_len as UUU {
                 per_matching(m);
             }
@@ -861,17 +3339,48 @@ impl<'stored> MetaAutocompleter<'stored, UUU, SSS> {
     }
 }
 
-fn measure_results(result: HashSet<Cow<'_, str>>, query: &str) -> Vec<MeasuredPrefix> {
-    let mut result: Vec<MeasuredPrefix> = result
-        .into_iter()
-        .map(|string| MeasuredPrefix {
-            string: string.to_string(),
-            prefix_distance: levenshtein::prefix_edit_distance(query, TreeStringT::to_str(&string)),
-        })
-        .collect();
+/// Incremental autocompletion of a query typed one character at a time
+///
+/// Owns a cache so that each keystroke reuses the matching sets of the previous prefixes
+pub struct TypingSession<'a, 'stored, P = ()> {
+    autocompleter: &'a MetaAutocompleter<'stored, UUU, SSS, P>,
+    cache: Cache<'static>,
+    query: String,
+    requested: usize,
+}
 
-    result.sort();
-    result
+impl<'a, 'stored, P> TypingSession<'a, 'stored, P> {
+    /// Starts a session with an empty query that returns up to `requested` completions per keystroke
+    pub fn new(
+        autocompleter: &'a MetaAutocompleter<'stored, UUU, SSS, P>,
+        requested: usize,
+    ) -> Self {
+        Self {
+            autocompleter,
+            cache: Default::default(),
+            query: String::new(),
+            requested,
+        }
+    }
+    /// Appends `character` to the query and returns the completions of the new query
+    pub fn push_char(&mut self, character: char) -> Vec<MeasuredPrefix> {
+        self.query.push(character);
+        self.completions()
+    }
+    /// Removes the last character of the query (if any) and returns the completions of the new query
+    pub fn pop_char(&mut self) -> Vec<MeasuredPrefix> {
+        self.query.pop();
+        self.completions()
+    }
+    /// Returns the query typed so far
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+    /// Returns the completions of the current query
+    pub fn completions(&mut self) -> Vec<MeasuredPrefix> {
+        self.autocompleter
+            .autocomplete(&self.query, self.requested, &mut self.cache)
+    }
 }
 
 impl Autocompleter for Yoke<MetaAutocompleter<'static>, Vec<String>> {
@@ -881,7 +3390,17 @@ impl Autocompleter for Yoke<MetaAutocompleter<'static>, Vec<String>> {
         requested: usize,
         max_threshold: usize,
     ) -> Vec<MeasuredPrefix> {
-        unimplemented!()
+        let options = QueryOptions {
+            max_prefix_distance: Some(max_threshold),
+            ..Default::default()
+        };
+        self.get().autocomplete_with_options(
+            query,
+            requested,
+            |_| true,
+            &options,
+            &mut Cache::default(),
+        )
     }
 }
 
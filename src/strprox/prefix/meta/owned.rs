@@ -0,0 +1,29 @@
+use serde::{Deserialize, Deserializer};
+
+use super::{MetaAutocompleter, SSS, UUU};
+
+/// Autocompleter that's deserialized without borrowing from the input, so it implements `DeserializeOwned`
+/// and can be read from streams such as network connections or decompressed files (e.g. by `serde_json::from_reader`)
+///
+/// `MetaAutocompleter` itself can only be deserialized from input that outlives it, since its strings have the lifetime of the input
+pub struct DeserializedAutocompleter<P = ()>(pub MetaAutocompleter<'static, UUU, SSS, P>);
+
+impl<P> DeserializedAutocompleter<P> {
+    /// Returns the deserialized autocompleter
+    pub fn into_inner(self) -> MetaAutocompleter<'static, UUU, SSS, P> {
+        self.0
+    }
+}
+
+impl<P> From<DeserializedAutocompleter<P>> for MetaAutocompleter<'static, UUU, SSS, P> {
+    fn from(deserialized: DeserializedAutocompleter<P>) -> Self {
+        deserialized.0
+    }
+}
+
+impl<'de, P: Deserialize<'de>> Deserialize<'de> for DeserializedAutocompleter<P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let autocompleter = MetaAutocompleter::<'de, UUU, SSS, P>::deserialize(deserializer)?;
+        Ok(Self(autocompleter.into_owned()))
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Cache, CachedPrefix, MatchingSet, MetaAutocompleter, NodeID, PState, PrioMap, TreeString,
+    TreeStringT, SSS, UUU,
+};
+use crate::strprox::stable_hash::StableHasher;
+
+/// Contents of a `Cache` that can be serialized to restore it later for the same index, such as at the next launch of an app
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheSnapshot {
+    /// Fingerprint of the strings of the index that the matching sets were deduced for
+    index: u64,
+    capacity: Option<usize>,
+    byte_budget: Option<usize>,
+    entries: Vec<SnapshotEntry>,
+}
+
+/// A cached prefix with its matching sets
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SnapshotEntry {
+    prefix: String,
+    /// Matchings of each set as (query prefix length, node, edit distance)
+    sets: Vec<Vec<(UUU, NodeID, UUU)>>,
+    /// Last visit of the prefix if it was a whole query, which orders it for pruning
+    visited: Option<u64>,
+}
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Returns the cached prefixes and matching sets of `cache` with its capacity and byte budget,
+    /// which can be restored by `restore_cache` for an autocompleter over the same strings
+    ///
+    /// Prefixes cached for another autocompleter or before a mutation aren't saved, since their matching sets are stale
+    pub fn save_cache(&self, cache: &Cache<'_>) -> CacheSnapshot {
+        let mut snapshot = CacheSnapshot {
+            index: self.fingerprint(),
+            capacity: cache.capacity,
+            byte_budget: cache.byte_budget,
+            entries: Vec::new(),
+        };
+        if cache.generation != Some(self.generation) {
+            return snapshot;
+        }
+        snapshot.entries.reserve(cache.lru.slab.len());
+        for (ix, entry) in cache.lru.slab.iter() {
+            let PState { sets, prio, .. } = &entry.state;
+            let visited = cache
+                .lru
+                .prio
+                .get(prio)
+                .is_some_and(|set| set.contains(&ix))
+                .then_some(*prio);
+            let sets = sets
+                .iter()
+                .map(|set| {
+                    set.matchings
+                        .iter()
+                        .map(|(&(query_prefix_len, node), &edit_distance)| {
+                            (query_prefix_len, node, edit_distance)
+                        })
+                        .collect()
+                })
+                .collect();
+            snapshot.entries.push(SnapshotEntry {
+                prefix: entry.prefix.to_string(),
+                sets,
+                visited,
+            });
+        }
+        snapshot
+    }
+    /// Returns a cache with the contents of `snapshot`, or None if it was saved for an autocompleter over other strings,
+    /// whose matching sets would give wrong results
+    pub fn restore_cache(&self, snapshot: CacheSnapshot) -> Option<Cache<'static>> {
+        if snapshot.index != self.fingerprint() {
+            return None;
+        }
+        let mut cache = Cache {
+            capacity: snapshot.capacity,
+            byte_budget: snapshot.byte_budget,
+            generation: Some(self.generation),
+            ..Default::default()
+        };
+        for entry in snapshot.entries {
+            let sets = entry
+                .sets
+                .into_iter()
+                .map(|matchings| MatchingSet {
+                    matchings: matchings
+                        .into_iter()
+                        .map(|(query_prefix_len, node, edit_distance)| {
+                            ((query_prefix_len, node), edit_distance)
+                        })
+                        .collect(),
+                })
+                .collect();
+            cache.restore(entry.prefix, sets, entry.visited);
+        }
+        Some(cache)
+    }
+    /// Returns a hash of the stored strings, which identifies the nodes that matching sets refer to
+    ///
+    /// The hash is stable across platforms and Rust releases, so that snapshots survive restarts of an app
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        hasher.write_u64(self.trie.nodes.len() as u64);
+        for string in self.iter_strings() {
+            hasher.write_str(string);
+        }
+        hasher.finish()
+    }
+}
+
+impl Cache<'_> {
+    /// Caches `sets` for `prefix`, marking it as a query last visited at `visited` if any
+    fn restore(&mut self, prefix: String, sets: Vec<MatchingSet<UUU>>, visited: Option<u64>) {
+        let Some(last) = prefix.chars().count().checked_sub(1) else {
+            return;
+        };
+        let set_bytes = sets.iter().map(MatchingSet::bytes).sum();
+        let prio = visited.unwrap_or_default();
+        let chars: Vec<char> = prefix.chars().collect();
+        let prefix: TreeString = TreeStringT::from_owned(prefix);
+        self.bytes += prefix.len() + set_bytes;
+        let entry = self.lru.slab.vacant_entry();
+        let ix = entry.key();
+        entry.insert(CachedPrefix {
+            prefix,
+            set_bytes,
+            state: PState { sets, prio, ix },
+        });
+        if let Some(visited) = visited {
+            self.lru.prio.add(visited, ix);
+            self.clock = self.clock.max(visited);
+        }
+        self.cached_prefix.insert(chars.into_iter(), |node, i| {
+            if i == Some(last) {
+                node.value = Some(ix);
+            }
+        });
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+};
+
+use super::{
+    dedup_pairs, next_generation, InvertedIndex, MetaAutocompleter, TreeString, Trie,
+    DEFAULT_CACHE_SIZE, SSS, UUU,
+};
+
+/// A string to index with its weight and payload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record<'stored, P> {
+    pub string: TreeString<'stored>,
+    pub weight: u32,
+    pub payload: P,
+}
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Constructs an Autocompleter given the `source` of records (does not copy strings)
+    ///
+    /// Results with the same prefix edit distance are ranked by descending weight,
+    /// and records with the same string have their weights summed and keep all of their payloads
+    pub fn from_records(source: impl IntoIterator<Item = Record<'stored, P>>) -> Self {
+        let source = source.into_iter();
+        let (strings, values) = dedup_pairs(
+            source.size_hint().0,
+            source.map(|record| (record.string, (record.weight, vec![record.payload]))),
+            |(weight, payloads), (other_weight, other_payloads)| {
+                *weight = weight.saturating_add(other_weight);
+                payloads.extend(other_payloads);
+            },
+        );
+        let (weights, payloads): (Vec<u32>, Vec<Vec<P>>) = values.into_iter().unzip();
+
+        let trie = Trie::<'stored, UUU, SSS>::new(strings.len(), strings);
+        debug_assert_eq!(trie.strings.len(), weights.len());
+        let inverted_index = InvertedIndex::<UUU, SSS>::new(&trie);
+        Self {
+            trie,
+            inverted_index,
+            payloads,
+            weights,
+            counts: Vec::new(),
+            originals: Vec::new(),
+            top: Default::default(),
+            threshold: None,
+            pins: Default::default(),
+            min_fuzzy_len: 0,
+            normalizer: Default::default(),
+            #[cfg(feature = "unicode-segmentation")]
+            graphemes: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+            generation: next_generation(),
+        }
+    }
+}
+
+/// Error from loading records
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    #[cfg(feature = "csv")]
+    Csv(csv::Error),
+    #[cfg(feature = "jsonl")]
+    Json(serde_json::Error),
+    /// A record doesn't have the field to index, with the line or row number (starting at 1)
+    MissingField {
+        line: usize,
+        field: String,
+    },
+    /// A record has a weight that isn't a u32, with the line or row number (starting at 1)
+    InvalidWeight {
+        line: usize,
+        weight: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            #[cfg(feature = "csv")]
+            Self::Csv(error) => write!(f, "{}", error),
+            #[cfg(feature = "jsonl")]
+            Self::Json(error) => write!(f, "{}", error),
+            Self::MissingField { line, field } => {
+                write!(f, "line {}: missing field {}", line, field)
+            }
+            Self::InvalidWeight { line, weight } => {
+                write!(f, "line {}: invalid weight {}", line, weight)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Parses the `weight` of the record on `line`
+#[cfg(feature = "csv")]
+fn parse_weight(line: usize, weight: &str) -> Result<u32, LoadError> {
+    weight.trim().parse().map_err(|_| LoadError::InvalidWeight {
+        line,
+        weight: weight.to_string(),
+    })
+}
+
+/// Loads a record with a weight of 1 for each non-empty line of `reader`, with the line number (starting at 1) as its payload
+pub fn load_lines(reader: impl BufRead) -> Result<Vec<Record<'static, usize>>, LoadError> {
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if !line.is_empty() {
+            records.push(Record {
+                string: line.into(),
+                weight: 1,
+                payload: index + 1,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Loads a record for each row of the CSV from `reader` with headers, indexing the column named `field`
+/// and weighting it by the column named `weight_field` (or 1 if None), with the row as its payload
+#[cfg(feature = "csv")]
+pub fn load_csv(
+    reader: impl io::Read,
+    field: &str,
+    weight_field: Option<&str>,
+) -> Result<Vec<Record<'static, csv::StringRecord>>, LoadError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers().map_err(LoadError::Csv)?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| LoadError::MissingField {
+                line: 1,
+                field: name.to_string(),
+            })
+    };
+    let string_column = column(field)?;
+    let weight_column = weight_field.map(column).transpose()?;
+
+    let mut records = Vec::new();
+    for (index, row) in reader.records().enumerate() {
+        let row = row.map_err(LoadError::Csv)?;
+        // the headers are the first row
+        let line = index + 2;
+        let missing = |name: &str| LoadError::MissingField {
+            line,
+            field: name.to_string(),
+        };
+        let string = row.get(string_column).ok_or_else(|| missing(field))?;
+        let weight = match (weight_column, weight_field) {
+            (Some(column), Some(name)) => {
+                parse_weight(line, row.get(column).ok_or_else(|| missing(name))?)?
+            }
+            _ => 1,
+        };
+        records.push(Record {
+            string: string.to_string().into(),
+            weight,
+            payload: row,
+        });
+    }
+    Ok(records)
+}
+
+/// Loads a record for each JSON object on a non-empty line of `reader`, indexing its string member `field`
+/// and weighting it by its integer member `weight_field` (or 1 if None), with the object as its payload
+#[cfg(feature = "jsonl")]
+pub fn load_jsonl(
+    reader: impl BufRead,
+    field: &str,
+    weight_field: Option<&str>,
+) -> Result<Vec<Record<'static, serde_json::Value>>, LoadError> {
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_number = index + 1;
+        let object: serde_json::Value = serde_json::from_str(&line).map_err(LoadError::Json)?;
+        let string = object
+            .get(field)
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| LoadError::MissingField {
+                line: line_number,
+                field: field.to_string(),
+            })?
+            .to_string();
+        let weight = match weight_field {
+            Some(name) => match object.get(name) {
+                Some(weight) => weight
+                    .as_u64()
+                    .and_then(|weight| u32::try_from(weight).ok())
+                    .ok_or_else(|| LoadError::InvalidWeight {
+                        line: line_number,
+                        weight: weight.to_string(),
+                    })?,
+                None => {
+                    return Err(LoadError::MissingField {
+                        line: line_number,
+                        field: name.to_string(),
+                    })
+                }
+            },
+            None => 1,
+        };
+        records.push(Record {
+            string: string.into(),
+            weight,
+            payload: object,
+        });
+    }
+    Ok(records)
+}
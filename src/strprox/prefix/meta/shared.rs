@@ -0,0 +1,117 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::MeasuredPrefix;
+
+use super::{Cache, CacheStats, MetaAutocompleter, SSS, UUU};
+
+/// Number of shards of a `SharedCache::default()`
+pub const DEFAULT_SHARD_COUNT: usize = 64;
+
+/// Number of leading characters of queries that pick their shard
+const SHARD_PREFIX_LEN: usize = 2;
+
+/// Cache that can be shared by threads querying the same autocompleter, such as through an `Arc<MetaAutocompleter>`
+///
+/// The cache is split into shards behind their own locks by the first two characters of queries,
+/// so that queries typed one character at a time keep using the same shard from their second character on
+/// while queries that start differently rarely wait for each other.
+/// A shard is locked for the whole query, so there should be several times more shards than threads
+pub struct SharedCache {
+    shards: Vec<Mutex<Cache<'static>>>,
+}
+
+impl Default for SharedCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHARD_COUNT)
+    }
+}
+
+impl SharedCache {
+    /// Returns an empty cache with `shard_count` shards (at least 1)
+    pub fn new(shard_count: usize) -> Self {
+        Self::from_caches((0..shard_count.max(1)).map(|_| Cache::default()))
+    }
+    /// Returns an empty cache with `shard_count` shards (at least 1), each limited to `capacity` query prefixes
+    pub fn with_capacity(shard_count: usize, capacity: usize) -> Self {
+        Self::from_caches((0..shard_count.max(1)).map(|_| Cache::with_capacity(capacity)))
+    }
+    fn from_caches(caches: impl Iterator<Item = Cache<'static>>) -> Self {
+        Self {
+            shards: caches.map(Mutex::new).collect(),
+        }
+    }
+    /// Returns the number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+    /// Locks and returns the shard for `query`, which can be passed to any method of `MetaAutocompleter` that takes a cache
+    ///
+    /// Other queries for the same shard wait until the guard is dropped.
+    /// If a query panicked while holding the shard, the shard is cleared instead of staying poisoned
+    pub fn lock(&self, query: &str) -> MutexGuard<'_, Cache<'static>> {
+        let mut hasher = DefaultHasher::new();
+        for character in query.chars().take(SHARD_PREFIX_LEN) {
+            character.hash(&mut hasher);
+        }
+        let shard = (hasher.finish() % self.shards.len() as u64) as usize;
+        lock_shard(&self.shards[shard])
+    }
+    /// Returns the number of cached query prefixes across all shards
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock_shard(shard).len())
+            .sum()
+    }
+    /// Returns whether no query prefixes are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the statistics of all shards added together
+    pub fn stats(&self) -> CacheStats {
+        self.shards
+            .iter()
+            .map(|shard| lock_shard(shard).stats())
+            .fold(CacheStats::default(), |total, stats| CacheStats {
+                entries: total.entries + stats.entries,
+                hits: total.hits + stats.hits,
+                misses: total.misses + stats.misses,
+                bytes: total.bytes + stats.bytes,
+                evictions: total.evictions + stats.evictions,
+                results: total.results + stats.results,
+                result_hits: total.result_hits + stats.result_hits,
+            })
+    }
+    /// Removes every cached prefix from all shards
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            lock_shard(shard).clear();
+        }
+    }
+}
+
+/// Locks `shard`, clearing it if a panic poisoned it, since the panicking query may have left it half updated
+fn lock_shard<'a>(shard: &'a Mutex<Cache<'static>>) -> MutexGuard<'a, Cache<'static>> {
+    shard.lock().unwrap_or_else(|poisoned| {
+        let mut cache = poisoned.into_inner();
+        cache.clear();
+        shard.clear_poison();
+        cache
+    })
+}
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Same as `autocomplete`, but with a cache shared by threads, which only locks the shard for `query`
+    pub fn autocomplete_shared(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &SharedCache,
+    ) -> Vec<MeasuredPrefix> {
+        self.autocomplete(query, requested, &mut cache.lock(query))
+    }
+}
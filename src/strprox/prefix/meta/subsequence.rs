@@ -0,0 +1,267 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use super::{Cache, MetaAutocompleter, SSS, UUU};
+use crate::{levenshtein, HybridPrefix, MeasuredPrefix, SubsequencePrefix};
+
+/// Points of a subsequence match, like those of fuzzy file finders, where matched characters
+/// score more at the starts of words and next to each other, and gaps between them score less
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubsequenceScoring {
+    /// Points for each matched character
+    pub match_score: i64,
+    /// Extra points for a character at the start of the string or after a separator like " ", "_", "-", "/" or "."
+    pub boundary_bonus: i64,
+    /// Extra points for an uppercase letter after a lowercase letter, or a digit after a letter
+    pub camel_bonus: i64,
+    /// Extra points for a character right after the previous matched character
+    pub consecutive_bonus: i64,
+    /// Points taken for a gap between matched characters
+    pub gap_start: i64,
+    /// Points taken for each character of a gap after its first
+    pub gap_extension: i64,
+}
+
+impl Default for SubsequenceScoring {
+    fn default() -> Self {
+        Self {
+            match_score: 16,
+            boundary_bonus: 8,
+            camel_bonus: 7,
+            consecutive_bonus: 4,
+            gap_start: 3,
+            gap_extension: 1,
+        }
+    }
+}
+
+/// Returns whether `character` separates words
+fn is_separator(character: char) -> bool {
+    character.is_whitespace() || matches!(character, '_' | '-' | '/' | '\\' | '.' | ':' | ',')
+}
+
+impl SubsequenceScoring {
+    /// Returns the bonus for matching the character at `position` of `chars`
+    fn bonus(&self, chars: &[char], position: usize) -> i64 {
+        let character = chars[position];
+        match position.checked_sub(1).map(|previous| chars[previous]) {
+            None => self.boundary_bonus,
+            Some(previous) if is_separator(previous) => self.boundary_bonus,
+            Some(previous)
+                if (previous.is_lowercase() && character.is_uppercase())
+                    || (previous.is_alphabetic() && character.is_ascii_digit()) =>
+            {
+                self.camel_bonus
+            }
+            _ => 0,
+        }
+    }
+    /// Returns the best score of `query` as a subsequence of `string` with the byte offsets of the matched characters,
+    /// or None if it isn't a subsequence
+    ///
+    /// Characters are compared regardless of case unless `query` has an uppercase letter
+    pub fn score(&self, query: &str, string: &str) -> Option<(i64, Vec<usize>)> {
+        let case_sensitive = query.chars().any(char::is_uppercase);
+        let fold = |character: char| {
+            if case_sensitive {
+                character
+            } else {
+                character.to_lowercase().next().unwrap_or(character)
+            }
+        };
+        let query: Vec<char> = query.chars().map(fold).collect();
+        let (offsets, chars): (Vec<usize>, Vec<char>) = string.char_indices().unzip();
+        if query.is_empty() {
+            return Some((0, vec![]));
+        }
+        let folded: Vec<char> = chars.iter().copied().map(fold).collect();
+
+        // scores[i][j] is the best score of the first i + 1 characters of the query with the last matched at j,
+        // with the position of the previous matched character
+        let mut scores: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; chars.len()]; query.len()];
+        for (j, &character) in folded.iter().enumerate() {
+            if character == query[0] {
+                scores[0][j] = Some((self.match_score + self.bonus(&chars, j), j));
+            }
+        }
+        for i in 1..query.len() {
+            // best previous score plus the extension penalty it would save, over positions at least two before j
+            let mut best_gapped: Option<(i64, usize)> = None;
+            for j in 1..chars.len() {
+                if j >= 2
+                    && let Some((score, _)) = scores[i - 1][j - 2]
+                {
+                    let candidate = score + self.gap_extension * (j - 2) as i64;
+                    if best_gapped.map_or(true, |(best, _)| candidate > best) {
+                        best_gapped = Some((candidate, j - 2));
+                    }
+                }
+                if folded[j] != query[i] {
+                    continue;
+                }
+                let consecutive =
+                    scores[i - 1][j - 1].map(|(score, _)| (score + self.consecutive_bonus, j - 1));
+                let gapped = best_gapped.map(|(score, previous)| {
+                    (
+                        score - self.gap_start - self.gap_extension * (j - 2) as i64,
+                        previous,
+                    )
+                });
+                let best = match (consecutive, gapped) {
+                    (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                };
+                scores[i][j] = best.map(|(score, previous)| {
+                    (score + self.match_score + self.bonus(&chars, j), previous)
+                });
+            }
+        }
+
+        let last = query.len() - 1;
+        // the first of the best end positions
+        let (mut position, score) = scores[last]
+            .iter()
+            .enumerate()
+            .filter_map(|(j, score)| score.map(|(score, _)| (j, score)))
+            .max_by_key(|&(j, score)| (score, Reverse(j)))?;
+        let mut positions = vec![0; query.len()];
+        for i in (0..query.len()).rev() {
+            positions[i] = offsets[position];
+            position = scores[i][position].unwrap().1;
+        }
+        Some((score, positions))
+    }
+}
+
+/// Weights of the normalized scores that `autocomplete_hybrid` sums
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HybridWeights {
+    /// Weight of the prefix edit distance as a similarity between 0.0 and 1.0, normalized by the length of the query
+    pub edit: f64,
+    /// Weight of the subsequence score between 0.0 and 1.0, normalized by the best score among the results
+    pub subsequence: f64,
+}
+
+impl Default for HybridWeights {
+    fn default() -> Self {
+        Self {
+            edit: 0.5,
+            subsequence: 0.5,
+        }
+    }
+}
+
+impl<'stored, P> MetaAutocompleter<'stored, UUU, SSS, P> {
+    /// Returns the top `requested` stored strings that have the characters of `query` in order,
+    /// sorted by descending score with `scoring`, then by length and lexicographical order
+    ///
+    /// Unlike `autocomplete`, this scans every stored string, like fuzzy file finders do.
+    /// The matched positions are byte offsets into the stored string (see `get`), which is the result string unless originals are kept
+    pub fn autocomplete_subsequence(
+        &self,
+        query: &str,
+        requested: usize,
+        scoring: &SubsequenceScoring,
+    ) -> Vec<SubsequencePrefix> {
+        let query: &str = &self.normalized(query);
+        self.subsequence_matches(query, requested, scoring)
+            .into_iter()
+            .map(|(score, index, positions)| SubsequencePrefix {
+                measure: MeasuredPrefix {
+                    string: self.display(index).to_string(),
+                    prefix_distance: levenshtein::prefix_edit_distance(query, self.get(index)),
+                },
+                score,
+                positions,
+            })
+            .collect()
+    }
+    /// Returns the top `requested` stored strings from both `autocomplete` and `autocomplete_subsequence`,
+    /// sorted by descending sum of their normalized scores weighted by `weights`, then by prefix edit distance
+    /// and lexicographical order
+    ///
+    /// Strings that don't have the characters of `query` in order have a subsequence score of 0
+    pub fn autocomplete_hybrid(
+        &self,
+        query: &str,
+        requested: usize,
+        weights: &HybridWeights,
+        scoring: &SubsequenceScoring,
+        cache: &mut Cache<'_>,
+    ) -> Vec<HybridPrefix> {
+        if requested == 0 {
+            return vec![];
+        }
+        let normalized: &str = &self.normalized(query);
+        let query_len = normalized.chars().count();
+        let mut candidates: HashMap<SSS, Option<i64>> = HashMap::new();
+        for (score, index, _) in self.subsequence_matches(normalized, requested, scoring) {
+            candidates.insert(index, Some(score));
+        }
+        for measure in self.autocomplete_indices(query, requested, cache) {
+            candidates.entry(measure.index).or_insert_with(|| {
+                scoring
+                    .score(normalized, self.get(measure.index))
+                    .map(|(score, _)| score)
+            });
+        }
+        let best = candidates.values().flatten().copied().max().unwrap_or(0);
+
+        let mut result: Vec<(SSS, HybridPrefix)> = candidates
+            .into_iter()
+            .map(|(index, subsequence_score)| {
+                let prefix_distance =
+                    levenshtein::prefix_edit_distance(normalized, self.get(index));
+                let edit = if query_len == 0 {
+                    1.0
+                } else {
+                    1.0 - (prefix_distance as f64 / query_len as f64).min(1.0)
+                };
+                let subsequence = match subsequence_score {
+                    Some(score) if best > 0 => score.max(0) as f64 / best as f64,
+                    Some(_) => 1.0,
+                    None => 0.0,
+                };
+                let hybrid = HybridPrefix {
+                    measure: MeasuredPrefix {
+                        string: self.display(index).to_string(),
+                        prefix_distance,
+                    },
+                    score: weights.edit * edit + weights.subsequence * subsequence,
+                    subsequence_score,
+                };
+                (index, hybrid)
+            })
+            .collect();
+        // strings are sorted, so ordering by index is the same as lexicographical order
+        result.sort_by(|(index, a), (other_index, b)| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.measure.prefix_distance.cmp(&b.measure.prefix_distance))
+                .then(index.cmp(other_index))
+        });
+        result.truncate(requested);
+        result.into_iter().map(|(_, hybrid)| hybrid).collect()
+    }
+    /// Returns the scores, indices and matched positions of the top `requested` stored strings
+    /// that have the characters of the normalized `query` in order, sorted like `autocomplete_subsequence`
+    fn subsequence_matches(
+        &self,
+        query: &str,
+        requested: usize,
+        scoring: &SubsequenceScoring,
+    ) -> Vec<(i64, SSS, Vec<usize>)> {
+        let mut matches: Vec<(i64, SSS, Vec<usize>)> = (0..self.len() as SSS)
+            .filter_map(|index| {
+                scoring
+                    .score(query, self.get(index))
+                    .map(|(score, positions)| (score, index, positions))
+            })
+            .collect();
+        // strings are sorted, so ordering by index is the same as lexicographical order
+        matches.sort_by_key(|(score, index, _)| {
+            (Reverse(*score), self.get(*index).chars().count(), *index)
+        });
+        matches.truncate(requested);
+        matches
+    }
+}
@@ -3,13 +3,21 @@ use std::collections::BinaryHeap;
 use crate::MeasuredPrefix;
 
 pub mod fst;
+pub mod infix;
+pub mod keyed;
 pub mod meta;
+pub mod phonetic;
+pub mod reload;
+pub mod sharded;
+pub mod snapshot;
+pub mod tokens;
 
 pub trait Autocompleter {
     /// Returns the `requested` number of strings with the best PEDs that are at most `max_threshold`,
     /// or all strings available with PEDs within `max_threshold`
     ///
-    /// Strings are sorted by prefix edit distance and then lexicographical order
+    /// Strings are sorted by prefix edit distance and then lexicographical order,
+    /// and ties for the last results are broken the same way so that results are the same across runs
     ///
     /// Assumes `query`'s length in Unicode characters is bounded by u8; will truncate to u8::MAX characters otherwise
     fn threshold_topk(
@@ -21,7 +29,8 @@ pub trait Autocompleter {
 
     /// Returns the `requested` number of strings with the best PEDs, or all strings available if less than `requested`
     ///
-    /// Strings are sorted by prefix edit distance and then lexicographical order
+    /// Strings are sorted by prefix edit distance and then lexicographical order,
+    /// and ties for the last results are broken the same way so that results are the same across runs
     ///
     /// Assumes `query`'s length in Unicode characters is bounded by u8; will truncate to u8::MAX characters otherwise
     fn autocomplete(&self, query: &str, requested: usize) -> Vec<MeasuredPrefix> {
@@ -34,7 +43,8 @@ pub trait FromStrings {
     fn from_strings(strings: &[&str]) -> Self;
 }
 
-/// Structure convertible to MeasuredPrefix that compared only using the PED
+/// Structure convertible to MeasuredPrefix that is compared using the PED and then the string,
+/// so that the same strings are kept regardless of the order they were ranked in
 #[derive(PartialEq, Eq, Clone, Debug)]
 struct PrefixRanking {
     string: String,
@@ -43,7 +53,9 @@ struct PrefixRanking {
 
 impl Ord for PrefixRanking {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.prefix_distance.cmp(&other.prefix_distance)
+        self.prefix_distance
+            .cmp(&other.prefix_distance)
+            .then_with(|| self.string.cmp(&other.string))
     }
 }
 impl PartialOrd for PrefixRanking {
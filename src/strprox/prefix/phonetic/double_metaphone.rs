@@ -0,0 +1,637 @@
+//! Double Metaphone by Lawrence Philips, which codes the sounds of English words and names of many origins
+//! with a primary code and an alternate code for other pronunciations, such as "Schmidt" as "XMT" or "SMT"
+//!
+//! The rules follow the original implementation, with codes of at most `MAX_LEN` characters
+
+/// Maximum number of characters of each code
+const MAX_LEN: usize = 4;
+
+/// Returns the primary and alternate Double Metaphone codes of `word`, which are empty if it has no letters
+pub fn double_metaphone(word: &str) -> (String, String) {
+    let value: Vec<char> = word.trim().to_uppercase().chars().collect();
+    let mut encoder = Encoder {
+        slavo_germanic: is_slavo_germanic(&value),
+        value,
+        primary: String::new(),
+        alternate: String::new(),
+    };
+    encoder.encode();
+    (encoder.primary, encoder.alternate)
+}
+
+/// Returns the primary Double Metaphone code of `word`, which can be given to `PhoneticAutocompleter::new` like `soundex`
+pub fn double_metaphone_primary(word: &str) -> String {
+    double_metaphone(word).0
+}
+
+/// Returns whether `value` has letters that only occur in Slavic or Germanic names, which changes some rules
+fn is_slavo_germanic(value: &[char]) -> bool {
+    value.contains(&'W')
+        || value.contains(&'K')
+        || value.windows(2).any(|pair| pair == ['C', 'Z'])
+        || value.windows(4).any(|four| four == ['W', 'I', 'T', 'Z'])
+}
+
+fn is_vowel(character: char) -> bool {
+    matches!(character, 'A' | 'E' | 'I' | 'O' | 'U' | 'Y')
+}
+
+/// State of the coding of a word, whose characters are indexed by `isize` since rules look behind the current one
+struct Encoder {
+    value: Vec<char>,
+    primary: String,
+    alternate: String,
+    slavo_germanic: bool,
+}
+
+impl Encoder {
+    fn len(&self) -> isize {
+        self.value.len() as isize
+    }
+    /// Returns the character at `index`, or '\0' if it's out of bounds
+    fn at(&self, index: isize) -> char {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.value.get(index))
+            .copied()
+            .unwrap_or('\0')
+    }
+    /// Returns whether the characters from `start` are one of `criteria`, which all have the same length
+    fn contains(&self, start: isize, criteria: &[&str]) -> bool {
+        let length = criteria[0].len() as isize;
+        if start < 0 || start + length > self.len() {
+            return false;
+        }
+        let target = &self.value[start as usize..(start + length) as usize];
+        criteria
+            .iter()
+            .any(|criterion| criterion.chars().eq(target.iter().copied()))
+    }
+    /// Appends `primary` and `alternate` to the codes, up to `MAX_LEN` characters
+    fn add(&mut self, primary: &str, alternate: &str) {
+        for (code, part) in [
+            (&mut self.primary, primary),
+            (&mut self.alternate, alternate),
+        ] {
+            let room = MAX_LEN.saturating_sub(code.len());
+            code.extend(part.chars().take(room));
+        }
+    }
+    fn both(&mut self, code: &str) {
+        self.add(code, code);
+    }
+    fn is_complete(&self) -> bool {
+        self.primary.len() >= MAX_LEN && self.alternate.len() >= MAX_LEN
+    }
+    /// Returns `index` advanced past `character` and past a repetition of it
+    fn skip_double(&self, index: isize, character: char) -> isize {
+        if self.at(index + 1) == character {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+    fn encode(&mut self) {
+        // the first letter is silent in these
+        let mut index = if self.contains(0, &["GN", "KN", "PN", "WR", "PS"]) {
+            1
+        } else {
+            0
+        };
+        while !self.is_complete() && index < self.len() {
+            index = match self.at(index) {
+                'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                    // only initial vowels are coded
+                    if index == 0 {
+                        self.both("A");
+                    }
+                    index + 1
+                }
+                'B' => {
+                    self.both("P");
+                    self.skip_double(index, 'B')
+                }
+                'Ç' => {
+                    self.both("S");
+                    index + 1
+                }
+                'C' => self.c(index),
+                'D' => self.d(index),
+                'F' => {
+                    self.both("F");
+                    self.skip_double(index, 'F')
+                }
+                'G' => self.g(index),
+                'H' => self.h(index),
+                'J' => self.j(index),
+                'K' => {
+                    self.both("K");
+                    self.skip_double(index, 'K')
+                }
+                'L' => self.l(index),
+                'M' => {
+                    self.both("M");
+                    if self.m_doubled(index) {
+                        index + 2
+                    } else {
+                        index + 1
+                    }
+                }
+                'N' => {
+                    self.both("N");
+                    self.skip_double(index, 'N')
+                }
+                'Ñ' => {
+                    self.both("N");
+                    index + 1
+                }
+                'P' => self.p(index),
+                'Q' => {
+                    self.both("K");
+                    self.skip_double(index, 'Q')
+                }
+                'R' => self.r(index),
+                'S' => self.s(index),
+                'T' => self.t(index),
+                'V' => {
+                    self.both("F");
+                    self.skip_double(index, 'V')
+                }
+                'W' => self.w(index),
+                'X' => self.x(index),
+                'Z' => self.z(index),
+                _ => index + 1,
+            };
+        }
+    }
+    fn c(&mut self, index: isize) -> isize {
+        if self.c_is_k(index) {
+            // various Germanic, such as "bacher"
+            self.both("K");
+            index + 2
+        } else if index == 0 && self.contains(index, &["CAESAR"]) {
+            self.both("S");
+            index + 2
+        } else if self.contains(index, &["CH"]) {
+            self.ch(index)
+        } else if self.contains(index, &["CZ"]) && !self.contains(index - 2, &["WICZ"]) {
+            // "Czerny"
+            self.add("S", "X");
+            index + 2
+        } else if self.contains(index + 1, &["CIA"]) {
+            // "focaccia"
+            self.both("X");
+            index + 3
+        } else if self.contains(index, &["CC"]) && !(index == 1 && self.at(0) == 'M') {
+            // double "cc" but not "McClelland"
+            self.cc(index)
+        } else if self.contains(index, &["CK", "CG", "CQ"]) {
+            self.both("K");
+            index + 2
+        } else if self.contains(index, &["CI", "CE", "CY"]) {
+            // Italian or English
+            if self.contains(index, &["CIO", "CIE", "CIA"]) {
+                self.add("S", "X");
+            } else {
+                self.both("S");
+            }
+            index + 2
+        } else {
+            self.both("K");
+            if self.contains(index + 1, &[" C", " Q", " G"]) {
+                // "Mac Caffrey", "Mac Gregor"
+                index + 3
+            } else if self.contains(index + 1, &["C", "K", "Q"])
+                && !self.contains(index + 1, &["CE", "CI"])
+            {
+                index + 2
+            } else {
+                index + 1
+            }
+        }
+    }
+    fn c_is_k(&self, index: isize) -> bool {
+        if self.contains(index, &["CHIA"]) {
+            true
+        } else if index <= 1 || is_vowel(self.at(index - 2)) || !self.contains(index - 1, &["ACH"])
+        {
+            false
+        } else {
+            let next = self.at(index + 2);
+            (next != 'I' && next != 'E') || self.contains(index - 2, &["BACHER", "MACHER"])
+        }
+    }
+    fn cc(&mut self, index: isize) -> isize {
+        if self.contains(index + 2, &["I", "E", "H"]) && !self.contains(index + 2, &["HU"]) {
+            // "bellocchio" but not "bacchus"
+            if (index == 1 && self.at(index - 1) == 'A')
+                || self.contains(index - 1, &["UCCEE", "UCCES"])
+            {
+                // "accident", "accede", "succeed"
+                self.both("KS");
+            } else {
+                // "bacci", "bertucci" and other Italian
+                self.both("X");
+            }
+            index + 3
+        } else {
+            // Pierce's rule
+            self.both("K");
+            index + 2
+        }
+    }
+    fn ch(&mut self, index: isize) -> isize {
+        if index > 0 && self.contains(index, &["CHAE"]) {
+            // "Michael"
+            self.add("K", "X");
+        } else if self.ch_is_greek(index) || self.ch_is_kh(index) {
+            // Greek roots such as "chemistry" and "chorus", or Germanic
+            self.both("K");
+        } else if index > 0 {
+            if self.contains(0, &["MC"]) {
+                self.both("K");
+            } else {
+                self.add("X", "K");
+            }
+        } else {
+            self.both("X");
+        }
+        index + 2
+    }
+    fn ch_is_greek(&self, index: isize) -> bool {
+        index == 0
+            && (self.contains(index + 1, &["HARAC", "HARIS"])
+                || self.contains(index + 1, &["HOR", "HYM", "HIA", "HEM"]))
+            && !self.contains(0, &["CHORE"])
+    }
+    fn ch_is_kh(&self, index: isize) -> bool {
+        self.contains(0, &["VAN ", "VON "])
+            || self.contains(0, &["SCH"])
+            || self.contains(index - 2, &["ORCHES", "ARCHIT", "ORCHID"])
+            || self.contains(index + 2, &["T", "S"])
+            || ((self.contains(index - 1, &["A", "O", "U", "E"]) || index == 0)
+                && (self.contains(
+                    index + 2,
+                    &["L", "R", "N", "M", "B", "H", "F", "V", "W", " "],
+                ) || index + 1 == self.len() - 1))
+    }
+    fn d(&mut self, index: isize) -> isize {
+        if self.contains(index, &["DG"]) {
+            if self.contains(index + 2, &["I", "E", "Y"]) {
+                // "edge"
+                self.both("J");
+                index + 3
+            } else {
+                // "Edgar"
+                self.both("TK");
+                index + 2
+            }
+        } else if self.contains(index, &["DT", "DD"]) {
+            self.both("T");
+            index + 2
+        } else {
+            self.both("T");
+            index + 1
+        }
+    }
+    fn g(&mut self, index: isize) -> isize {
+        let next = self.at(index + 1);
+        if next == 'H' {
+            self.gh(index)
+        } else if next == 'N' {
+            if index == 1 && is_vowel(self.at(0)) && !self.slavo_germanic {
+                self.add("KN", "N");
+            } else if !self.contains(index + 2, &["EY"]) && !self.slavo_germanic {
+                self.add("N", "KN");
+            } else {
+                self.both("KN");
+            }
+            index + 2
+        } else if self.contains(index + 1, &["LI"]) && !self.slavo_germanic {
+            self.add("KL", "L");
+            index + 2
+        } else if index == 0
+            && (next == 'Y'
+                || self.contains(
+                    index + 1,
+                    &[
+                        "ES", "EP", "EB", "EL", "EY", "IB", "IL", "IN", "IE", "EI", "ER",
+                    ],
+                ))
+        {
+            // "ges", "gep", "gel" and "gie" at the beginning
+            self.add("K", "J");
+            index + 2
+        } else if (self.contains(index + 1, &["ER"]) || next == 'Y')
+            && !self.contains(0, &["DANGER", "RANGER", "MANGER"])
+            && !self.contains(index - 1, &["E", "I"])
+            && !self.contains(index - 1, &["RGY", "OGY"])
+        {
+            // "ger" and "gy"
+            self.add("K", "J");
+            index + 2
+        } else if self.contains(index + 1, &["E", "I", "Y"])
+            || self.contains(index - 1, &["AGGI", "OGGI"])
+        {
+            // Italian such as "biaggi"
+            if self.contains(0, &["VAN ", "VON "])
+                || self.contains(0, &["SCH"])
+                || self.contains(index + 1, &["ET"])
+            {
+                // obviously Germanic
+                self.both("K");
+            } else if self.contains(index + 1, &["IER"]) {
+                self.both("J");
+            } else {
+                self.add("J", "K");
+            }
+            index + 2
+        } else if next == 'G' {
+            self.both("K");
+            index + 2
+        } else {
+            self.both("K");
+            index + 1
+        }
+    }
+    fn gh(&mut self, index: isize) -> isize {
+        if index > 0 && !is_vowel(self.at(index - 1)) {
+            self.both("K");
+        } else if index == 0 {
+            // "ghislane" and "ghiradelli"
+            if self.at(index + 2) == 'I' {
+                self.both("J");
+            } else {
+                self.both("K");
+            }
+        } else if (index > 1 && self.contains(index - 2, &["B", "H", "D"]))
+            || (index > 2 && self.contains(index - 3, &["B", "H", "D"]))
+            || (index > 3 && self.contains(index - 4, &["B", "H"]))
+        {
+            // Parker's rule, such as "hugh"
+        } else if index > 2
+            && self.at(index - 1) == 'U'
+            && self.contains(index - 3, &["C", "G", "L", "R", "T"])
+        {
+            // "laugh", "McLaughlin", "cough", "gough", "rough" and "tough"
+            self.both("F");
+        } else if index > 0 && self.at(index - 1) != 'I' {
+            self.both("K");
+        }
+        index + 2
+    }
+    fn h(&mut self, index: isize) -> isize {
+        // only coded when first or between vowels
+        if (index == 0 || is_vowel(self.at(index - 1))) && is_vowel(self.at(index + 1)) {
+            self.both("H");
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+    fn j(&mut self, index: isize) -> isize {
+        if self.contains(index, &["JOSE"]) || self.contains(0, &["SAN "]) {
+            // obviously Spanish, such as "Jose" and "San Jacinto"
+            if (index == 0 && self.at(index + 4) == ' ')
+                || self.len() == 4
+                || self.contains(0, &["SAN "])
+            {
+                self.both("H");
+            } else {
+                self.add("J", "H");
+            }
+            return index + 1;
+        }
+        if index == 0 {
+            // "Yankelovich" and "Jankelowicz"
+            self.add("J", "A");
+        } else if is_vowel(self.at(index - 1))
+            && !self.slavo_germanic
+            && matches!(self.at(index + 1), 'A' | 'O')
+        {
+            // Spanish pronunciation of "bajador"
+            self.add("J", "H");
+        } else if index == self.len() - 1 {
+            self.add("J", "");
+        } else if !self.contains(index + 1, &["L", "T", "K", "S", "N", "M", "B", "Z"])
+            && !self.contains(index - 1, &["S", "K", "L"])
+        {
+            self.both("J");
+        }
+        self.skip_double(index, 'J')
+    }
+    fn l(&mut self, index: isize) -> isize {
+        if self.at(index + 1) == 'L' {
+            if self.ll_is_spanish(index) {
+                // "cabrillo" and "gallegos"
+                self.add("L", "");
+            } else {
+                self.both("L");
+            }
+            index + 2
+        } else {
+            self.both("L");
+            index + 1
+        }
+    }
+    fn ll_is_spanish(&self, index: isize) -> bool {
+        let len = self.len();
+        (index == len - 3 && self.contains(index - 1, &["ILLO", "ILLA", "ALLE"]))
+            || ((self.contains(len - 2, &["AS", "OS"]) || self.contains(len - 1, &["A", "O"]))
+                && self.contains(index - 1, &["ALLE"]))
+    }
+    /// Returns whether the "m" at `index` is followed by another or by a silent "b", as in "dumb" and "thumb"
+    fn m_doubled(&self, index: isize) -> bool {
+        self.at(index + 1) == 'M'
+            || (self.contains(index - 1, &["UMB"])
+                && (index + 1 == self.len() - 1 || self.contains(index + 2, &["ER"])))
+    }
+    fn p(&mut self, index: isize) -> isize {
+        if self.at(index + 1) == 'H' {
+            self.both("F");
+            index + 2
+        } else {
+            // also "campbell" and "raspberry"
+            self.both("P");
+            if self.contains(index + 1, &["P", "B"]) {
+                index + 2
+            } else {
+                index + 1
+            }
+        }
+    }
+    fn r(&mut self, index: isize) -> isize {
+        if index == self.len() - 1
+            && !self.slavo_germanic
+            && self.contains(index - 2, &["IE"])
+            && !self.contains(index - 4, &["ME", "MA"])
+        {
+            // French such as "rogier", but not "hochmeier"
+            self.add("", "R");
+        } else {
+            self.both("R");
+        }
+        self.skip_double(index, 'R')
+    }
+    fn s(&mut self, index: isize) -> isize {
+        if self.contains(index - 1, &["ISL", "YSL"]) {
+            // "island", "isle", "carlisle" and "carlysle"
+            index + 1
+        } else if index == 0 && self.contains(index, &["SUGAR"]) {
+            self.add("X", "S");
+            index + 1
+        } else if self.contains(index, &["SH"]) {
+            if self.contains(index + 1, &["HEIM", "HOEK", "HOLM", "HOLZ"]) {
+                // Germanic
+                self.both("S");
+            } else {
+                self.both("X");
+            }
+            index + 2
+        } else if self.contains(index, &["SIO", "SIA"]) || self.contains(index, &["SIAN"]) {
+            // Italian and Armenian
+            if self.slavo_germanic {
+                self.both("S");
+            } else {
+                self.add("S", "X");
+            }
+            index + 3
+        } else if (index == 0 && self.contains(index + 1, &["M", "N", "L", "W"]))
+            || self.contains(index + 1, &["Z"])
+        {
+            // German and anglicized, so "smith" matches "schmidt" and "snider" matches "schneider",
+            // and "sz" in Slavic languages although Hungarian pronounces it "s"
+            self.add("S", "X");
+            if self.contains(index + 1, &["Z"]) {
+                index + 2
+            } else {
+                index + 1
+            }
+        } else if self.contains(index, &["SC"]) {
+            self.sc(index)
+        } else {
+            if index == self.len() - 1 && self.contains(index - 2, &["AI", "OI"]) {
+                // French such as "resnais" and "artois"
+                self.add("", "S");
+            } else {
+                self.both("S");
+            }
+            if self.contains(index + 1, &["S", "Z"]) {
+                index + 2
+            } else {
+                index + 1
+            }
+        }
+    }
+    fn sc(&mut self, index: isize) -> isize {
+        if self.at(index + 2) == 'H' {
+            // Schlesinger's rule
+            if self.contains(index + 3, &["OO", "ER", "EN", "UY", "ED", "EM"]) {
+                // Dutch origin, such as "school" and "schooner"
+                if self.contains(index + 3, &["ER", "EN"]) {
+                    // "schermerhorn" and "schenker"
+                    self.add("X", "SK");
+                } else {
+                    self.both("SK");
+                }
+            } else if index == 0 && !is_vowel(self.at(3)) && self.at(3) != 'W' {
+                self.add("X", "S");
+            } else {
+                self.both("X");
+            }
+        } else if self.contains(index + 2, &["I", "E", "Y"]) {
+            self.both("S");
+        } else {
+            self.both("SK");
+        }
+        index + 3
+    }
+    fn t(&mut self, index: isize) -> isize {
+        if self.contains(index, &["TION"]) || self.contains(index, &["TIA", "TCH"]) {
+            self.both("X");
+            index + 3
+        } else if self.contains(index, &["TH"]) || self.contains(index, &["TTH"]) {
+            if self.contains(index + 2, &["OM", "AM"])
+                || self.contains(0, &["VAN ", "VON "])
+                || self.contains(0, &["SCH"])
+            {
+                // "thomas", "thames" or Germanic
+                self.both("T");
+            } else {
+                self.both("0");
+            }
+            index + 2
+        } else {
+            self.both("T");
+            if self.contains(index + 1, &["T", "D"]) {
+                index + 2
+            } else {
+                index + 1
+            }
+        }
+    }
+    fn w(&mut self, index: isize) -> isize {
+        if self.contains(index, &["WR"]) {
+            // also in the middle of words
+            self.both("R");
+            return index + 2;
+        }
+        if index == 0 && (is_vowel(self.at(index + 1)) || self.contains(index, &["WH"])) {
+            if is_vowel(self.at(index + 1)) {
+                // "Wasserman" matches "Vasserman"
+                self.add("A", "F");
+            } else {
+                // "Uomo" matches "Womo"
+                self.both("A");
+            }
+            index + 1
+        } else if (index == self.len() - 1 && is_vowel(self.at(index - 1)))
+            || self.contains(index - 1, &["EWSKI", "EWSKY", "OWSKI", "OWSKY"])
+            || self.contains(0, &["SCH"])
+        {
+            // "Arnow" matches "Arnoff"
+            self.add("", "F");
+            index + 1
+        } else if self.contains(index, &["WICZ", "WITZ"]) {
+            // Polish such as "filipowicz"
+            self.add("TS", "FX");
+            index + 4
+        } else {
+            index + 1
+        }
+    }
+    fn x(&mut self, index: isize) -> isize {
+        if index == 0 {
+            // "Xavier"
+            self.both("S");
+            return index + 1;
+        }
+        let french = index == self.len() - 1
+            && (self.contains(index - 3, &["IAU", "EAU"])
+                || self.contains(index - 2, &["AU", "OU"]));
+        // French such as "breaux" is silent
+        if !french {
+            self.both("KS");
+        }
+        if self.contains(index + 1, &["C", "X"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+    fn z(&mut self, index: isize) -> isize {
+        if self.at(index + 1) == 'H' {
+            // Chinese pinyin such as "zhao"
+            self.both("J");
+            return index + 2;
+        }
+        if self.contains(index + 1, &["ZO", "ZI", "ZA"])
+            || (self.slavo_germanic && index > 0 && self.at(index - 1) != 'T')
+        {
+            self.add("S", "TS");
+        } else {
+            self.both("S");
+        }
+        self.skip_double(index, 'Z')
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{levenshtein, MeasuredPrefix, PhoneticPrefix};
+
+use super::meta::{Cache, MetaAutocompleter, ThresholdPolicy, TreeString};
+
+mod double_metaphone;
+
+pub use double_metaphone::{double_metaphone, double_metaphone_primary};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+/// Autocompletes strings by their spelling and also finds the strings with words that sound like the words of the query,
+/// so "smyth" finds "Smith" even if it's beyond the threshold of edits
+///
+/// Phonetic codes are compared for whole words, so they only match once a word of the query is complete.
+/// `soundex` and `double_metaphone_primary` are provided as encoders, but any other can be given
+pub struct PhoneticAutocompleter<'stored> {
+    strings: MetaAutocompleter<'stored>,
+    /// Phonetic code |-> indices of the stored strings with a word that has it
+    codes: HashMap<String, Vec<SSS>>,
+    /// Returns the phonetic code of a word, or an empty string if it has none
+    encode: fn(&str) -> String,
+}
+
+/// Returns the Soundex code of `word`, which is its first letter followed by three digits for the sounds of its consonants
+/// (or an empty string if it has no ASCII letters)
+pub fn soundex(word: &str) -> String {
+    let digit = |character: char| match character {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    };
+    let mut letters = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|character| character.to_ascii_lowercase());
+    let Some(first) = letters.next() else {
+        return String::new();
+    };
+    let mut code = String::from(first.to_ascii_uppercase());
+    let mut last = digit(first);
+    for character in letters {
+        let sound = digit(character);
+        if let Some(sound) = sound
+            && Some(sound) != last
+        {
+            code.push(sound);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // consonants with the same digit are only coded once when separated by "h" or "w", but not by vowels
+        if character != 'h' && character != 'w' {
+            last = sound;
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    code
+}
+
+impl<'stored> PhoneticAutocompleter<'stored> {
+    /// Constructs an autocompleter over the strings in `source` that also indexes the codes from `encode`
+    /// of their whitespace-separated words, such as `soundex` or `double_metaphone_primary` (does not copy strings)
+    pub fn new(
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+        encode: fn(&str) -> String,
+    ) -> Self {
+        let source: Vec<TreeString<'stored>> = source.into_iter().collect();
+        Self::from_autocompleter(MetaAutocompleter::new(source.len(), source), encode)
+    }
+    /// Constructs an autocompleter over the strings of `strings` that also indexes the codes from `encode`
+    /// of their whitespace-separated words, such as one built with a normalizer by `MetaAutocompleterBuilder`
+    pub fn from_autocompleter(
+        strings: MetaAutocompleter<'stored>,
+        encode: fn(&str) -> String,
+    ) -> Self {
+        let mut codes = HashMap::<String, Vec<SSS>>::new();
+        for (index, string) in strings.iter_strings().enumerate() {
+            for word in string.split_whitespace() {
+                let code = encode(word);
+                if code.is_empty() {
+                    continue;
+                }
+                let indices = codes.entry(code).or_default();
+                // the words of a string are visited together
+                if indices.last() != Some(&(index as SSS)) {
+                    indices.push(index as SSS);
+                }
+            }
+        }
+        Self {
+            strings,
+            codes,
+            encode,
+        }
+    }
+    /// Limits the prefix edit distance of results that are found by their spelling by `policy`
+    pub fn set_threshold(&mut self, policy: Option<ThresholdPolicy>) {
+        self.strings.set_threshold(policy);
+    }
+    /// Returns the indices of the stored strings that have a word with the code of every word of `query`
+    fn sounding_like(&self, query: &str) -> Vec<SSS> {
+        let mut result: Option<Vec<SSS>> = None;
+        for word in query.split_whitespace() {
+            let code = (self.encode)(word);
+            let indices = self.codes.get(&code).map_or(&[][..], Vec::as_slice);
+            result = Some(match result {
+                None => indices.to_vec(),
+                Some(result) => {
+                    let indices: HashSet<SSS> = indices.iter().copied().collect();
+                    result
+                        .into_iter()
+                        .filter(|index| indices.contains(index))
+                        .collect()
+                }
+            });
+        }
+        result.unwrap_or_default()
+    }
+    /// Returns the top `requested` stored strings found by their spelling or by the sound of the words of `query`,
+    /// sorted by prefix edit distance, then with the strings found by spelling first, and then by the order they were stored in
+    ///
+    /// Results have the original spelling of the strings when the autocompleter keeps it
+    pub fn autocomplete_phonetic(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<PhoneticPrefix> {
+        let orthographic = self.strings.autocomplete_indices(query, requested, cache);
+        // phonetic hits are measured against the query as the stored strings were normalized, like the orthographic ones
+        let query = self.strings.normalized(query);
+        let mut result: Vec<(SSS, PhoneticPrefix)> = orthographic
+            .iter()
+            .map(|measure| {
+                (
+                    measure.index,
+                    PhoneticPrefix {
+                        measure: MeasuredPrefix {
+                            string: self.strings.display(measure.index).to_string(),
+                            prefix_distance: measure.prefix_distance,
+                        },
+                        phonetic: false,
+                    },
+                )
+            })
+            .collect();
+        for index in self.sounding_like(&query) {
+            if orthographic.iter().any(|measure| measure.index == index) {
+                continue;
+            }
+            result.push((
+                index,
+                PhoneticPrefix {
+                    measure: MeasuredPrefix {
+                        string: self.strings.display(index).to_string(),
+                        prefix_distance: levenshtein::prefix_edit_distance(
+                            &query,
+                            self.strings.get(index),
+                        ),
+                    },
+                    phonetic: true,
+                },
+            ));
+        }
+        result.sort_by_key(|(index, prefix)| {
+            (prefix.measure.prefix_distance, prefix.phonetic, *index)
+        });
+        let mut result: Vec<PhoneticPrefix> =
+            result.into_iter().map(|(_, prefix)| prefix).collect();
+        result.truncate(requested);
+        result
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    sync::{Arc, RwLock},
+    thread::{self, JoinHandle},
+};
+
+use yoke::Yoke;
+
+use crate::MeasuredPrefix;
+
+use super::meta::{Cache, MetaAutocompleter};
+
+/// Autocompleter that owns its strings so that it can be moved into a `ReloadableAutocompleter`
+pub type OwnedAutocompleter = Yoke<MetaAutocompleter<'static>, Vec<String>>;
+
+/// Constructs an autocompleter that owns `strings`
+pub fn owned_autocompleter(strings: Vec<String>) -> OwnedAutocompleter {
+    Yoke::attach_to_cart(strings, |strings| {
+        let cows: Vec<_> = strings.iter().map(Into::into).collect();
+        MetaAutocompleter::new(cows.len(), cows)
+    })
+}
+
+/// Autocompleter whose index can be rebuilt from a new dataset in the background
+/// and swapped in without blocking queries for longer than it takes to clone an `Arc`
+///
+/// Queries that already hold a snapshot finish on the index they started with
+pub struct ReloadableAutocompleter {
+    /// The current index with the number of times it has been swapped,
+    /// so that caches built for an older index can be invalidated
+    current: RwLock<(u64, Arc<OwnedAutocompleter>)>,
+}
+
+/// Cache for an index that can be swapped, like that of a `ReloadableAutocompleter`,
+/// which is cleared when the index is swapped
+#[derive(Default)]
+pub struct ReloadCache {
+    generation: u64,
+    cache: Cache<'static>,
+}
+
+impl ReloadCache {
+    /// Returns the cache for the index with `generation`, clearing it if it was used with another index
+    pub(super) fn for_generation(&mut self, generation: u64) -> &mut Cache<'static> {
+        if self.generation != generation {
+            // the settings and statistics of the cache are kept for the new index
+            self.generation = generation;
+            self.cache.invalidate_all();
+        }
+        &mut self.cache
+    }
+}
+
+impl ReloadableAutocompleter {
+    /// Constructs an autocompleter over `strings`
+    pub fn new(strings: Vec<String>) -> Self {
+        Self::from_autocompleter(owned_autocompleter(strings))
+    }
+    /// Wraps an already constructed `autocompleter`, which may have settings like thresholds and pins
+    pub fn from_autocompleter(autocompleter: OwnedAutocompleter) -> Self {
+        Self {
+            current: RwLock::new((0, Arc::new(autocompleter))),
+        }
+    }
+    /// Returns a snapshot of the current index that stays valid after later swaps
+    pub fn load(&self) -> Arc<OwnedAutocompleter> {
+        self.current.read().unwrap().1.clone()
+    }
+    /// Returns the number of times the index has been swapped
+    pub fn generation(&self) -> u64 {
+        self.current.read().unwrap().0
+    }
+    /// Replaces the index with `autocompleter`, returning the previous one
+    pub fn store(&self, autocompleter: OwnedAutocompleter) -> Arc<OwnedAutocompleter> {
+        let mut current = self.current.write().unwrap();
+        current.0 += 1;
+        std::mem::replace(&mut current.1, Arc::new(autocompleter))
+    }
+    /// Rebuilds the index from `strings` on a background thread and swaps it in once it's built
+    pub fn reload(self: &Arc<Self>, strings: Vec<String>) -> JoinHandle<()> {
+        self.reload_with(move || owned_autocompleter(strings))
+    }
+    /// Builds a new index with `build` on a background thread and swaps it in once it's built
+    pub fn reload_with(
+        self: &Arc<Self>,
+        build: impl FnOnce() -> OwnedAutocompleter + Send + 'static,
+    ) -> JoinHandle<()> {
+        let this = Arc::clone(self);
+        thread::spawn(move || {
+            let autocompleter = build();
+            this.store(autocompleter);
+        })
+    }
+    /// Returns the top `requested` strings for `query` from the current index,
+    /// clearing `cache` first if it was used with a previous index
+    pub fn autocomplete(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut ReloadCache,
+    ) -> Vec<MeasuredPrefix> {
+        let (generation, snapshot) = self.current.read().unwrap().clone();
+        snapshot
+            .get()
+            .autocomplete(query, requested, cache.for_generation(generation))
+    }
+}
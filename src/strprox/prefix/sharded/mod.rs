@@ -0,0 +1,137 @@
+use crate::{strprox::stable_hash::StableHasher, MeasuredPrefix};
+
+use super::meta::{Cache, MetaAutocompleter, TreeString};
+
+/// Partitions strings into several autocompleters so that each can be rebuilt on its own,
+/// and queries all of them (in parallel with the `rayon` feature) before merging their results
+pub struct ShardedAutocompleter<'stored> {
+    shards: Vec<MetaAutocompleter<'stored>>,
+    /// Number of times each shard has been rebuilt, so that caches built for an older shard can be invalidated
+    generations: Vec<u64>,
+}
+
+/// Caches for each shard of a `ShardedAutocompleter`, each of which is cleared when its shard is rebuilt
+#[derive(Default)]
+pub struct ShardedCache<'stored> {
+    caches: Vec<Cache<'stored>>,
+    /// Generation of the shard that each cache was used with
+    generations: Vec<u64>,
+}
+
+impl<'stored> ShardedAutocompleter<'stored> {
+    /// Constructs an autocompleter with `shard_count` shards (at least 1) over `source` (does not copy strings)
+    pub fn new(shard_count: usize, source: impl IntoIterator<Item = TreeString<'stored>>) -> Self {
+        let shard_count = shard_count.max(1);
+        let mut partitions: Vec<Vec<TreeString<'stored>>> = vec![Vec::new(); shard_count];
+        for string in source {
+            partitions[Self::partition(shard_count, &string)].push(string);
+        }
+        let shards = partitions
+            .into_iter()
+            .map(|strings| MetaAutocompleter::new(strings.len(), strings))
+            .collect();
+        Self {
+            shards,
+            generations: vec![0; shard_count],
+        }
+    }
+    /// Returns the shard of `string` out of `shard_count`, which is the same on every platform and Rust release,
+    /// so that shard assignments can be persisted
+    fn partition(shard_count: usize, string: &str) -> usize {
+        let mut hasher = StableHasher::new();
+        hasher.write(string.as_bytes());
+        (hasher.finish() % shard_count as u64) as usize
+    }
+    /// Returns the index of the shard that `string` belongs to
+    pub fn shard_of(&self, string: &str) -> usize {
+        Self::partition(self.shards.len(), string)
+    }
+    /// Returns the shards in order
+    pub fn shards(&self) -> &[MetaAutocompleter<'stored>] {
+        &self.shards
+    }
+    /// Rebuilds the shard at `index` from `source`, returning the previous shard (panics if `index` is out of bounds)
+    ///
+    /// Strings in `source` should belong to the shard according to `shard_of`, or they may also be stored in another shard
+    pub fn rebuild_shard(
+        &mut self,
+        index: usize,
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+    ) -> MetaAutocompleter<'stored> {
+        let strings: Vec<_> = source.into_iter().collect();
+        self.generations[index] += 1;
+        std::mem::replace(
+            &mut self.shards[index],
+            MetaAutocompleter::new(strings.len(), strings),
+        )
+    }
+    /// Returns the number of stored strings across all shards
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(MetaAutocompleter::len).sum()
+    }
+    /// Returns whether no strings are stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the top `requested` strings for `query` across all shards,
+    /// sorted by prefix edit distance and then lexicographical order
+    pub fn autocomplete(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut ShardedCache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        cache
+            .caches
+            .resize_with(self.shards.len(), Default::default);
+        cache.generations.resize(self.shards.len(), 0);
+        for ((shard_cache, cached), &generation) in cache
+            .caches
+            .iter_mut()
+            .zip(&mut cache.generations)
+            .zip(&self.generations)
+        {
+            if *cached != generation {
+                *cached = generation;
+                shard_cache.invalidate_all();
+            }
+        }
+        let mut result: Vec<MeasuredPrefix> = self
+            .query_shards(query, requested, &mut cache.caches)
+            .into_iter()
+            .flatten()
+            .collect();
+        result.sort();
+        result.truncate(requested);
+        result
+    }
+    /// Returns the results of every shard for `query`
+    #[cfg(not(feature = "rayon"))]
+    fn query_shards(
+        &self,
+        query: &str,
+        requested: usize,
+        caches: &mut [Cache<'_>],
+    ) -> Vec<Vec<MeasuredPrefix>> {
+        self.shards
+            .iter()
+            .zip(caches)
+            .map(|(shard, cache)| shard.autocomplete(query, requested, cache))
+            .collect()
+    }
+    /// Returns the results of every shard for `query`, querying them in parallel
+    #[cfg(feature = "rayon")]
+    fn query_shards(
+        &self,
+        query: &str,
+        requested: usize,
+        caches: &mut [Cache<'_>],
+    ) -> Vec<Vec<MeasuredPrefix>> {
+        use rayon::prelude::*;
+        self.shards
+            .par_iter()
+            .zip(caches)
+            .map(|(shard, cache)| shard.autocomplete(query, requested, cache))
+            .collect()
+    }
+}
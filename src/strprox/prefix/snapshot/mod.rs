@@ -0,0 +1,154 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crate::{levenshtein, MeasuredPrefix};
+
+use super::reload::{owned_autocompleter, OwnedAutocompleter, ReloadCache};
+
+/// Strings inserted and deleted since the immutable index was built
+#[derive(Clone, Default)]
+struct Overlay {
+    /// Strings that aren't in the index
+    inserted: BTreeSet<String>,
+    /// Strings of the index that were deleted
+    deleted: HashSet<String>,
+}
+
+/// Immutable view of the strings of a `SnapshotAutocompleter` at some point,
+/// which can be queried for as long as it's held regardless of later changes
+pub struct Generation {
+    number: u64,
+    /// Number of times the index has been rebuilt, to invalidate caches
+    index_number: u64,
+    index: Arc<OwnedAutocompleter>,
+    overlay: Overlay,
+}
+
+impl Generation {
+    /// Returns the number of changes committed before this generation
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+    /// Returns whether `string` is stored in this generation
+    pub fn contains(&self, string: &str) -> bool {
+        self.overlay.inserted.contains(string)
+            || (self.index.get().contains(string) && !self.overlay.deleted.contains(string))
+    }
+    /// Returns the number of strings stored in this generation
+    pub fn len(&self) -> usize {
+        self.index.get().len() - self.overlay.deleted.len() + self.overlay.inserted.len()
+    }
+    /// Returns whether no strings are stored in this generation
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the top `requested` strings of this generation for `query`,
+    /// sorted by prefix edit distance and then lexicographical order
+    pub fn autocomplete(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut ReloadCache,
+    ) -> Vec<MeasuredPrefix> {
+        let deleted = &self.overlay.deleted;
+        let mut result = self.index.get().autocomplete_filtered(
+            query,
+            requested,
+            |string| !deleted.contains(string),
+            cache.for_generation(self.index_number),
+        );
+        // the overlay is small, so it's searched exhaustively
+        result.extend(self.overlay.inserted.iter().map(|string| MeasuredPrefix {
+            string: string.clone(),
+            prefix_distance: levenshtein::prefix_edit_distance(query, string),
+        }));
+        result.sort();
+        result.truncate(requested);
+        result
+    }
+}
+
+/// Autocompleter where writers stage inserts and deletes and commit them as a new generation,
+/// while readers keep querying the generation they hold until they drop it
+///
+/// Changes are kept in a small overlay on top of an immutable index until `compact` rebuilds the index with them
+pub struct SnapshotAutocompleter {
+    current: RwLock<Arc<Generation>>,
+    /// Overlay of the next generation
+    pending: Mutex<Overlay>,
+}
+
+impl SnapshotAutocompleter {
+    /// Constructs an autocompleter over `strings`
+    pub fn new(strings: Vec<String>) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(Generation {
+                number: 0,
+                index_number: 0,
+                index: Arc::new(owned_autocompleter(strings)),
+                overlay: Default::default(),
+            })),
+            pending: Default::default(),
+        }
+    }
+    /// Returns the latest committed generation
+    pub fn snapshot(&self) -> Arc<Generation> {
+        self.current.read().unwrap().clone()
+    }
+    /// Stages the insertion of `string` for the next generation
+    pub fn insert(&self, string: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        // the index only changes while `pending` is locked
+        let stored = self.snapshot().index.get().contains(string);
+        if !pending.deleted.remove(string) && !stored {
+            pending.inserted.insert(string.to_string());
+        }
+    }
+    /// Stages the deletion of `string` for the next generation
+    pub fn remove(&self, string: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        let stored = self.snapshot().index.get().contains(string);
+        if !pending.inserted.remove(string) && stored {
+            pending.deleted.insert(string.to_string());
+        }
+    }
+    /// Publishes the staged changes as a new generation and returns it
+    pub fn commit(&self) -> Arc<Generation> {
+        let pending = self.pending.lock().unwrap();
+        let current = self.snapshot();
+        self.publish(Generation {
+            number: current.number + 1,
+            index_number: current.index_number,
+            index: current.index.clone(),
+            overlay: pending.clone(),
+        })
+    }
+    /// Rebuilds the index with the staged changes, publishing it as a new generation with an empty overlay
+    pub fn compact(&self) -> Arc<Generation> {
+        let mut pending = self.pending.lock().unwrap();
+        let current = self.snapshot();
+        let strings: Vec<String> = current
+            .index
+            .get()
+            .iter_strings()
+            .filter(|string| !pending.deleted.contains(*string))
+            .map(str::to_string)
+            .chain(pending.inserted.iter().cloned())
+            .collect();
+        *pending = Overlay::default();
+        self.publish(Generation {
+            number: current.number + 1,
+            index_number: current.index_number + 1,
+            index: Arc::new(owned_autocompleter(strings)),
+            overlay: Default::default(),
+        })
+    }
+    /// Makes `generation` the current one and returns it
+    fn publish(&self, generation: Generation) -> Arc<Generation> {
+        let generation = Arc::new(generation);
+        *self.current.write().unwrap() = generation.clone();
+        generation
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+};
+
+use crate::{levenshtein, MeasuredPrefix};
+
+use super::meta::{Cache, MetaAutocompleter, TreeString};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+/// Autocompletes multi-word strings by fuzzily matching each word of the query against any word of the stored strings
+pub struct TokenAutocompleter<'stored> {
+    /// Stored strings in the order they were given
+    strings: Vec<TreeString<'stored>>,
+    /// Distinct words of the stored strings with the indices of the strings that contain them as payloads
+    tokens: MetaAutocompleter<'stored, u8, SSS, SSS>,
+    /// Words that are ignored in queries and stored strings
+    stopwords: HashSet<String>,
+    /// How stored strings and queries are split into words
+    tokenizer: Tokenizer,
+}
+
+/// How strings are split into words
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// Words are separated by whitespace
+    #[default]
+    Whitespace,
+    /// Code symbols are split on characters that aren't alphanumeric (like "::" and "_") and on camelCase boundaries,
+    /// and their words are lowercased, so "MetaAutocompleter::autocomplete" has the words "meta", "autocompleter" and "autocomplete"
+    Symbols,
+}
+
+impl Tokenizer {
+    /// Returns the words of `string`, borrowing from it if possible
+    pub fn tokenize<'stored>(&self, string: &TreeString<'stored>) -> Vec<TreeString<'stored>> {
+        match (self, string) {
+            (Self::Whitespace, Cow::Borrowed(string)) => {
+                string.split_whitespace().map(Cow::Borrowed).collect()
+            }
+            (Self::Whitespace, Cow::Owned(string)) => string
+                .split_whitespace()
+                .map(|token| Cow::Owned(token.to_string()))
+                .collect(),
+            (Self::Symbols, string) => split_symbol(string).into_iter().map(Cow::Owned).collect(),
+        }
+    }
+}
+
+/// Returns the lowercased words of the identifiers in `symbol`, where a word starts at an uppercase letter
+/// that follows a lowercase letter or digit, or that starts a capitalized word after an acronym ("HTTPServer" is "http" and "server")
+fn split_symbol(symbol: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for identifier in symbol.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = identifier.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let boundary = chars[i].is_uppercase()
+                && (!chars[i - 1].is_uppercase()
+                    || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+            if boundary {
+                words.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+        if start < chars.len() {
+            words.push(chars[start..].iter().collect::<String>().to_lowercase());
+        }
+    }
+    words
+}
+
+impl<'stored> TokenAutocompleter<'stored> {
+    /// Constructs an autocompleter over the whitespace-separated words of the strings in `source` (does not copy borrowed strings)
+    pub fn new(source: impl IntoIterator<Item = TreeString<'stored>>) -> Self {
+        Self::with_tokenizer(source, Tokenizer::Whitespace)
+    }
+    /// Constructs an autocompleter over the words of the strings in `source` as split by `tokenizer`
+    /// (does not copy borrowed strings, but copies the words of `Tokenizer::Symbols`)
+    pub fn with_tokenizer(
+        source: impl IntoIterator<Item = TreeString<'stored>>,
+        tokenizer: Tokenizer,
+    ) -> Self {
+        let strings: Vec<TreeString<'stored>> = source.into_iter().collect();
+        let mut pairs = Vec::new();
+        for (index, string) in strings.iter().enumerate() {
+            for token in tokenizer.tokenize(string) {
+                pairs.push((token, index as SSS));
+            }
+        }
+        let tokens = MetaAutocompleter::new_with_payloads(pairs.len(), pairs);
+        Self {
+            strings,
+            tokens,
+            stopwords: Default::default(),
+            tokenizer,
+        }
+    }
+    /// Ignores the `stopwords` in queries and stored strings, so that words like "the" don't match every string
+    pub fn set_stopwords(&mut self, stopwords: impl IntoIterator<Item = String>) {
+        self.stopwords = stopwords.into_iter().collect();
+    }
+    /// Only completes words of queries shorter than `len` characters with words that have them as an exact prefix
+    pub fn set_min_fuzzy_len(&mut self, len: usize) {
+        self.tokens.set_min_fuzzy_len(len);
+    }
+    /// Returns the top `requested` stored strings for the words of `query` in any order,
+    /// where the distance of each result is the sum of the prefix edit distances from each word of the query
+    /// to its closest word in the string
+    ///
+    /// Results are sorted by distance and then lexicographical order
+    pub fn autocomplete_tokens(
+        &self,
+        query: &str,
+        requested: usize,
+        cache: &mut Cache<'_>,
+    ) -> Vec<MeasuredPrefix> {
+        let query_tokens: Vec<TreeString> = self
+            .tokenizer
+            .tokenize(&Cow::Borrowed(query))
+            .into_iter()
+            .filter(|token| !self.stopwords.contains(token.as_ref()))
+            .collect();
+        if query_tokens.is_empty() || requested == 0 {
+            return vec![];
+        }
+        // strings containing one of the closest words of any query word
+        let mut candidates = BTreeSet::<SSS>::new();
+        for query_token in &query_tokens {
+            for measure in self
+                .tokens
+                .autocomplete_indices(query_token, requested, cache)
+            {
+                candidates.extend(self.tokens.payloads(self.tokens.get(measure.index)));
+            }
+        }
+
+        let mut result: Vec<MeasuredPrefix> = candidates
+            .into_iter()
+            .map(|index| {
+                let string = &self.strings[index as usize];
+                let prefix_distance = query_tokens
+                    .iter()
+                    .map(|query_token| {
+                        self.tokenizer
+                            .tokenize(&Cow::Borrowed(string))
+                            .iter()
+                            .filter(|token| !self.stopwords.contains(token.as_ref()))
+                            .map(|token| levenshtein::prefix_edit_distance(query_token, token))
+                            .min()
+                            .unwrap_or(query_token.chars().count())
+                    })
+                    .sum();
+                MeasuredPrefix {
+                    string: string.to_string(),
+                    prefix_distance,
+                }
+            })
+            .collect();
+        result.sort();
+        result.dedup();
+        result.truncate(requested);
+        result
+    }
+}
@@ -0,0 +1,236 @@
+//! Backend-agnostic interface for finding the stored strings closest to a query,
+//! so that the algorithm can be switched without changing the code that queries it
+use std::borrow::Cow;
+
+use crate::{
+    hs_tree::{HSTree, MapGAT},
+    minhash::{MinHashIndex, MinHashParams},
+    prefix::meta::{Cache, MetaAutocompleter, QueryOptions, SharedCache},
+    symspell::SymSpell,
+    MeasuredString,
+};
+
+/// What the distances of a searcher's results measure
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchKind {
+    /// Prefix edit distance from the query to the closest prefix of each string, for autocompletion
+    Prefix,
+    /// Edit distance from the query to each whole string, for spelling correction and deduplication
+    Whole,
+}
+
+/// Index that returns the stored strings within some distance of a query, or the ones with the best distances,
+/// which every backend of `Searcher` implements
+///
+/// Distances are prefix edit distances or edit distances of whole strings depending on `match_kind`
+pub trait SimilaritySearcher {
+    /// Returns whether the distances of results are prefix edit distances or edit distances of whole strings
+    fn match_kind(&self) -> MatchKind;
+
+    /// Returns the `requested` number of strings with the best distances that are at most `max_threshold`,
+    /// or all strings available with distances within `max_threshold`
+    ///
+    /// Strings are sorted by distance and then lexicographical order
+    fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString>;
+
+    /// Returns all strings with distances of at most `threshold`, sorted by distance and then lexicographical order
+    fn threshold_query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        self.threshold_topk(query, usize::MAX, threshold)
+    }
+
+    /// Returns the `requested` number of strings with the best distances, or all strings available if less than `requested`,
+    /// sorted by distance and then lexicographical order
+    fn topk(&self, query: &str, requested: usize) -> Vec<MeasuredString> {
+        self.threshold_topk(query, requested, usize::MAX)
+    }
+}
+
+/// Same as `SimilaritySearcher::threshold_topk` for `autocompleter`, but with the matching sets cached in `cache`
+fn meta_threshold_topk(
+    autocompleter: &MetaAutocompleter<'_>,
+    query: &str,
+    requested: usize,
+    max_threshold: usize,
+    cache: &mut Cache<'_>,
+) -> Vec<MeasuredString> {
+    // there can't be more results than stored strings
+    let requested = requested.min(autocompleter.len());
+    if requested == 0 {
+        return vec![];
+    }
+    let options = QueryOptions {
+        max_prefix_distance: Some(max_threshold),
+        ..Default::default()
+    };
+    autocompleter
+        .autocomplete_with_options(query, requested, |_| true, &options, cache)
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+/// Each query starts with an empty cache, so `Searcher` should be used to reuse matching sets across queries
+impl<'stored> SimilaritySearcher for MetaAutocompleter<'stored> {
+    fn match_kind(&self) -> MatchKind {
+        MatchKind::Prefix
+    }
+    fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        meta_threshold_topk(self, query, requested, max_threshold, &mut Cache::default())
+    }
+}
+
+impl<'stored, M: MapGAT> SimilaritySearcher for HSTree<'stored, M> {
+    fn match_kind(&self) -> MatchKind {
+        MatchKind::Whole
+    }
+    fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        HSTree::threshold_topk(self, query, requested, max_threshold)
+    }
+    fn threshold_query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        self.query(query, threshold)
+    }
+}
+
+impl<'stored> SimilaritySearcher for SymSpell<'stored> {
+    fn match_kind(&self) -> MatchKind {
+        MatchKind::Whole
+    }
+    fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        SymSpell::threshold_topk(self, query, requested, max_threshold)
+    }
+    fn threshold_query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        self.query(query, threshold)
+    }
+}
+
+impl<'stored> SimilaritySearcher for MinHashIndex<'stored> {
+    fn match_kind(&self) -> MatchKind {
+        MatchKind::Whole
+    }
+    fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        MinHashIndex::threshold_topk(self, query, requested, max_threshold)
+    }
+    fn threshold_query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        self.query(query, threshold)
+    }
+}
+
+/// Algorithm that a `Searcher` uses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// `MetaAutocompleter`, which matches prefixes of the stored strings
+    #[default]
+    Meta,
+    /// `HSTree`, which matches whole stored strings
+    HSTree,
+    /// `SymSpell`, which looks up whole stored strings within `symspell::MAX_DISTANCE` edits
+    /// and compares every string for larger distances, using more memory for faster queries
+    SymSpell,
+    /// `MinHashIndex` with signatures shaped by the parameters, which matches whole stored strings
+    /// among the candidates with similar q-grams, using the least memory but possibly missing strings
+    MinHash(MinHashParams),
+}
+
+/// Searcher whose backend is chosen when it's constructed
+pub enum Searcher<'stored> {
+    /// `MetaAutocompleter` with a cache of the matching sets of its queries, so that queries typed a character at a time
+    /// only deduce the sets of the new characters
+    Meta(MetaAutocompleter<'stored>, SharedCache),
+    HSTree(HSTree<'stored>),
+    SymSpell(SymSpell<'stored>),
+    MinHash(MinHashIndex<'stored>),
+}
+
+impl<'stored> Searcher<'stored> {
+    /// Constructs a searcher over the strings in `source` with the algorithm chosen by `backend` (does not copy strings)
+    pub fn new(source: impl IntoIterator<Item = &'stored str>, backend: Backend) -> Self {
+        match backend {
+            Backend::Meta => {
+                let strings: Vec<_> = source.into_iter().map(Cow::Borrowed).collect();
+                Self::Meta(
+                    MetaAutocompleter::new(strings.len(), strings),
+                    SharedCache::default(),
+                )
+            }
+            Backend::HSTree => Self::HSTree(HSTree::new(source)),
+            Backend::SymSpell => Self::SymSpell(SymSpell::new(source)),
+            Backend::MinHash(params) => Self::MinHash(MinHashIndex::with_params(source, params)),
+        }
+    }
+    /// Returns the algorithm the searcher uses
+    pub fn backend(&self) -> Backend {
+        match self {
+            Self::Meta(..) => Backend::Meta,
+            Self::HSTree(_) => Backend::HSTree,
+            Self::SymSpell(_) => Backend::SymSpell,
+            Self::MinHash(index) => Backend::MinHash(index.params()),
+        }
+    }
+    /// Returns the backend as a trait object
+    fn searcher(&self) -> &dyn SimilaritySearcher {
+        match self {
+            Self::Meta(autocompleter, _) => autocompleter,
+            Self::HSTree(tree) => tree,
+            Self::SymSpell(index) => index,
+            Self::MinHash(index) => index,
+        }
+    }
+}
+
+impl<'stored> SimilaritySearcher for Searcher<'stored> {
+    fn match_kind(&self) -> MatchKind {
+        self.searcher().match_kind()
+    }
+    fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        match self {
+            Self::Meta(autocompleter, cache) => meta_threshold_topk(
+                autocompleter,
+                query,
+                requested,
+                max_threshold,
+                &mut cache.lock(query),
+            ),
+            _ => self
+                .searcher()
+                .threshold_topk(query, requested, max_threshold),
+        }
+    }
+    fn threshold_query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        match self {
+            // the default method goes through the cache
+            Self::Meta(..) => self.threshold_topk(query, usize::MAX, threshold),
+            _ => self.searcher().threshold_query(query, threshold),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Hashing whose results are the same on every platform and Rust release, unlike `DefaultHasher`,
+//! for hashes that are persisted or that decide where strings are stored
+
+/// 64-bit FNV-1a hasher whose result is finished by the finalizer of SplitMix64,
+/// so that every bit of the hash depends on every byte written
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    pub(crate) fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+    /// Returns a hasher whose hashes are independent of those of hashers with other seeds
+    pub(crate) fn with_seed(seed: u64) -> Self {
+        Self(Self::OFFSET_BASIS ^ mix(seed))
+    }
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+    /// Writes `string` after its length, so that the boundaries between strings change the hash
+    pub(crate) fn write_str(&mut self, string: &str) {
+        self.write_u64(string.len() as u64);
+        self.write(string.as_bytes());
+    }
+    pub(crate) fn finish(&self) -> u64 {
+        mix(self.0)
+    }
+}
+
+/// Returns `value` with its bits mixed by the finalizer of SplitMix64
+pub(crate) fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9e3779b97f4a7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
@@ -0,0 +1,181 @@
+//! Implementation of the symmetric delete algorithm of SymSpell for whole-string similarity search
+//!
+//! Two strings within an edit distance of k give the same string after at most k deletions from each,
+//! since a substitution is a deletion from both and an insertion is a deletion from the other,
+//! so the deletion variants of the stored strings are precomputed to find candidates by looking up the variants of the query,
+//! and the candidates are verified with the `levenshtein` module
+//!
+//! A string of n characters has about (n choose k) variants, so the distance is limited to `MAX_DISTANCE` to bound memory,
+//! and queries beyond it compare every stored string instead
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{levenshtein, MeasuredString};
+
+/// Type that bounds the number of stored strings
+type SSS = u32;
+
+/// Largest edit distance that deletion variants can be precomputed for
+pub const MAX_DISTANCE: usize = 2;
+
+/// Error from asking for deletion variants of more than `MAX_DISTANCE` edits, holding the distance asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxDistanceTooLarge(pub usize);
+
+impl fmt::Display for MaxDistanceTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "deletion variants are only precomputed for up to {MAX_DISTANCE} edits, not {}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for MaxDistanceTooLarge {}
+
+/// Structure that finds the stored strings within a small edit distance of a query by looking up their deletion variants
+#[derive(Clone, Debug)]
+pub struct SymSpell<'stored> {
+    /// Stored strings in the order they were inserted
+    strings: Vec<&'stored str>,
+    /// Strings made by deleting up to `max_distance` characters from the stored strings,
+    /// with the indices of the strings they were made from
+    deletes: HashMap<String, Vec<SSS>>,
+    /// Largest edit distance that queries can find strings within
+    max_distance: usize,
+}
+
+impl<'stored> SymSpell<'stored> {
+    /// Constructs an index over the strings in `source` that finds strings within `MAX_DISTANCE` edits (does not copy strings)
+    pub fn new(source: impl IntoIterator<Item = &'stored str>) -> Self {
+        Self::build(source, MAX_DISTANCE)
+    }
+    /// Constructs an index over the strings in `source` that finds strings within `max_distance` edits (does not copy strings),
+    /// which takes less memory for smaller distances
+    ///
+    /// Returns an error if `max_distance` is larger than `MAX_DISTANCE`
+    pub fn with_max_distance(
+        source: impl IntoIterator<Item = &'stored str>,
+        max_distance: usize,
+    ) -> Result<Self, MaxDistanceTooLarge> {
+        if max_distance > MAX_DISTANCE {
+            return Err(MaxDistanceTooLarge(max_distance));
+        }
+        Ok(Self::build(source, max_distance))
+    }
+    fn build(source: impl IntoIterator<Item = &'stored str>, max_distance: usize) -> Self {
+        let mut index = Self {
+            strings: Vec::new(),
+            deletes: HashMap::new(),
+            max_distance,
+        };
+        for string in source {
+            index.insert(string);
+        }
+        index
+    }
+    /// Inserts a string and its deletion variants into the index
+    pub fn insert(&mut self, string: &'stored str) {
+        let index = self.strings.len() as SSS;
+        self.strings.push(string);
+        for variant in deletes(string, self.max_distance) {
+            self.deletes.entry(variant).or_default().push(index);
+        }
+    }
+    /// Returns the number of stored strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+    /// Returns whether no strings are stored
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+    /// Returns the largest edit distance that queries can find strings within
+    pub fn max_distance(&self) -> usize {
+        self.max_distance
+    }
+    /// Returns the stored strings within an edit distance of `threshold` from `query`,
+    /// sorted by edit distance and then lexicographical order
+    ///
+    /// Thresholds larger than `max_distance` are beyond the precomputed variants, so every stored string is compared instead
+    pub fn query(&self, query: &str, threshold: usize) -> Vec<MeasuredString> {
+        if threshold > self.max_distance {
+            return self.verify(query, threshold, 0..self.strings.len() as SSS);
+        }
+        let mut candidates = HashSet::<SSS>::new();
+        for variant in deletes(query, threshold) {
+            if let Some(indices) = self.deletes.get(&variant) {
+                candidates.extend(indices);
+            }
+        }
+        self.verify(query, threshold, candidates)
+    }
+    /// Returns the strings at `indices` within an edit distance of `threshold` from `query`,
+    /// sorted by edit distance and then lexicographical order
+    fn verify(
+        &self,
+        query: &str,
+        threshold: usize,
+        indices: impl IntoIterator<Item = SSS>,
+    ) -> Vec<MeasuredString> {
+        let mut result: Vec<MeasuredString> = indices
+            .into_iter()
+            .filter_map(|index| {
+                let string = self.strings[index as usize];
+                levenshtein::distance_within(query, string, threshold).map(|distance| {
+                    MeasuredString {
+                        string: string.to_string(),
+                        distance,
+                    }
+                })
+            })
+            .collect();
+        result.sort();
+        result.dedup();
+        result
+    }
+    /// Returns the top `requested` stored strings closest to `query`, sorted by edit distance and then lexicographical order
+    ///
+    /// Only strings within `max_distance` edits are looked up, unless there are fewer than `requested` of them
+    pub fn topk(&self, query: &str, requested: usize) -> Vec<MeasuredString> {
+        self.threshold_topk(query, requested, usize::MAX)
+    }
+    /// Same as `topk`, but only returns strings within an edit distance of `max_threshold` from `query`
+    pub fn threshold_topk(
+        &self,
+        query: &str,
+        requested: usize,
+        max_threshold: usize,
+    ) -> Vec<MeasuredString> {
+        let mut result = self.query(query, max_threshold.min(self.max_distance));
+        // the strings that weren't found are farther than those that were, so they're only needed to fill the results
+        if result.len() < requested && max_threshold > self.max_distance {
+            result = self.query(query, max_threshold);
+        }
+        result.truncate(requested);
+        result
+    }
+}
+
+/// Returns the distinct strings made by deleting at most `max_deletions` characters from `string`, including `string` itself
+fn deletes(string: &str, max_deletions: usize) -> HashSet<String> {
+    let mut variants = HashSet::from([string.to_string()]);
+    // variants with one more deletion are made from the variants with the most deletions so far
+    let mut frontier = vec![string.to_string()];
+    for _ in 0..max_deletions {
+        let mut next = Vec::new();
+        for variant in &frontier {
+            for (i, character) in variant.char_indices() {
+                let deleted = format!("{}{}", &variant[..i], &variant[i + character.len_utf8()..]);
+                if variants.insert(deleted.clone()) {
+                    next.push(deleted);
+                }
+            }
+        }
+        frontier = next;
+    }
+    variants
+}
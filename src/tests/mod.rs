@@ -1,6 +1,9 @@
 use std::{
+    collections::HashMap,
     fs,
     io::Write,
+    sync::Arc,
+    thread,
     time::{Duration, Instant}
 };
 
@@ -9,10 +12,50 @@ use rand::thread_rng;
 use yoke::Yoke;
 
 use crate::{
-    levenshtein::{prefix_edit_distance, sample_edited_string, unindexed_autocomplete},
+    levenshtein::{
+        alignment, confidence_prefix_edit_distance, damerau_edit_distance,
+        damerau_prefix_edit_distance, distance_with, distance_within, edit_distance, hamming, jaro,
+        jaro_winkler, lcs_length, lcs_similarity, prefix_alignment, prefix_distance_with,
+        prefix_distance_within, prefix_edit_distance, prefix_edit_distance_with_end,
+        prefix_similarity, qgram_cosine, qgram_jaccard, qgrams, sample_edited_string, similarity,
+        slice_edit_distance, slice_hamming, slice_lcs_length, slice_prefix_edit_distance,
+        slice_substring_edit_distance, substring_edit_distance, unindexed_autocomplete,
+        unindexed_correct, unrestricted_damerau_edit_distance, weighted_edit_distance,
+        weighted_prefix_edit_distance, ConfusionCosts, DamerauEditDistance,
+        DamerauPrefixEditDistance, Edit, EditCosts, EditDistance, JaroWinklerDistance,
+        KeyboardCosts, KeyboardLayout, Metric, OperationCosts, PrefixEditDistance, QGramFilter,
+        Scratch, UnrestrictedDamerauEditDistance, WeightedEditDistance, WeightedPrefixEditDistance,
+    },
+    prefix::meta::{
+        keypad_digit, load_lines, not_punctuation, Cache, CacheStats, CaseFolding, CharFilter,
+        DedupPolicy, HybridWeights, JaroWinklerRanking, MetaAutocompleterBuilder, Pipeline,
+        QueryOptions, QueryScratch, Record, SearchAlgorithm, SharedCache, SubsequenceScoring,
+        ThresholdPolicy, TypingSession, DEFAULT_SHARD_COUNT,
+    },
+    prefix::phonetic::{double_metaphone, double_metaphone_primary, soundex},
+    prefix::reload::ReloadCache,
+    prefix::sharded::ShardedCache,
+    prefix::tokens::Tokenizer,
+    prefix::FromStrings,
+    strprox::hs_tree::{
+        dedup_clusters, similarity_join, similarity_self_join, BTreeMapGAT, Linkage,
+    },
+    strprox::minhash::MinHashParams,
+    strprox::searcher::{Backend, MatchKind, Searcher, SimilaritySearcher},
+    strprox::BKTree,
     strprox::FstAutocompleter,
+    strprox::HSTree,
+    strprox::InfixAutocompleter,
+    strprox::KeyedAutocompleter,
     strprox::MetaAutocompleter,
-    Autocompleter, MeasuredPrefix, prefix::FromStrings,
+    strprox::MinHashIndex,
+    strprox::PhoneticAutocompleter,
+    strprox::ReloadableAutocompleter,
+    strprox::ShardedAutocompleter,
+    strprox::SnapshotAutocompleter,
+    strprox::TokenAutocompleter,
+    strprox::{MaxDistanceTooLarge, SymSpell},
+    Autocompleter, MeasuredPrefix, MeasuredString, MetricString, TreeString,
 };
 
 type YokedMetaAutocompleter = Yoke<MetaAutocompleter<'static>, Vec<String>>;
@@ -22,6 +65,24 @@ fn contains_string(measures: &Vec<MeasuredPrefix>, expected: &str) -> bool {
     measures.iter().any(|measure| measure.string == expected)
 }
 
+/// Returns an autocompleter over a few strings that share prefixes, for tests of caching and persistence
+fn sample_autocompleter() -> MetaAutocompleter<'static> {
+    let strings: Vec<TreeString> = ["solve", "solution", "sold", "example", "sample"]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    MetaAutocompleter::new(strings.len(), strings)
+}
+
+/// Strings that share prefixes, with "zolo" one substitution away from "solo", for tests of queries
+const SOLO_STRINGS: [&str; 7] = ["soho", "solid", "solo", "solve", "soon", "throw", "zolo"];
+
+/// Returns an autocompleter over `SOLO_STRINGS`
+fn solo_autocompleter() -> MetaAutocompleter<'static> {
+    let strings: Vec<TreeString> = SOLO_STRINGS.into_iter().map(Into::into).collect();
+    MetaAutocompleter::new(strings.len(), strings)
+}
+
 // Words from https://github.com/dwyl/english-words/blob/master/words.txt
 const WORDS: &str = include_str!("words.txt");
 
@@ -306,6 +367,3345 @@ mod generic {
     mod fst {}
 }
 
+#[test]
+/// Tests that filtered strings don't take up any of the requested slots
+fn meta_filtered() {
+    let source: Vec<_> = vec!["soho", "solid", "solo", "solve", "soon", "throw"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let mut cache = Cache::default();
+    let result = autocompleter.autocomplete_filtered("ssol", 2, |s| s != "solid", &mut cache);
+    assert_eq!(result.len(), 2);
+    assert!(!contains_string(&result, "solid"));
+}
+
+#[test]
+/// Tests that payloads are returned with their strings, including those of duplicate strings
+fn meta_payloads() {
+    let source = vec![("solo", 1), ("solid", 2), ("solo", 3), ("throw", 4)];
+    let autocompleter = MetaAutocompleter::new_with_payloads(
+        source.len(),
+        source.into_iter().map(|(s, payload)| (s.into(), payload)),
+    );
+    assert_eq!(autocompleter.payloads("solo"), &[1, 3]);
+    assert_eq!(autocompleter.payloads("soon"), &[] as &[i32]);
+
+    let mut cache = Cache::default();
+    let result = autocompleter.autocomplete_entries("sol", 2, &mut cache);
+    for entry in &result {
+        assert!(autocompleter
+            .payloads(&entry.measure.string)
+            .contains(entry.payload));
+    }
+    assert!(result
+        .iter()
+        .any(|entry| entry.measure.string == "solo" && *entry.payload == 3));
+}
+
+#[test]
+/// Tests that the indices returned refer to the same results as the copied strings
+fn meta_indices() {
+    let source: Vec<_> = vec!["soho", "solid", "solo", "solve", "soon", "throw"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let indices = autocompleter.autocomplete_indices("ssol", 3, &mut Cache::default());
+    let result = autocompleter.autocomplete("ssol", 3, &mut Cache::default());
+    assert_eq!(indices.len(), result.len());
+    for (measure, expected) in indices.iter().zip(&result) {
+        assert_eq!(autocompleter.get(measure.index), expected.string);
+        assert_eq!(measure.prefix_distance, expected.prefix_distance);
+    }
+}
+
+#[test]
+/// Tests that the matched span of each result is a prefix with the length of the matched depth
+fn meta_matched_spans() {
+    let source: Vec<_> = vec!["soho", "solid", "solo", "solve", "soon", "throw", "ésol"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let result = autocompleter.autocomplete_matched("ssol", 4, &mut Cache::default());
+    assert_ne!(result.len(), 0);
+    for matched in &result {
+        let prefix = &matched.measure.string[matched.span.clone()];
+        assert_eq!(matched.span.start, 0);
+        assert_eq!(prefix.chars().count(), matched.depth);
+    }
+}
+
+#[test]
+/// Tests that the corrected prefix is the query itself without typos and a stored prefix otherwise
+fn meta_corrected_prefix() {
+    let source: Vec<_> = vec!["exam", "example", "examples", "solo", "solve", "soon"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let (corrected, _) = autocompleter.autocomplete_corrected("sol", 2, &mut Cache::default());
+    assert_eq!(corrected.as_deref(), Some("sol"));
+    let (corrected, result) =
+        autocompleter.autocomplete_corrected("exampel", 2, &mut Cache::default());
+    let corrected = corrected.unwrap();
+    assert!(corrected.starts_with("exampl"));
+    assert!(contains_string(&result, "example"));
+}
+
+/// Returns `string` with the `edits` applied
+fn apply_edits(string: &str, edits: &[Edit]) -> String {
+    let chars: Vec<char> = string.chars().collect();
+    let mut result = String::new();
+    let mut edits = edits.iter().peekable();
+    for position in 0..=chars.len() {
+        let mut keep = position < chars.len();
+        while let Some(&&edit) = edits.peek() {
+            match edit {
+                Edit::Insert {
+                    position: p,
+                    character,
+                } if p == position => result.push(character),
+                Edit::Delete { position: p } if p == position => keep = false,
+                Edit::Substitute {
+                    position: p,
+                    character,
+                } if p == position => {
+                    result.push(character);
+                    keep = false;
+                }
+                _ => break,
+            }
+            edits.next();
+        }
+        if keep {
+            result.push(chars[position]);
+        }
+    }
+    result
+}
+
+#[test]
+/// Tests that alignments have as many edits as the distances and transform the first string into the second
+fn alignments() {
+    for (first, second) in [
+        ("kitten", "sitting"),
+        ("", "ab"),
+        ("ab", ""),
+        ("zucc", "success"),
+    ] {
+        let edits = alignment(first, second);
+        assert_eq!(edits.len(), edit_distance(first, second));
+        assert_eq!(apply_edits(first, &edits), second);
+
+        let edits = prefix_alignment(first, second);
+        assert_eq!(edits.len(), prefix_edit_distance(first, second));
+        assert!(second.starts_with(&apply_edits(first, &edits)));
+    }
+}
+
+#[test]
+/// Tests that heavier strings are ranked first among strings with the same PED
+fn meta_weighted() {
+    let source = vec![("solid", 1), ("solo", 5), ("solve", 3), ("solo", 2)];
+    let autocompleter = MetaAutocompleter::new_weighted(
+        source.len(),
+        source.into_iter().map(|(s, weight)| (s.into(), weight)),
+    );
+    let result = autocompleter.autocomplete("sol", 3, &mut Cache::default());
+    let result_strings: Vec<&str> = result
+        .iter()
+        .map(|measured_prefix| measured_prefix.string.as_str())
+        .collect();
+    assert_eq!(result_strings, vec!["solo", "solve", "solid"]);
+}
+
+#[test]
+/// Tests that completions of exact prefixes are the same with and without precomputing
+fn meta_precomputed_top() {
+    let source = vec![
+        ("solid", 1),
+        ("solo", 5),
+        ("solve", 3),
+        ("soon", 4),
+        ("throw", 2),
+    ];
+    let mut autocompleter = MetaAutocompleter::new_weighted(
+        source.len(),
+        source.into_iter().map(|(s, weight)| (s.into(), weight)),
+    );
+    let expected: Vec<_> = ["", "s", "sol", "solo", "x"]
+        .iter()
+        .map(|prefix| autocompleter.complete(prefix, 2))
+        .collect();
+    autocompleter.precompute_top(3);
+    for (prefix, expected) in ["", "s", "sol", "solo", "x"].iter().zip(expected) {
+        assert_eq!(autocompleter.complete(prefix, 2), expected);
+    }
+    let result = autocompleter.complete("so", 2);
+    assert_eq!(result[0].string, "solo");
+    assert_eq!(result[1].string, "soon");
+
+    let result = autocompleter.autocomplete("", 2, &mut Cache::default());
+    assert!(contains_string(&result, "solo"));
+    assert!(contains_string(&result, "soon"));
+}
+
+#[test]
+/// Tests that exact matches are returned first when there are enough, and typos are still corrected otherwise
+fn meta_exact_first() {
+    let source: Vec<_> = vec!["soho", "solid", "solo", "solve", "soon", "throw"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let options = QueryOptions {
+        exact_first: true,
+        ..Default::default()
+    };
+    let result = autocompleter.autocomplete_with_options(
+        "sol",
+        3,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    let result_strings: Vec<&str> = result
+        .iter()
+        .map(|measured_prefix| measured_prefix.string.as_str())
+        .collect();
+    assert_eq!(result_strings, vec!["solid", "solo", "solve"]);
+
+    let result = autocompleter.autocomplete_with_options(
+        "thro",
+        2,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    assert!(contains_string(&result, "throw"));
+    assert_eq!(result.len(), 2);
+}
+
+#[test]
+/// Tests that results all start with the pinned characters of the query
+fn meta_strict_prefix() {
+    let autocompleter = solo_autocompleter();
+    let options = QueryOptions {
+        strict_prefix: 1,
+        ..Default::default()
+    };
+    let result = autocompleter.autocomplete_with_options(
+        "zsol",
+        3,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    assert!(contains_string(&result, "zolo"));
+    assert!(result.iter().all(|measure| measure.string.starts_with('z')));
+
+    let result = autocompleter.autocomplete_with_options(
+        "xolo",
+        3,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    assert_eq!(result.len(), 0);
+}
+
+#[test]
+/// Tests that the allowed prefix edit distance of results grows with the length of the query
+fn meta_threshold() {
+    let scaled = ThresholdPolicy::Scaled { per_chars: 4 };
+    assert_eq!(scaled.max_edits(2), 0);
+    assert_eq!(scaled.max_edits(3), 1);
+    assert_eq!(scaled.max_edits(6), 1);
+    assert_eq!(scaled.max_edits(7), 2);
+
+    let mut autocompleter = solo_autocompleter();
+    autocompleter.set_threshold(Some(scaled));
+    let result = autocompleter.autocomplete("zo", 10, &mut Cache::default());
+    assert_eq!(result.len(), 1);
+    assert!(contains_string(&result, "zolo"));
+
+    let result = autocompleter.autocomplete("ssol", 10, &mut Cache::default());
+    assert!(contains_string(&result, "solo"));
+    assert!(result.iter().all(|measure| measure.prefix_distance <= 1));
+
+    autocompleter.set_threshold(None);
+    let result = autocompleter.autocomplete("zo", 10, &mut Cache::default());
+    assert!(result.len() > 1);
+}
+
+#[test]
+/// Tests that completions of a typing session match those of separate queries
+fn meta_typing_session() {
+    let autocompleter = solo_autocompleter();
+    let mut session = TypingSession::new(&autocompleter, 3);
+    for character in "solv".chars() {
+        session.push_char(character);
+    }
+    assert_eq!(session.query(), "solv");
+    let result = session.completions();
+    assert_eq!(
+        result,
+        autocompleter.autocomplete("solv", 3, &mut Cache::default())
+    );
+    assert_eq!(result[0].string, "solve");
+
+    let result = session.pop_char();
+    assert_eq!(session.query(), "sol");
+    assert!(result.iter().all(|measure| measure.prefix_distance == 0));
+    assert_eq!(
+        result,
+        autocompleter.autocomplete("sol", 3, &mut Cache::default())
+    );
+}
+
+#[test]
+/// Tests that a shared cache gives the same results when the query shrinks and grows again
+fn meta_cache_backspace() {
+    let autocompleter = solo_autocompleter();
+    let mut cache = Cache::default();
+    for query in ["zsolv", "zso", "zsol", "zs", "zsolv", "ssol"] {
+        let result = autocompleter.autocomplete(query, 3, &mut cache);
+        assert_eq!(
+            result,
+            autocompleter.autocomplete(query, 3, &mut Cache::default())
+        );
+    }
+}
+
+#[test]
+/// Tests that every word of a query is matched against the words of multi-word strings in any order
+fn tokens_any_order() {
+    let source: Vec<_> = vec!["new york city", "newark", "york minster", "mexico city"];
+    let autocompleter = TokenAutocompleter::new(source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_tokens("new yrok ci", 2, &mut Cache::default());
+    assert_eq!(result[0].string, "new york city");
+    // "yrok" needs 2 edits to match "york"
+    assert_eq!(result[0].prefix_distance, 2);
+
+    let result = autocompleter.autocomplete_tokens("city mex", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "mexico city");
+    assert_eq!(result[0].prefix_distance, 0);
+
+    assert!(autocompleter
+        .autocomplete_tokens(" ", 1, &mut Cache::default())
+        .is_empty());
+}
+
+#[test]
+/// Tests that queries match inside of stored strings
+fn infix_search() {
+    let source: Vec<_> = vec!["strprox_utils", "prefix_tree", "levenshtein", "proximity"];
+    let autocompleter = InfixAutocompleter::new(source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_infix("utils", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "strprox_utils");
+    assert_eq!(result[0].prefix_distance, 0);
+
+    let result = autocompleter.autocomplete_infix("shtien", 2, &mut Cache::default());
+    assert_eq!(result[0].string, "levenshtein");
+    assert_eq!(result[0].prefix_distance, 2);
+    assert_ne!(result[0].string, result[1].string);
+
+    assert_eq!(substring_edit_distance("prox", "strprox_utils"), 0);
+    assert_eq!(substring_edit_distance("proxstr", "strprox_utils"), 3);
+    assert_eq!(substring_edit_distance("abc", ""), 3);
+}
+
+#[test]
+/// Tests that whole-string corrections match the full edit distances of the unindexed search
+fn meta_correct() {
+    let autocompleter = solo_autocompleter();
+    let result = autocompleter.correct("slove", 2, 10);
+    let mut expected: Vec<_> = SOLO_STRINGS
+        .iter()
+        .map(|&string| (edit_distance("slove", string), string.to_string()))
+        .filter(|&(distance, _)| distance <= 2)
+        .collect();
+    expected.sort();
+    let result: Vec<_> = result
+        .into_iter()
+        .map(|measure| (measure.distance, measure.string))
+        .collect();
+    assert_eq!(result, expected);
+    assert_eq!(autocompleter.correct("sol", 0, 10).len(), 0);
+    assert_eq!(autocompleter.correct("solo", 1, 1)[0].string, "solo");
+}
+
+#[test]
+/// Tests that corrections with the q-gram pre-filter are the same as without it
+fn meta_correct_qgrams() {
+    let strings: Vec<TreeString> = WORDS.lines().map(Into::into).collect();
+    let autocompleter = MetaAutocompleter::new(strings.len(), strings);
+    for query in [
+        "apple",
+        "pineaple",
+        "recieve",
+        "independant",
+        "xylophone",
+        "a",
+        "",
+    ] {
+        for max_distance in 0..4 {
+            let expected = autocompleter.correct(query, max_distance, 20);
+            for q in 0..4 {
+                assert_eq!(
+                    autocompleter.correct_with_qgrams(query, max_distance, 20, q),
+                    expected,
+                    "{query} {max_distance} {q}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+/// Tests that excluded strings are skipped without reducing the number of results
+fn meta_exclusions() {
+    let autocompleter = solo_autocompleter();
+    let options = QueryOptions {
+        exclude_prefixes: vec!["soh".to_string(), "sol".to_string()],
+        deny: ["soon".to_string()].into(),
+        ..Default::default()
+    };
+    let result =
+        autocompleter.autocomplete_with_options("so", 2, |_| true, &options, &mut Cache::default());
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .iter()
+        .all(|measure| measure.string == "throw" || measure.string == "zolo"));
+}
+
+#[test]
+/// Tests that counts of strings within a prefix edit distance match the unindexed counts
+fn meta_count_within() {
+    let autocompleter = solo_autocompleter();
+    for query in ["", "so", "ssol", "zsolv", "throw"] {
+        for b in 0..=3 {
+            let expected = SOLO_STRINGS
+                .iter()
+                .filter(|string| prefix_edit_distance(query, string) <= b)
+                .count();
+            assert_eq!(autocompleter.count_within(query, b), expected);
+        }
+    }
+}
+
+#[test]
+/// Tests that a batch of queries gives the same results as running them separately
+fn meta_batch() {
+    let autocompleter = solo_autocompleter();
+    let queries = ["so", "sol", "ssol", "", "thr", "so"];
+    let results = autocompleter.autocomplete_batch(&queries, 3);
+    assert_eq!(results.len(), queries.len());
+    for (query, result) in queries.iter().zip(&results) {
+        assert_eq!(
+            *result,
+            autocompleter.autocomplete(query, 3, &mut Cache::default())
+        );
+    }
+    #[cfg(feature = "rayon")]
+    assert_eq!(autocompleter.par_autocomplete_batch(&queries, 3), results);
+}
+
+#[test]
+/// Tests that a search which runs out of its budget still returns results and reports that it stopped early
+fn meta_bounded() {
+    let autocompleter = solo_autocompleter();
+    let options = QueryOptions {
+        max_matchings: Some(3),
+        ..Default::default()
+    };
+    let (result, truncated) =
+        autocompleter.autocomplete_bounded("solvx", 2, |_| true, &options, &mut Cache::default());
+    assert!(truncated);
+    assert_eq!(result.len(), 2);
+
+    let options = QueryOptions {
+        deadline: Some(Instant::now() + Duration::from_secs(60)),
+        ..Default::default()
+    };
+    let (result, truncated) =
+        autocompleter.autocomplete_bounded("solvx", 2, |_| true, &options, &mut Cache::default());
+    assert!(!truncated);
+    assert_eq!(
+        result,
+        autocompleter.autocomplete("solvx", 2, &mut Cache::default())
+    );
+}
+
+#[test]
+/// Tests that a cancelled query stops and doesn't leave incomplete matchings in the cache
+fn meta_cancel() {
+    let autocompleter = solo_autocompleter();
+    let mut cache = Cache::default();
+    let options = QueryOptions::default();
+    options.cancel.clone().cancel();
+    let (_, truncated) =
+        autocompleter.autocomplete_bounded("ssol", 3, |_| true, &options, &mut cache);
+    assert!(truncated);
+
+    let result = autocompleter.autocomplete("ssol", 3, &mut cache);
+    assert_eq!(
+        result,
+        autocompleter.autocomplete("ssol", 3, &mut Cache::default())
+    );
+}
+
+#[test]
+/// Tests that results beyond the maximum prefix edit distance are left out instead of filling the requested slots
+fn meta_max_prefix_distance() {
+    let autocompleter = solo_autocompleter();
+    let options = QueryOptions {
+        max_prefix_distance: Some(1),
+        ..Default::default()
+    };
+    let result = autocompleter.autocomplete_with_options(
+        "zol",
+        10,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    assert!(contains_string(&result, "zolo"));
+    assert!(result.iter().all(|measure| measure.prefix_distance <= 1));
+
+    let yoked = YokedMetaAutocompleter::from_strings(&SOLO_STRINGS);
+    let result = yoked.threshold_topk("zol", 10, 0);
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].string, "zolo");
+}
+
+#[test]
+/// Tests that grouped results branch from the matched prefix
+fn meta_grouped() {
+    let source: Vec<_> = vec![
+        "applaud",
+        "apple",
+        "apples",
+        "application",
+        "apply",
+        "banana",
+    ];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let groups = autocompleter.autocomplete_grouped("appl", 5, &mut Cache::default());
+    let prefixes: Vec<_> = groups.iter().map(|group| group.prefix.as_str()).collect();
+    assert_eq!(prefixes, vec!["appla", "apple", "appli", "apply"]);
+    let apple = &groups[1].results;
+    assert_eq!(apple.len(), 2);
+    assert!(apple
+        .iter()
+        .all(|measure| measure.string.starts_with("apple")));
+}
+
+#[test]
+/// Tests that pinned strings come first for queries with their prefixes
+fn meta_pinned() {
+    let mut autocompleter = solo_autocompleter();
+    assert!(autocompleter.pin("so", "zolo"));
+    assert!(autocompleter.pin("sol", "solve"));
+    assert!(!autocompleter.pin("so", "sun"));
+
+    let result = autocompleter.autocomplete("sol", 3, &mut Cache::default());
+    assert_eq!(result.len(), 3);
+    assert_eq!(result[0].string, "solve");
+    assert_eq!(result[1].string, "zolo");
+
+    // not pinned for other prefixes
+    let result = autocompleter.autocomplete("zo", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "zolo");
+    let result = autocompleter.autocomplete("s", 1, &mut Cache::default());
+    assert_ne!(result[0].string, "zolo");
+
+    // only within the threshold
+    autocompleter.set_threshold(Some(ThresholdPolicy::Fixed(0)));
+    let result = autocompleter.autocomplete("so", 2, &mut Cache::default());
+    assert!(!contains_string(&result, "zolo"));
+
+    autocompleter.unpin("so");
+    autocompleter.set_threshold(None);
+    let result = autocompleter.autocomplete("so", 1, &mut Cache::default());
+    assert_ne!(result[0].string, "zolo");
+}
+
+#[test]
+/// Tests that short queries are only completed exactly and that stopwords are ignored
+fn min_fuzzy_len_and_stopwords() {
+    let mut autocompleter = solo_autocompleter();
+    autocompleter.set_min_fuzzy_len(3);
+    let result = autocompleter.autocomplete("th", 5, &mut Cache::default());
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].string, "throw");
+    assert!(autocompleter
+        .autocomplete("xo", 5, &mut Cache::default())
+        .is_empty());
+    let result = autocompleter.autocomplete("zol", 5, &mut Cache::default());
+    assert!(result.len() > 1);
+
+    let source: Vec<_> = vec!["the new york times", "the city", "new yorker"];
+    let mut autocompleter = TokenAutocompleter::new(source.iter().map(|&s| s.into()));
+    autocompleter.set_stopwords(["the".to_string()]);
+    let result = autocompleter.autocomplete_tokens("the yorker", 3, &mut Cache::default());
+    assert_eq!(result[0].string, "new yorker");
+    assert_eq!(result[0].prefix_distance, 0);
+    assert!(result
+        .iter()
+        .skip(1)
+        .all(|measure| measure.prefix_distance > 0));
+}
+
+#[test]
+/// Tests that results have the full edit distances of their strings along with their PEDs
+fn meta_distances() {
+    let autocompleter = solo_autocompleter();
+    let result = autocompleter.autocomplete_with_distances("sol", 3, &mut Cache::default());
+    assert_eq!(result.len(), 3);
+    for distanced in &result {
+        assert_eq!(distanced.measure.prefix_distance, 0);
+        assert_eq!(
+            distanced.distance,
+            edit_distance("sol", &distanced.measure.string)
+        );
+    }
+    let solo = result
+        .iter()
+        .find(|distanced| distanced.measure.string == "solo")
+        .unwrap();
+    assert_eq!(solo.distance, 1);
+}
+
+#[test]
+/// Tests that the methods that add to the results of `autocomplete` normalize the query only once
+fn meta_normalized_once() {
+    let source = ["banana", "bandana"];
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .normalization(|string| string.replace('a', "aa"))
+        .build(source.iter().map(|&s| s.into()));
+    let mut cache = Cache::default();
+    let distanced = autocompleter.autocomplete_with_distances("banana", 1, &mut cache);
+    assert_eq!(distanced[0].measure.prefix_distance, 0);
+    assert_eq!(distanced[0].distance, 0);
+    let aligned = autocompleter.autocomplete_with_alignments("banana", 1, &mut cache);
+    assert_eq!(aligned[0].measure.prefix_distance, 0);
+    assert!(aligned[0].edits.is_empty());
+    let scored = autocompleter.autocomplete_scored("banana", 1, &mut cache);
+    assert_eq!(scored[0].prefix_similarity, 1.0);
+    assert_eq!(scored[0].similarity, 1.0);
+}
+
+#[test]
+/// Tests that results written into a reused buffer replace its previous contents
+fn meta_into_buffer() {
+    let autocompleter = solo_autocompleter();
+    let mut cache = Cache::default();
+    let mut scratch = QueryScratch::new();
+    let mut buffer = Vec::new();
+    autocompleter.autocomplete_into("so", 4, &mut cache, &mut scratch, &mut buffer);
+    assert_eq!(
+        buffer,
+        autocompleter.autocomplete("so", 4, &mut Cache::default())
+    );
+    autocompleter.autocomplete_into("thr", 1, &mut cache, &mut scratch, &mut buffer);
+    assert_eq!(
+        buffer,
+        autocompleter.autocomplete("thr", 1, &mut Cache::default())
+    );
+    autocompleter.autocomplete_into("sol", 3, &mut cache, &mut scratch, &mut buffer);
+    assert_eq!(
+        buffer,
+        autocompleter.autocomplete("sol", 3, &mut Cache::default())
+    );
+}
+
+#[test]
+/// Tests that ties at the cutoff keep the lexicographically first strings, the same as the unindexed search
+fn deterministic_ties() {
+    let source: Vec<_> = vec!["aba", "abc", "abd", "abe", "abf", "abg", "abz", "xyz"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let fst = FstAutocompleter::<Vec<u8>>::from_strings(&source);
+    let meta = MetaAutocompleter::new(cows.len(), cows.clone());
+    for query in ["ab", "abz"] {
+        for requested in 1..=5 {
+            let expected = unindexed_autocomplete(query, requested, &cows);
+            assert_eq!(fst.autocomplete(query, requested), expected);
+            assert_eq!(
+                meta.autocomplete(query, requested, &mut Cache::default()),
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+/// Tests that walking the trie visits every prefix of the stored strings with their counts
+fn trie_traversal() {
+    let autocompleter = solo_autocompleter();
+    let trie = &autocompleter.trie;
+    let strings: Vec<_> = trie.iter_strings_with_prefix("sol").collect();
+    assert_eq!(strings, vec!["solid", "solo", "solve"]);
+    assert_eq!(trie.iter_strings_with_prefix("x").count(), 0);
+
+    let mut visited = Vec::new();
+    trie.visit_prefix("sol", |node, prefix| {
+        assert_eq!(node.depth(), prefix.chars().count());
+        assert_eq!(prefix.chars().last(), Some(node.character()));
+        visited.push((prefix.to_string(), node.string_count()));
+    });
+    assert_eq!(visited[0], ("sol".to_string(), 3));
+    assert_eq!(visited[1], ("soli".to_string(), 1));
+    assert!(visited.contains(&("solv".to_string(), 1)));
+    assert!(visited.iter().all(|(prefix, _)| prefix.starts_with("sol")));
+    // one node for each extra character of the strings
+    assert_eq!(visited.len(), 1 + 2 + 1 + 2);
+}
+
+#[test]
+/// Tests that ranges of the stored strings are in lexicographical order
+fn meta_ranges() {
+    let autocompleter = solo_autocompleter();
+    let strings: Vec<_> = autocompleter.range(1..3).collect();
+    assert_eq!(strings, vec!["solid", "solo"]);
+    let strings: Vec<_> = autocompleter.strings_between("solo", "t").collect();
+    assert_eq!(strings, vec!["solo", "solve", "soon"]);
+    assert_eq!(autocompleter.strings_between("t", "a").count(), 0);
+    assert_eq!(
+        autocompleter.strings_between("", "~").count(),
+        SOLO_STRINGS.len()
+    );
+}
+
+#[test]
+/// Tests that queries are extended to the prefix shared by their completions
+fn meta_longest_common_prefix() {
+    let source: Vec<_> = vec![
+        "soho", "solid", "solve", "soon", "throw", "throwing", "zolo",
+    ];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    assert_eq!(autocompleter.longest_common_prefix("so"), "so");
+    assert_eq!(autocompleter.longest_common_prefix("sol"), "sol");
+    assert_eq!(autocompleter.longest_common_prefix("soli"), "solid");
+    assert_eq!(autocompleter.longest_common_prefix("t"), "throw");
+    assert_eq!(autocompleter.longest_common_prefix("z"), "zolo");
+    assert_eq!(autocompleter.longest_common_prefix("solx"), "sol");
+    assert_eq!(autocompleter.longest_common_prefix(""), "");
+}
+
+#[test]
+/// Tests exact lookups of stored strings
+fn meta_contains() {
+    let autocompleter = solo_autocompleter();
+    for string in &SOLO_STRINGS {
+        assert!(autocompleter.contains(string));
+        assert_eq!(
+            autocompleter.get(autocompleter.position(string).unwrap()),
+            *string
+        );
+    }
+    assert!(!autocompleter.contains("sol"));
+    assert_eq!(autocompleter.position("zzz"), None);
+}
+
+#[test]
+/// Export the stored strings and rebuild an equivalent autocompleter from them
+fn meta_iter_strings() {
+    let source: Vec<_> = vec!["solo", "soho", "zolo", "solid", "solo", "throw"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows);
+    let exported: Vec<String> = autocompleter.iter_strings().map(str::to_string).collect();
+    assert_eq!(exported, vec!["soho", "solid", "solo", "throw", "zolo"]);
+    let strings = autocompleter.into_strings();
+    let rebuilt = MetaAutocompleter::new(strings.len(), strings);
+    assert_eq!(rebuilt.iter_strings().collect::<Vec<_>>(), exported);
+}
+
+#[test]
+/// Merge two shards with an overlapping string and check that results, payloads and pins cover both
+fn meta_merge() {
+    let first = MetaAutocompleter::new_with_payloads(
+        3,
+        [("soho", 1), ("solo", 2), ("zolo", 3)].map(|(s, payload)| (s.into(), payload)),
+    );
+    let mut second = MetaAutocompleter::new_with_payloads(
+        3,
+        [("solid", 4), ("solo", 5), ("throw", 6)].map(|(s, payload)| (s.into(), payload)),
+    );
+    assert!(second.pin("t", "throw"));
+    let merged = first.merge(second);
+    assert_eq!(
+        merged.iter_strings().collect::<Vec<_>>(),
+        vec!["soho", "solid", "solo", "throw", "zolo"]
+    );
+    assert_eq!(merged.payloads("solo"), &[2, 5]);
+    assert_eq!(merged.payloads("throw"), &[6]);
+
+    let mut cache = Cache::default();
+    let result = merged.autocomplete("solid", 1, &mut cache);
+    assert_eq!(result[0].string, "solid");
+    let result = merged.autocomplete("t", 1, &mut cache);
+    assert_eq!(result[0].string, "throw");
+}
+
+#[test]
+/// Reload the dataset in the background and check that queries see the new index with a fresh cache
+fn reload_swap() {
+    let strings = |source: &[&str]| source.iter().map(|&s| s.to_string()).collect::<Vec<_>>();
+    let autocompleter = Arc::new(ReloadableAutocompleter::new(strings(&[
+        "soho", "solo", "zolo",
+    ])));
+    let mut cache = ReloadCache::default();
+    let result = autocompleter.autocomplete("solv", 1, &mut cache);
+    assert_eq!(result[0].string, "solo");
+
+    let snapshot = autocompleter.load();
+    autocompleter
+        .reload(strings(&["solid", "solve", "throw"]))
+        .join()
+        .unwrap();
+    assert_eq!(autocompleter.generation(), 1);
+    let result = autocompleter.autocomplete("solv", 1, &mut cache);
+    assert_eq!(result[0].string, "solve");
+    // the old snapshot is still usable
+    assert!(snapshot.get().contains("zolo"));
+}
+
+#[test]
+/// Tests that the merged results of the shards match those of a single autocompleter
+fn sharded_matches_unsharded() {
+    let unsharded = solo_autocompleter();
+    let mut sharded = ShardedAutocompleter::new(3, SOLO_STRINGS.map(Into::into));
+    assert_eq!(sharded.len(), SOLO_STRINGS.len());
+    // assignments don't depend on the platform or the Rust release, so they can be persisted
+    let shards: Vec<usize> = SOLO_STRINGS
+        .iter()
+        .map(|string| sharded.shard_of(string))
+        .collect();
+    assert_eq!(shards, [1, 0, 1, 0, 1, 0, 1]);
+    let mut cache = ShardedCache::default();
+    for query in ["sol", "zoo", "thro", "s"] {
+        let expected = unsharded.autocomplete(query, 3, &mut Cache::default());
+        let result = sharded.autocomplete(query, 3, &mut cache);
+        assert_eq!(
+            result.iter().map(|m| m.prefix_distance).collect::<Vec<_>>(),
+            expected
+                .iter()
+                .map(|m| m.prefix_distance)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    let shard = sharded.shard_of("solar");
+    let mut strings: Vec<_> = sharded.shards()[shard]
+        .iter_strings()
+        .map(|s| s.to_string().into())
+        .collect();
+    strings.push("solar".into());
+    sharded.rebuild_shard(shard, strings);
+    assert_eq!(
+        sharded.autocomplete("solar", 1, &mut cache)[0].string,
+        "solar"
+    );
+}
+
+#[test]
+/// Tests that readers keep their generation while inserts and deletes are committed and compacted
+fn snapshot_generations() {
+    let strings = ["soho", "solo", "zolo"].map(String::from).to_vec();
+    let autocompleter = SnapshotAutocompleter::new(strings);
+    let mut cache = ReloadCache::default();
+    let old = autocompleter.snapshot();
+
+    autocompleter.insert("solve");
+    autocompleter.remove("solo");
+    // pending changes aren't visible until they're committed
+    assert!(autocompleter.snapshot().contains("solo"));
+    let new = autocompleter.commit();
+    assert_eq!(new.number(), 1);
+    assert!(!new.contains("solo"));
+    assert_eq!(new.len(), 3);
+    assert_eq!(new.autocomplete("sol", 1, &mut cache)[0].string, "solve");
+    assert_eq!(old.autocomplete("sol", 1, &mut cache)[0].string, "solo");
+
+    let compacted = autocompleter.compact();
+    assert_eq!(compacted.number(), 2);
+    let result = compacted.autocomplete("s", 3, &mut cache);
+    let result: Vec<_> = result.iter().map(|m| m.string.as_str()).collect();
+    assert_eq!(result, vec!["soho", "solve", "zolo"]);
+}
+
+#[test]
+/// Tests that duplicates are counted and can rank results by frequency
+fn meta_counted() {
+    let source = vec!["solid", "solo", "solve", "solo", "solve", "solo"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new_counted(cows.len(), cows.clone(), true);
+    assert_eq!(autocompleter.len(), 3);
+    let result = autocompleter.autocomplete_counted("sol", 3, &mut Cache::default());
+    let result: Vec<_> = result
+        .iter()
+        .map(|counted| (counted.measure.string.as_str(), counted.count))
+        .collect();
+    assert_eq!(result, vec![("solo", 3), ("solve", 2), ("solid", 1)]);
+
+    let unranked = MetaAutocompleter::new_counted(cows.len(), cows, false);
+    assert_eq!(
+        unranked.autocomplete("sol", 1, &mut Cache::default())[0].string,
+        "solid"
+    );
+    assert_eq!(unranked.count(unranked.position("solo").unwrap()), 3);
+}
+
+#[test]
+/// Tests that the options of the builder are applied to the stored strings and queries
+fn meta_builder() {
+    let source = vec!["Solid", "solo", "SOLO", "Solve", "zolo"];
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Unicode)
+        .normalization(|string| string.replace('z', "s"))
+        .dedup_policy(DedupPolicy::CountAsWeight)
+        .threshold(ThresholdPolicy::Fixed(1))
+        .precompute_top(2)
+        .build(source.iter().map(|&s| s.into()));
+    assert_eq!(
+        autocompleter.iter_strings().collect::<Vec<_>>(),
+        vec!["solid", "solo", "solve"]
+    );
+    assert!(autocompleter.contains("SOLVE"));
+    assert_eq!(
+        autocompleter.count(autocompleter.position("solo").unwrap()),
+        3
+    );
+    let result = autocompleter.autocomplete("SOL", 3, &mut Cache::default());
+    assert_eq!(result[0].string, "solo");
+    assert!(result.iter().all(|measure| measure.prefix_distance == 0));
+    assert_eq!(autocompleter.complete("Sol", 1)[0].string, "solo");
+    assert!(autocompleter
+        .autocomplete("xyz", 3, &mut Cache::default())
+        .is_empty());
+}
+
+#[cfg(all(feature = "serde", feature = "jsonl"))]
+#[test]
+/// Tests that the settings of the normalizer are serialized, and that custom normalization can't be
+fn serialized_normalizer() {
+    let source = vec!["Solid", "solo", "Solve", "e-mail"];
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Ascii)
+        .char_filter(CharFilter::Remove)
+        .build(source.iter().map(|&s| s.into()));
+    let json = serde_json::to_string(&autocompleter).unwrap();
+    let deserialized: MetaAutocompleter = serde_json::from_str(&json).unwrap();
+    for query in ["SOL", "Solv", "E MAIL"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert!(!expected.is_empty());
+        assert_eq!(
+            deserialized.autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+
+    let custom = MetaAutocompleterBuilder::new()
+        .normalization(|string| string.replace('z', "s"))
+        .build(source.iter().map(|&s| s.into()));
+    assert!(serde_json::to_string(&custom).is_err());
+}
+
+#[test]
+/// Tests that records keep their weights and payloads, and that lines are loaded as records
+fn meta_records() {
+    let records = [
+        ("solid", 1, 'a'),
+        ("solo", 2, 'b'),
+        ("solve", 3, 'c'),
+        ("solo", 2, 'd'),
+    ];
+    let autocompleter =
+        MetaAutocompleter::from_records(records.map(|(string, weight, payload)| Record {
+            string: string.into(),
+            weight,
+            payload,
+        }));
+    let result = autocompleter.autocomplete("sol", 3, &mut Cache::default());
+    let result: Vec<_> = result.iter().map(|m| m.string.as_str()).collect();
+    assert_eq!(result, vec!["solo", "solve", "solid"]);
+    assert_eq!(autocompleter.payloads("solo"), &['b', 'd']);
+
+    let records = load_lines("soho\n\nzolo\n".as_bytes()).unwrap();
+    assert_eq!(
+        records
+            .iter()
+            .map(|r| (&*r.string, r.payload))
+            .collect::<Vec<_>>(),
+        vec![("soho", 1), ("zolo", 3)]
+    );
+}
+
+#[cfg(all(feature = "csv", feature = "jsonl"))]
+#[test]
+/// Tests that CSV and JSONL records index the selected field with the selected weight
+fn meta_records_csv_jsonl() {
+    use crate::prefix::meta::{load_csv, load_jsonl};
+    let csv = "name,population\nsolo,20\nsolid,5\n";
+    let records = load_csv(csv.as_bytes(), "name", Some("population")).unwrap();
+    let autocompleter = MetaAutocompleter::from_records(records);
+    assert_eq!(
+        autocompleter.weight(autocompleter.position("solo").unwrap()),
+        20
+    );
+    assert_eq!(&autocompleter.payloads("solid")[0][1], "5");
+
+    let jsonl = "{\"name\": \"solve\", \"rank\": 3}\n\n{\"name\": \"zolo\"}\n";
+    let records = load_jsonl(jsonl.as_bytes(), "name", None).unwrap();
+    let autocompleter = MetaAutocompleter::from_records(records);
+    assert_eq!(autocompleter.payloads("solve")[0]["rank"], 3);
+    assert!(load_jsonl(jsonl.as_bytes(), "name", Some("rank")).is_err());
+}
+
+#[test]
+/// Tests that case is ignored when matching while results keep their original casing
+fn meta_case_insensitive() {
+    let source = vec!["Solid", "SOLO", "solo", "Straße", "Zolo"];
+    let cows: Vec<_> = source.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Unicode)
+        .keep_originals(true)
+        .build(cows.clone());
+    let result = autocompleter.autocomplete("sOLi", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "Solid");
+    assert_eq!(result[0].prefix_distance, 0);
+    assert_eq!(
+        autocompleter.autocomplete("solo", 1, &mut Cache::default())[0].string,
+        "SOLO"
+    );
+    assert_eq!(autocompleter.complete("STRA", 1)[0].string, "Straße");
+    // full case folding turns "ß" into "ss"
+    assert!(autocompleter.contains("STRASSE"));
+    let result = autocompleter.autocomplete("STRASSE", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "Straße");
+    assert_eq!(result[0].prefix_distance, 0);
+    let result = autocompleter.autocomplete_with_distances("zolo", 1, &mut Cache::default());
+    assert_eq!(result[0].distance, 0);
+    // matched spans cover the original characters that were folded into the matched prefix
+    let result = autocompleter.autocomplete_matched("STRAS", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "Straße");
+    assert_eq!(&result[0].measure.string[result[0].span.clone()], "Straß");
+    let result = autocompleter.autocomplete_matched("SOLI", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "Solid");
+    assert_eq!(&result[0].measure.string[result[0].span.clone()], "Soli");
+    let groups = autocompleter.autocomplete_grouped("SOL", 2, &mut Cache::default());
+    let prefixes: Vec<_> = groups.iter().map(|group| group.prefix.as_str()).collect();
+    assert_eq!(prefixes, vec!["soli", "solo"]);
+    assert_eq!(groups[0].results[0].string, "Solid");
+    assert_eq!(groups[1].results[0].string, "SOLO");
+
+    let ascii = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Ascii)
+        .build(["ÉCOLE".into()]);
+    assert!(ascii.contains("École"));
+}
+
+#[cfg(feature = "unicode")]
+#[test]
+/// Tests that strings in different normalization forms match exactly
+fn meta_unicode_normalization() {
+    use crate::prefix::meta::NormalizationForm;
+    // "é" as one character and as "e" with a combining accent
+    let source = vec!["caf\u{e9}", "cafe\u{301} noir", "\u{fb01}le"];
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .unicode_normalization(NormalizationForm::Nfkc)
+        .keep_originals(true)
+        .build(source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete("cafe\u{301}", 2, &mut Cache::default());
+    assert!(result.iter().all(|measure| measure.prefix_distance == 0));
+    assert_eq!(result[1].string, "cafe\u{301} noir");
+    assert!(autocompleter.contains("file"));
+    assert_eq!(
+        crate::prefix::meta::strip_accents("cafe\u{301} cr\u{e8}me"),
+        "cafe creme"
+    );
+}
+
+#[cfg(feature = "unicode-segmentation")]
+#[test]
+/// Tests that a grapheme cluster of several characters is a single edit
+fn meta_graphemes() {
+    use crate::levenshtein::{grapheme_edit_distance, grapheme_prefix_edit_distance};
+    // a family emoji joined from 4 emoji and 3 zero width joiners
+    let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{200d}\u{1f466}";
+    let stored = format!("{}park", family);
+    assert_eq!(grapheme_edit_distance("park", &stored), 1);
+    assert_eq!(
+        grapheme_prefix_edit_distance(&format!("x{}", family), &stored),
+        1
+    );
+    assert_eq!(edit_distance("park", &stored), 7);
+
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .graphemes(true)
+        .build([stored.as_str().into(), "parking".into()]);
+    let result = autocompleter.autocomplete(&format!("{}pa", family), 2, &mut Cache::default());
+    assert_eq!(result[0].string, stored);
+    assert_eq!(result[0].prefix_distance, 0);
+    // "x" replaces the whole cluster
+    let result = autocompleter.autocomplete("xpar", 1, &mut Cache::default());
+    assert_eq!(result[0].string, stored);
+    assert_eq!(result[0].prefix_distance, 1);
+}
+
+#[cfg(feature = "unicode-segmentation")]
+#[test]
+/// Tests that merged autocompleters encode the clusters of both with one table, and that too many clusters are an error
+fn meta_graphemes_merge() {
+    use crate::prefix::meta::GraphemeOverflow;
+    let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+    let accented = "e\u{301}";
+    let builder = MetaAutocompleterBuilder::new().graphemes(true);
+    let first = builder.build([format!("{}park", family).into(), "parking".into()]);
+    let second = builder.build([
+        format!("caf{}", accented).into(),
+        format!("{}{}", accented, family).into(),
+    ]);
+    let plain = MetaAutocompleter::new(1, [format!("{}trail", accented).into()]);
+    let merged = first.merge(second).merge(plain);
+    for (query, expected) in [
+        (format!("{}pa", family), format!("{}park", family)),
+        (format!("caf{}", accented), format!("caf{}", accented)),
+        (
+            format!("{}{}", accented, family),
+            format!("{}{}", accented, family),
+        ),
+        (format!("{}tr", accented), format!("{}trail", accented)),
+    ] {
+        let result = merged.autocomplete(&query, 1, &mut Cache::default());
+        assert_eq!(
+            (result[0].string.as_str(), result[0].prefix_distance),
+            (expected.as_str(), 0),
+            "{query}"
+        );
+    }
+    // the accented "e" is a single edit from "x"
+    let result = merged.autocomplete("xtrail", 1, &mut Cache::default());
+    assert_eq!(result[0].prefix_distance, 1);
+
+    // every base character with every combining mark is a distinct cluster
+    let clusters = (0x4E00..0x4E00 + 1200).flat_map(|base| {
+        (0x300..0x370)
+            .map(move |mark| [char::from_u32(base).unwrap(), char::from_u32(mark).unwrap()])
+    });
+    let strings: Vec<String> = clusters
+        .collect::<Vec<_>>()
+        .chunks(100)
+        .map(|chunk| chunk.iter().flatten().collect())
+        .collect();
+    assert_eq!(
+        builder
+            .try_build(strings.iter().map(|string| string.as_str().into()))
+            .err(),
+        Some(GraphemeOverflow)
+    );
+}
+
+#[test]
+/// Tests that an analyzer pipeline is applied to both stored strings and queries
+fn meta_analyzer() {
+    let analyzer = Pipeline::new()
+        .normalize(|string| string.replace('&', " and "))
+        .filter(not_punctuation)
+        .split_on(char::is_whitespace);
+    let autocompleter = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Unicode)
+        .analyzer(analyzer)
+        .keep_originals(true)
+        .build(["Rock & Roll!".into(), "St. Louis, MO".into()]);
+    assert!(autocompleter.contains("rock and roll"));
+    let result = autocompleter.autocomplete("rock  &  roll", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "Rock & Roll!");
+    assert_eq!(result[0].prefix_distance, 0);
+    let result = autocompleter.autocomplete("st louis", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "St. Louis, MO");
+    assert_eq!(result[0].prefix_distance, 0);
+}
+
+#[test]
+/// Tests that strings are found by their transliterations while results have the original strings
+fn keyed_transliteration() {
+    let pinyin: HashMap<&str, &str> = [
+        ("北京", "beijing"),
+        ("上海", "shanghai"),
+        ("南京", "nanjing"),
+    ]
+    .into();
+    let autocompleter = KeyedAutocompleter::new(pinyin.keys().map(|&s| s.into()), |string| {
+        vec![pinyin[string].to_string()]
+    });
+    let result = autocompleter.autocomplete_keyed("beij", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "北京");
+    assert_eq!(result[0].key, "beijing");
+    assert_eq!(result[0].measure.prefix_distance, 0);
+
+    let result = autocompleter.autocomplete_keyed("nanjnig", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "南京");
+    assert_eq!(result[0].measure.prefix_distance, 2);
+
+    let result = autocompleter.autocomplete_keyed("上", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "上海");
+    assert_eq!(result[0].key, "上海");
+}
+
+#[test]
+/// Tests that each string is measured by its closest key, even if a farther key of it is found first
+fn keyed_closest_key() {
+    let keys: Vec<(TreeString, Vec<TreeString>)> = vec![
+        (
+            "alpha".into(),
+            vec!["qwerty".into(), "qwertz".into(), "qwxrty".into()],
+        ),
+        ("beta".into(), vec!["qwerta".into()]),
+        ("gamma".into(), vec!["zwertz".into(), "qwert".into()]),
+    ];
+    let autocompleter = KeyedAutocompleter::from_keys(keys.clone());
+    for query in ["qwertz", "qwerty", "qwxr", "zwe", "gam"] {
+        for requested in 1..=3 {
+            let result = autocompleter.autocomplete_keyed(query, requested, &mut Cache::default());
+            assert_eq!(result.len(), requested, "{query}");
+            for keyed in &result {
+                let (string, alternates) = keys
+                    .iter()
+                    .find(|(string, _)| *string == keyed.measure.string)
+                    .unwrap();
+                let closest = std::iter::once(string)
+                    .chain(alternates)
+                    .map(|key| prefix_edit_distance(query, key))
+                    .min()
+                    .unwrap();
+                assert_eq!(
+                    keyed.measure.prefix_distance, closest,
+                    "{query} {requested}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+/// Tests that queries matching an alias return the canonical string
+fn keyed_aliases() {
+    let autocompleter = KeyedAutocompleter::with_aliases(
+        ["New York City".into(), "Los Angeles".into()],
+        [
+            ("NYC".into(), "New York City".into()),
+            ("Big Apple".into(), "New York City".into()),
+            ("LA".into(), "Los Angeles".into()),
+            ("SF".into(), "San Francisco".into()),
+        ],
+    );
+    let result = autocompleter.autocomplete_keyed("NYC", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "New York City");
+    assert_eq!(result[0].key, "NYC");
+    assert!(result[0].alias);
+
+    let result = autocompleter.autocomplete_keyed("New Y", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "New York City");
+    assert!(!result[0].alias);
+
+    // canonical strings only given by aliases are stored too
+    let result = autocompleter.autocomplete_keyed("San Fran", 1, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "San Francisco");
+    assert_eq!(result[0].measure.prefix_distance, 0);
+}
+
+#[test]
+/// Tests that separators don't use up the edit distance when they're filtered
+fn meta_char_filter() {
+    let source = ["e-mail", "foo  bar", "email client"];
+    let removed = MetaAutocompleterBuilder::new()
+        .char_filter(CharFilter::Remove)
+        .keep_originals(true)
+        .build(source.map(Into::into));
+    let result = removed.autocomplete("email", 1, &mut Cache::default());
+    assert_eq!(result[0].prefix_distance, 0);
+    let result = removed.autocomplete("e mail cl", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "email client");
+    assert_eq!(result[0].prefix_distance, 0);
+
+    let collapsed = MetaAutocompleterBuilder::new()
+        .char_filter(CharFilter::Collapse)
+        .keep_originals(true)
+        .build(source.map(Into::into));
+    let result = collapsed.autocomplete("foo-bar", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "foo  bar");
+    assert_eq!(result[0].prefix_distance, 0);
+    assert_eq!(CharFilter::Collapse.apply(" e-mail, "), "e mail");
+}
+
+#[test]
+/// Tests that code symbols are found by the words of their identifiers
+fn tokens_symbols() {
+    let source = [
+        "MetaAutocompleter::autocomplete",
+        "FstAutocompleter::new",
+        "strprox::levenshtein::prefix_edit_distance",
+        "HTTPServer",
+    ];
+    let autocompleter =
+        TokenAutocompleter::with_tokenizer(source.iter().map(|&s| s.into()), Tokenizer::Symbols);
+    let result = autocompleter.autocomplete_tokens("meta auto", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "MetaAutocompleter::autocomplete");
+    assert_eq!(result[0].prefix_distance, 0);
+
+    let result = autocompleter.autocomplete_tokens("strp edit", 1, &mut Cache::default());
+    assert_eq!(
+        result[0].string,
+        "strprox::levenshtein::prefix_edit_distance"
+    );
+    assert_eq!(result[0].prefix_distance, 0);
+
+    let result = autocompleter.autocomplete_tokens("server", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "HTTPServer");
+    assert_eq!(
+        Tokenizer::Symbols.tokenize(&"parseHTTPResponse_v2".into()),
+        ["parse", "http", "response", "v2"]
+    );
+}
+
+#[test]
+/// Tests that substitutions of adjacent keys cost less than other edits
+fn keyboard_costs() {
+    let qwerty = KeyboardCosts::new(KeyboardLayout::Qwerty);
+    assert!(KeyboardLayout::Qwerty.adjacent('s', 'd'));
+    assert!(KeyboardLayout::Qwerty.adjacent('S', 'w'));
+    assert!(KeyboardLayout::Qwerty.adjacent('s', 'z'));
+    assert!(!KeyboardLayout::Qwerty.adjacent('s', 'r'));
+    assert!(KeyboardLayout::Azerty.adjacent('a', 'z'));
+    assert_eq!(weighted_prefix_edit_distance("hrllo", "hello", &qwerty), 1);
+    assert_eq!(weighted_prefix_edit_distance("hpllo", "hello", &qwerty), 2);
+    assert_eq!(weighted_prefix_edit_distance("hllo", "hello", &qwerty), 2);
+
+    // "cst" is one edit from both, but "a" is next to "s"
+    let source = ["cut", "cat"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_weighted("cst", 1, &qwerty, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "cat");
+    assert_eq!(result[0].cost, 1);
+
+    // "sat" needs three cheap edits to cost less than "dpp", which is searched first, so "sat" is only found by the exact search
+    let source = ["sat", "dpp"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_weighted("dsy", 1, &qwerty, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "dpp");
+    assert_eq!(result[0].cost, 4);
+    let result =
+        autocompleter.autocomplete_weighted_exact("dsy", 1, &qwerty, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "sat");
+    assert_eq!(result[0].measure.prefix_distance, 2);
+    assert_eq!(result[0].cost, 3);
+}
+
+#[test]
+/// Tests that results are ranked by the costs of each kind of edit
+fn operation_costs() {
+    let unit = OperationCosts::default();
+    assert_eq!(
+        weighted_edit_distance("kitten", "sitting", &unit),
+        edit_distance("kitten", "sitting")
+    );
+    assert_eq!(
+        weighted_prefix_edit_distance("kitten", "sitting", &unit),
+        prefix_edit_distance("kitten", "sitting")
+    );
+
+    // dropped characters are cheap, but extra ones aren't
+    let costs = OperationCosts {
+        insert: 1,
+        delete: 4,
+        substitute: 3,
+        case_substitute: 1,
+    };
+    assert_eq!(weighted_edit_distance("hllo", "hello", &costs), 1);
+    assert_eq!(weighted_edit_distance("heello", "hello", &costs), 4);
+    assert_eq!(weighted_edit_distance("Hello", "hello", &costs), 1);
+    assert_eq!(costs.min_cost(), 1);
+
+    let source = ["bell", "hello"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_weighted_exact("hll", 1, &costs, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "hello");
+    assert_eq!(result[0].cost, 1);
+}
+
+#[test]
+/// Tests that characters and sequences confused by OCR are cheap to substitute
+fn confusion_costs() {
+    let ocr = ConfusionCosts::ocr();
+    assert_eq!(weighted_edit_distance("rnodern", "modern", &ocr), 2);
+    assert_eq!(weighted_edit_distance("C0RNER", "CORNER", &ocr), 2);
+    assert_eq!(weighted_edit_distance("Hel1o", "Hello", &ocr), 2);
+    assert_eq!(weighted_edit_distance("Hexlo", "Hello", &ocr), 4);
+    assert_eq!(weighted_prefix_edit_distance("vvor", "world", &ocr), 2);
+    assert_eq!(ocr.min_cost(), 1);
+
+    // "rodent" has fewer edits, but they aren't confusions
+    let source = ["modern", "rodent"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_weighted_exact("rnod", 1, &ocr, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "modern");
+    assert_eq!(result[0].cost, 2);
+
+    let costs = ConfusionCosts::new(3).confuse("ph", "f", 1);
+    assert_eq!(weighted_edit_distance("fone", "phone", &costs), 1);
+    assert_eq!(costs.min_cost(), 0);
+}
+
+#[test]
+/// Tests edit distances between sequences of words and bytes
+fn slice_distances() {
+    let query = ["new", "york"];
+    let stored = ["new", "york", "city"];
+    assert_eq!(slice_prefix_edit_distance(&query, &stored), 0);
+    assert_eq!(slice_edit_distance(&query, &stored), 1);
+    assert_eq!(slice_substring_edit_distance(&["york", "city"], &stored), 0);
+    assert_eq!(slice_edit_distance(&["old", "york"], &stored[..2]), 1);
+
+    assert_eq!(slice_edit_distance(b"kitten", b"sitting"), 3);
+    assert_eq!(slice_prefix_edit_distance(b"", b"abc"), 0);
+    assert_eq!(slice_prefix_edit_distance(b"abc", b""), 3);
+    assert_eq!(slice_edit_distance::<u8>(&[], &[]), 0);
+    // characters give the same distances as strings
+    let first: Vec<char> = "résumé".chars().collect();
+    let second: Vec<char> = "resume".chars().collect();
+    assert_eq!(
+        slice_edit_distance(&first, &second),
+        edit_distance("résumé", "resume")
+    );
+}
+
+#[test]
+/// Tests that the banded distances agree with the full distances up to the threshold
+fn distances_within() {
+    let strings = [
+        "",
+        "a",
+        "abc",
+        "kitten",
+        "sitting",
+        "mitten",
+        "kit",
+        "ktitne",
+        "sitten on",
+    ];
+    for first in strings {
+        for second in strings {
+            let distance = edit_distance(first, second);
+            let prefix_distance = prefix_edit_distance(first, second);
+            for k in 0..6 {
+                assert_eq!(
+                    distance_within(first, second, k),
+                    (distance <= k).then_some(distance),
+                    "{first} {second} {k}"
+                );
+                assert_eq!(
+                    prefix_distance_within(first, second, k),
+                    (prefix_distance <= k).then_some(prefix_distance),
+                    "{first} {second} {k}"
+                );
+            }
+            // thresholds larger than the strings, like an unlimited one, are the same as the full distances
+            assert_eq!(distance_within(first, second, usize::MAX), Some(distance));
+            assert_eq!(
+                prefix_distance_within(first, second, usize::MAX),
+                Some(prefix_distance)
+            );
+        }
+    }
+}
+
+#[test]
+/// Tests that reusing buffers gives the same distances as allocating them
+fn scratch_distances() {
+    let strings = ["", "a", "kitten", "sitting", "ß", "größer", "grosser"];
+    let mut scratch = Scratch::new();
+    for first in strings {
+        for second in strings {
+            assert_eq!(
+                distance_with(&mut scratch, first, second),
+                edit_distance(first, second)
+            );
+            assert_eq!(
+                prefix_distance_with(&mut scratch, first, second),
+                prefix_edit_distance(first, second)
+            );
+        }
+    }
+}
+
+#[test]
+/// Tests that the end of the best matching prefix splits the completion from the matched part
+fn prefix_end() {
+    assert_eq!(prefix_edit_distance_with_end("helo", "hello world"), (1, 3));
+    assert_eq!(prefix_edit_distance_with_end("", "hello"), (0, 0));
+    assert_eq!(prefix_edit_distance_with_end("hello", ""), (5, 0));
+    assert_eq!(prefix_edit_distance_with_end("abcdef", "abc"), (3, 3));
+    let (distance, end) = prefix_edit_distance_with_end("straße", "strasse nord");
+    assert_eq!(distance, 2);
+    assert_eq!(&"strasse nord"[..end], "stra");
+    assert_eq!(&"strasse nord"[end..], "sse nord");
+    for (first, second) in [("kitten", "sitting"), ("über", "überall"), ("x", "abc")] {
+        assert_eq!(
+            prefix_edit_distance_with_end(first, second).0,
+            prefix_edit_distance(first, second)
+        );
+    }
+}
+
+#[test]
+/// Tests that similarities are normalized by the lengths of the strings
+fn similarities() {
+    assert_eq!(similarity("", ""), 1.0);
+    assert_eq!(similarity("abcd", "abcd"), 1.0);
+    assert_eq!(similarity("abcd", "abce"), 0.75);
+    assert_eq!(similarity("ab", "abcd"), 0.5);
+    assert_eq!(similarity("abc", "xyz"), 0.0);
+    assert_eq!(prefix_similarity("ab", "abcd"), 1.0);
+    assert_eq!(prefix_similarity("abxd", "abcdef"), 0.75);
+    assert_eq!(prefix_similarity("", "abc"), 1.0);
+    assert_eq!(prefix_similarity("xyz", ""), 0.0);
+
+    let source = ["apple", "applesauce"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_scored("appl", 2, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "apple");
+    assert_eq!(result[0].prefix_similarity, 1.0);
+    assert_eq!(result[0].similarity, 0.8);
+    assert_eq!(result[1].similarity, 0.4);
+}
+
+#[test]
+/// Tests the Jaro and Jaro-Winkler similarities and ranking by them
+fn jaro_winkler_similarity() {
+    let close = |a: f64, b: f64| (a - b).abs() < 0.001;
+    assert!(close(jaro("MARTHA", "MARHTA"), 0.944));
+    assert!(close(jaro_winkler("MARTHA", "MARHTA"), 0.961));
+    assert!(close(jaro("DIXON", "DICKSONX"), 0.767));
+    assert!(close(jaro_winkler("DIXON", "DICKSONX"), 0.813));
+    assert_eq!(jaro("", ""), 1.0);
+    assert_eq!(jaro("abc", ""), 0.0);
+    assert_eq!(jaro_winkler("abc", "xyz"), 0.0);
+    assert_eq!(jaro_winkler("same", "same"), 1.0);
+
+    // both are one edit from the query, but "katie" has more in common with it
+    let source = ["kath", "katie"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete("kate", 2, &mut Cache::default());
+    assert_eq!(result[0].string, "kath");
+    let options = QueryOptions {
+        jaro_winkler: JaroWinklerRanking::Tiebreaker,
+        ..Default::default()
+    };
+    let result = autocompleter.autocomplete_with_options(
+        "kate",
+        2,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    assert_eq!(result[0].string, "katie");
+
+    // "marhta" is closer by Jaro-Winkler, but the other string has the query as a prefix
+    let source = ["marhta", "marthas vineyard island"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete("martha", 2, &mut Cache::default());
+    assert_eq!(result[0].string, "marthas vineyard island");
+    let options = QueryOptions {
+        jaro_winkler: JaroWinklerRanking::Primary,
+        ..Default::default()
+    };
+    let result = autocompleter.autocomplete_with_options(
+        "martha",
+        2,
+        |_| true,
+        &options,
+        &mut Cache::default(),
+    );
+    assert_eq!(result[0].string, "marhta");
+}
+
+#[test]
+/// Tests the longest common subsequence and the similarity from it
+fn longest_common_subsequence() {
+    assert_eq!(lcs_length("ABCBDAB", "BDCABA"), 4);
+    assert_eq!(lcs_length("", "abc"), 0);
+    assert_eq!(lcs_length("abc", "abc"), 3);
+    assert_eq!(lcs_length("größe", "große"), 4);
+    assert_eq!(slice_lcs_length(&["a", "b", "c"], &["b", "c", "d"]), 2);
+    assert_eq!(lcs_similarity("", ""), 1.0);
+    assert_eq!(lcs_similarity("abcd", "abcd"), 1.0);
+    assert_eq!(lcs_similarity("abc", "xyz"), 0.0);
+    // one substitution lowers it more than one deletion, though both are one edit
+    assert_eq!(lcs_similarity("abcd", "abxd"), 0.75);
+    assert!(lcs_similarity("abcd", "abd") > 0.75);
+}
+
+#[test]
+/// Tests q-gram profiles, similarities and filtering
+fn qgram_similarities() {
+    let profile = qgrams("banana", 2);
+    assert_eq!(profile.len(), 3);
+    assert_eq!(profile["an"], 2);
+    assert_eq!(profile["na"], 2);
+    assert_eq!(profile["ba"], 1);
+    assert_eq!(qgrams("ä€ö", 2).len(), 2);
+    assert!(qgrams("a", 2).is_empty());
+
+    assert_eq!(qgram_jaccard("night", "night", 2), 1.0);
+    assert_eq!(qgram_jaccard("night", "nacht", 2), 1.0 / 7.0);
+    assert_eq!(qgram_jaccard("a", "b", 2), 1.0);
+    assert!((qgram_cosine("banana", "banana", 2) - 1.0).abs() < 1e-9);
+    assert_eq!(qgram_cosine("abc", "xyz", 2), 0.0);
+    assert_eq!(qgram_cosine("abc", "x", 2), 0.0);
+
+    // the filter never discards a string within the distance
+    let strings = [
+        "kitten", "sitting", "mitten", "kitchen", "bitten", "k", "", "smitten",
+    ];
+    for query in strings {
+        for q in 1..4 {
+            let filter = QGramFilter::new(query, q);
+            for string in strings {
+                for k in 0..4 {
+                    if edit_distance(query, string) <= k {
+                        assert!(filter.may_be_within(string, k), "{query} {string} {q} {k}");
+                    }
+                }
+            }
+        }
+    }
+    assert!(!QGramFilter::new("kitten", 2).may_be_within("zzzzzz", 1));
+
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    let result = unindexed_correct("kiten", 1, 10, &cows, Some(2));
+    assert_eq!(result, unindexed_correct("kiten", 1, 10, &cows, None));
+    let result_strings: Vec<&str> = result
+        .iter()
+        .map(|measure| measure.string.as_str())
+        .collect();
+    assert_eq!(result_strings, ["kitten"]);
+}
+
+#[test]
+/// Tests the Hamming distance and that the fast path agrees with the edit distance
+fn hamming_distance() {
+    assert_eq!(hamming("karolin", "kathrin"), Some(3));
+    assert_eq!(hamming("", ""), Some(0));
+    assert_eq!(hamming("ab", "abc"), None);
+    assert_eq!(hamming("ñu", "nu"), Some(1));
+    assert_eq!(slice_hamming(b"1011101", b"1001001"), Some(2));
+
+    let codes = [
+        "AB-1234", "AB-1235", "AB-2134", "BA-1234", "AB-12345", "AB1234",
+    ];
+    for first in codes {
+        for second in codes {
+            let distance = edit_distance(first, second);
+            for k in 0..4 {
+                assert_eq!(
+                    distance_within(first, second, k),
+                    (distance <= k).then_some(distance)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+/// Tests that strings that sound like the query are found beyond the threshold
+fn phonetic_soundex() {
+    assert_eq!(soundex("Robert"), "R163");
+    assert_eq!(soundex("Rupert"), "R163");
+    assert_eq!(soundex("Ashcraft"), "A261");
+    assert_eq!(soundex("Tymczak"), "T522");
+    assert_eq!(soundex("Pfister"), "P236");
+    assert_eq!(soundex("Lee"), "L000");
+    assert_eq!(soundex("123"), "");
+
+    let source = ["Smith", "Smithers", "Jones"];
+    let mut autocompleter = PhoneticAutocompleter::new(source.iter().map(|&s| s.into()), soundex);
+    autocompleter.set_threshold(Some(ThresholdPolicy::Fixed(1)));
+    let result = autocompleter.autocomplete_phonetic("smyth", 3, &mut Cache::default());
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].measure.string, "Smith");
+    assert_eq!(result[0].measure.prefix_distance, 2);
+    assert!(result[0].phonetic);
+
+    let result = autocompleter.autocomplete_phonetic("Smit", 3, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "Smith");
+    assert!(!result[0].phonetic);
+
+    // phonetic hits are measured against the normalized query, but have the original spelling
+    let mut autocompleter = PhoneticAutocompleter::from_autocompleter(
+        MetaAutocompleterBuilder::new()
+            .case_folding(CaseFolding::Ascii)
+            .keep_originals(true)
+            .build(source.iter().map(|&s| s.into())),
+        soundex,
+    );
+    autocompleter.set_threshold(Some(ThresholdPolicy::Fixed(0)));
+    let result = autocompleter.autocomplete_phonetic("SMYTH", 3, &mut Cache::default());
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].measure.string, "Smith");
+    assert_eq!(result[0].measure.prefix_distance, 1);
+    assert!(result[0].phonetic);
+    let result = autocompleter.autocomplete_phonetic("SMITH", 3, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "Smith");
+    assert!(!result[0].phonetic);
+}
+
+#[test]
+/// Tests the Double Metaphone codes and finding strings with them
+fn phonetic_double_metaphone() {
+    for (word, primary, alternate) in [
+        ("Smith", "SM0", "XM0"),
+        ("Schmidt", "XMT", "SMT"),
+        ("Xavier", "SF", "SFR"),
+        ("Jose", "HS", "HS"),
+        ("Michael", "MKL", "MXL"),
+        ("Caesar", "SSR", "SSR"),
+        ("Knight", "NT", "NT"),
+        ("Arnow", "ARN", "ARNF"),
+        ("", "", ""),
+    ] {
+        assert_eq!(
+            double_metaphone(word),
+            (primary.to_string(), alternate.to_string()),
+            "{word}"
+        );
+    }
+    // codes are cut at 4 characters
+    assert_eq!(double_metaphone_primary("Washington").len(), 4);
+
+    let source = ["Smith", "Schneider", "Jones"];
+    let mut autocompleter = PhoneticAutocompleter::from_autocompleter(
+        MetaAutocompleterBuilder::new()
+            .case_folding(CaseFolding::Ascii)
+            .keep_originals(true)
+            .build(source.iter().map(|&s| s.into())),
+        double_metaphone_primary,
+    );
+    autocompleter.set_threshold(Some(ThresholdPolicy::Fixed(0)));
+    let result = autocompleter.autocomplete_phonetic("smyth", 3, &mut Cache::default());
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].measure.string, "Smith");
+    assert!(result[0].phonetic);
+    // "Snider" and "Schneider" only share the alternate code, so the primary code doesn't match them
+    assert!(autocompleter
+        .autocomplete_phonetic("snider", 3, &mut Cache::default())
+        .is_empty());
+}
+
+#[test]
+/// Tests ranking results by other metrics than the prefix edit distance
+fn metric_ranking() {
+    assert_eq!(damerau_edit_distance("ca", "ac"), 1);
+    assert_eq!(edit_distance("ca", "ac"), 2);
+    assert_eq!(damerau_edit_distance("ca", "abc"), 3);
+    assert_eq!(unrestricted_damerau_edit_distance("ca", "abc"), 2);
+    assert_eq!(unrestricted_damerau_edit_distance("ca", "ac"), 1);
+    assert_eq!(unrestricted_damerau_edit_distance("", "abc"), 3);
+    assert_eq!(unrestricted_damerau_edit_distance("abcdef", "badcfe"), 3);
+    for (first, second) in [
+        ("kitten", "sitting"),
+        ("abcdef", "badcfe"),
+        ("", ""),
+        ("résumé", "ésruém"),
+    ] {
+        assert!(
+            unrestricted_damerau_edit_distance(first, second)
+                <= damerau_edit_distance(first, second)
+        );
+        assert_eq!(
+            unrestricted_damerau_edit_distance(first, second),
+            unrestricted_damerau_edit_distance(second, first)
+        );
+    }
+    assert_eq!(damerau_prefix_edit_distance("teh", "the quick"), 1);
+    assert_eq!(damerau_prefix_edit_distance("", "abc"), 0);
+    assert_eq!(PrefixEditDistance.distance("hte", "the"), 2.0);
+
+    // both are two edits from the query, but "the" is one swap
+    let source = ["hxx", "the"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete("hte", 2, &mut Cache::default());
+    assert_eq!(result[0].string, "hxx");
+    let result = autocompleter.autocomplete_with_metric(
+        "hte",
+        2,
+        &DamerauPrefixEditDistance,
+        &mut Cache::default(),
+    );
+    assert_eq!(result[0].measure.string, "the");
+    assert_eq!(result[0].distance, 1.0);
+
+    let source = ["tex", "the"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+
+    let result = autocompleter.autocomplete_with_metric(
+        "tez",
+        2,
+        &WeightedPrefixEditDistance(KeyboardCosts::new(KeyboardLayout::Qwerty)),
+        &mut Cache::default(),
+    );
+    assert_eq!(result[0].measure.string, "tex");
+    let result = autocompleter.autocomplete_with_metric(
+        "th",
+        2,
+        &JaroWinklerDistance,
+        &mut Cache::default(),
+    );
+    assert_eq!(result[0].measure.string, "the");
+
+    // closures are metrics, such as to prefer shorter strings
+    let shortest = |_: &str, string: &str| string.len() as f64;
+    let source = ["abcdef", "abc"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_with_metric("ab", 2, &shortest, &mut Cache::default());
+    assert_eq!(result[0].measure.string, "abc");
+}
+
+#[test]
+/// Tests that subsequence matches prefer word boundaries and consecutive characters
+fn subsequence_matching() {
+    let scoring = SubsequenceScoring::default();
+    assert_eq!(scoring.score("abc", "axbxc").unwrap().1, [0, 2, 4]);
+    assert!(scoring.score("abc", "acb").is_none());
+    assert_eq!(scoring.score("", "abc"), Some((0, vec![])));
+    // the "m" and "a" at the starts of words score more than the earlier ones
+    assert_eq!(scoring.score("ma", "format main").unwrap().1, [7, 8]);
+    assert_eq!(scoring.score("fb", "foo_bar").unwrap().1, [0, 4]);
+    assert_eq!(scoring.score("fb", "fooBar").unwrap().1, [0, 3]);
+    assert!(scoring.score("FB", "foobar").is_none());
+    assert_eq!(scoring.score("é", "café").unwrap().1, [3]);
+
+    let source = [
+        "src/strprox/prefix/meta/mod.rs",
+        "src/strprox/prefix/mod.rs",
+        "src/tests/mod.rs",
+        "Cargo.toml",
+    ];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let result = autocompleter.autocomplete_subsequence("pmm", 2, &scoring);
+    // the other paths have one "m" at most
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].measure.string, "src/strprox/prefix/meta/mod.rs");
+    let result = autocompleter.autocomplete_subsequence("tests", 4, &scoring);
+    assert_eq!(result[0].measure.string, "src/tests/mod.rs");
+    assert!(result
+        .iter()
+        .all(|result| result.measure.string != "Cargo.toml"));
+}
+
+#[test]
+/// Tests that hybrid ranking finds both typos and abbreviations, weighted as configured
+fn hybrid_ranking() {
+    let source = ["format_main", "formal", "fmt", "marmot"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let scoring = SubsequenceScoring::default();
+    let mut cache = Cache::default();
+    let strings = |weights: &HybridWeights, cache: &mut Cache| -> Vec<String> {
+        autocompleter
+            .autocomplete_hybrid("fmn", 4, weights, &scoring, cache)
+            .into_iter()
+            .map(|result| result.measure.string)
+            .collect()
+    };
+    let edit_only = HybridWeights {
+        edit: 1.0,
+        subsequence: 0.0,
+    };
+    // "fmt" has the query with one edit, and the others need two
+    assert_eq!(strings(&edit_only, &mut cache)[0], "fmt");
+    let subsequence_only = HybridWeights {
+        edit: 0.0,
+        subsequence: 1.0,
+    };
+    // only "format_main" has "f", "m" and "n" in order
+    assert_eq!(strings(&subsequence_only, &mut cache)[0], "format_main");
+    let result =
+        autocompleter.autocomplete_hybrid("fmn", 4, &Default::default(), &scoring, &mut cache);
+    assert_eq!(result[0].measure.string, "format_main");
+    assert_eq!(
+        result[0].subsequence_score,
+        scoring.score("fmn", "format_main").map(|(score, _)| score)
+    );
+    assert!(result[1..]
+        .iter()
+        .all(|result| result.subsequence_score.is_none()));
+}
+
+#[test]
+/// Tests that digits match the letters of their keys, with typos on top
+fn keypad_queries() {
+    assert_eq!(keypad_digit('S'), Some('7'));
+    assert_eq!(keypad_digit('z'), Some('9'));
+    assert_eq!(keypad_digit('1'), None);
+
+    let source = ["home", "good", "gone", "hood", "in", "2nd"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let strings = |query: &str, max_edits: usize| -> Vec<String> {
+        autocompleter
+            .autocomplete_keypad(query, 10, max_edits)
+            .into_iter()
+            .map(|result| result.string)
+            .collect()
+    };
+    // "4663" is the same keys for all four words
+    assert_eq!(strings("4663", 0), ["gone", "good", "home", "hood"]);
+    assert_eq!(strings("466", 0), ["gone", "good", "home", "hood"]);
+    assert_eq!(strings("46", 0), ["gone", "good", "home", "hood", "in"]);
+    assert_eq!(strings("2n", 0), ["2nd"]);
+    // the 5 is a typo of the 6 of "hood"
+    assert_eq!(strings("4563", 0), Vec::<String>::new());
+    let result = autocompleter.autocomplete_keypad("4563", 10, 1);
+    assert!(result.iter().all(|result| result.prefix_distance == 1));
+    assert_eq!(result.len(), 4);
+}
+
+#[test]
+/// Tests that substituting low-confidence characters of the query is cheaper
+fn confidence_queries() {
+    assert_eq!(
+        confidence_prefix_edit_distance(&[('c', 1.0), ('a', 1.0), ('t', 0.25)], "cab"),
+        0.25
+    );
+    assert_eq!(
+        confidence_prefix_edit_distance(&[('c', 1.0), ('a', 1.0), ('t', 1.0)], "cab"),
+        1.0
+    );
+    assert_eq!(
+        confidence_prefix_edit_distance(&[('c', 1.0), ('a', 0.0)], "co"),
+        0.0
+    );
+    assert_eq!(
+        confidence_prefix_edit_distance(&[('c', 0.0), ('a', 1.0)], "ca"),
+        0.0
+    );
+    // deleting still costs 1
+    assert_eq!(confidence_prefix_edit_distance(&[('x', 0.0)], ""), 1.0);
+
+    let source = ["cab", "cat", "cot"];
+    let autocompleter = MetaAutocompleter::new(source.len(), source.iter().map(|&s| s.into()));
+    let mut cache = Cache::default();
+    // "cut" is a substitution from both "cat" and "cot", but the "u" might have been an "o"
+    let result =
+        autocompleter.autocomplete_confident(&[('c', 1.0), ('u', 0.5), ('t', 1.0)], 2, &mut cache);
+    let strings: Vec<&str> = result
+        .iter()
+        .map(|result| result.measure.string.as_str())
+        .collect();
+    assert_eq!(strings, ["cat", "cot"]);
+    assert!(result.iter().all(|result| result.cost == 0.5));
+    // "cab" substitutes the confident "t" too
+    let result =
+        autocompleter.autocomplete_confident(&[('c', 1.0), ('u', 0.5), ('t', 0.9)], 3, &mut cache);
+    assert_eq!(result[2].measure.string, "cab");
+    assert!((result[2].cost - 1.4).abs() < 1e-6);
+}
+
+#[test]
+/// Tests that the HS-tree finds the same strings as a scan over all of them
+fn hs_tree_query() {
+    let strings = [
+        "kitten",
+        "sitting",
+        "mitten",
+        "kitchen",
+        "smitten",
+        "bitten",
+        "knitting",
+        "written",
+        "kit",
+        "",
+        "a",
+        "abcdefghij",
+        "abcdefghik",
+        "bacdefghij",
+        "abcdeghij",
+        "café",
+        "cafe",
+        "naïve",
+        "日本語の文字列",
+        "日本語文字列",
+    ];
+    let tree = HSTree::new(strings);
+    let btree_tree = HSTree::<BTreeMapGAT>::with_map(strings);
+    assert_eq!(tree.len(), strings.len());
+    let result = tree.query("kitten", 1);
+    let result_strings: Vec<&str> = result
+        .iter()
+        .map(|measure| measure.string.as_str())
+        .collect();
+    assert_eq!(result_strings, ["kitten", "bitten", "mitten"]);
+    assert_eq!(tree.query("", 1).len(), 2);
+
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    for query in [
+        "kitten",
+        "sittin",
+        "abcdefghij",
+        "xbcdefghiy",
+        "ab",
+        "knitting",
+        "kithen",
+        "cafè",
+        "naive",
+        "日本語の文字",
+    ] {
+        for threshold in 0..5 {
+            assert_eq!(
+                tree.query(query, threshold),
+                unindexed_correct(query, threshold, usize::MAX, &cows, None),
+                "{query} {threshold}"
+            );
+            assert_eq!(
+                btree_tree.query(query, threshold),
+                tree.query(query, threshold)
+            );
+        }
+    }
+}
+
+#[test]
+/// Tests that the HS-tree verifies its candidates by a metric and ranks them by it
+fn hs_tree_metric() {
+    let tree = HSTree::new(["kitten", "iktten", "mitten", "sitting"]);
+    let result = tree.query_with_metric("kitten", 2, &DamerauEditDistance);
+    let pairs: Vec<(&str, f64)> = result
+        .iter()
+        .map(|ranked| (ranked.string.as_str(), ranked.distance))
+        .collect();
+    // the transposition is one edit by the metric, so "iktten" comes before "mitten"
+    assert_eq!(pairs, [("kitten", 0.0), ("iktten", 1.0), ("mitten", 1.0)]);
+    let result = tree.query_with_metric("kitten", 1, &DamerauEditDistance);
+    assert!(result.iter().all(|ranked| ranked.distance <= 1.0));
+    let result = tree.query_with_metric("kitten", 3, &|_: &str, string: &str| {
+        string.len() as f64 / 2.0
+    });
+    assert_eq!(result[0].string, "iktten");
+    assert_eq!(result.len(), 3);
+}
+
+#[test]
+/// Tests that strings can be removed from and inserted into the HS-tree
+fn hs_tree_updates() {
+    let mut tree = HSTree::new(["kitten", "mitten", "kitten", "café"]);
+    assert!(tree.remove("kitten"));
+    assert!(!tree.remove("bitten"));
+    assert_eq!(tree.len(), 3);
+    let strings = |tree: &HSTree, query| -> Vec<String> {
+        tree.query(query, 1)
+            .into_iter()
+            .map(|measure| measure.string)
+            .collect()
+    };
+    // one copy is left
+    assert_eq!(strings(&tree, "kitten"), ["kitten", "mitten"]);
+    assert!(tree.remove("kitten"));
+    assert_eq!(strings(&tree, "kitten"), ["mitten"]);
+    tree.insert("bitten");
+    assert_eq!(strings(&tree, "kitten"), ["bitten", "mitten"]);
+    assert!(tree.remove("café"));
+    assert!(tree.query("cafe", 1).is_empty());
+    assert!(tree.remove("mitten") && tree.remove("bitten"));
+    assert!(tree.is_empty());
+    assert!(tree.topk("kitten", 1).is_empty());
+}
+
+#[test]
+/// Tests that the HS-tree finds the closest strings with a threshold that's relaxed as needed
+fn hs_tree_topk() {
+    let strings = [
+        "kitten", "sitting", "mitten", "kitchen", "smitten", "bitten", "knitting", "a",
+    ];
+    let tree = HSTree::new(strings);
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    for query in ["kitten", "sittin", "xyz", ""] {
+        for requested in [0, 1, 3, 8, 20] {
+            assert_eq!(
+                tree.topk(query, requested),
+                unindexed_correct(query, 100, requested, &cows, None),
+                "{query} {requested}"
+            );
+        }
+    }
+    assert!(HSTree::new([]).topk("kitten", 1).is_empty());
+}
+
+#[cfg(all(feature = "serde", feature = "jsonl"))]
+#[test]
+/// Tests that a deserialized HS-tree borrows its strings from the input and answers queries like the original
+fn hs_tree_serde() {
+    let strings = ["kitten", "sitting", "mitten", "kitchen", "café", "a"];
+    let tree = HSTree::new(strings);
+    let json = serde_json::to_string(&tree).unwrap();
+    let deserialized: HSTree = serde_json::from_str(&json).unwrap();
+    let btree = HSTree::<BTreeMapGAT>::with_map(strings);
+    let btree_json = serde_json::to_string(&btree).unwrap();
+    let btree_deserialized: HSTree<BTreeMapGAT> = serde_json::from_str(&btree_json).unwrap();
+    for query in ["kitten", "sittin", "cafe", "xyz"] {
+        for threshold in 0..=2 {
+            assert_eq!(
+                deserialized.query(query, threshold),
+                tree.query(query, threshold),
+                "{query} {threshold}"
+            );
+            assert_eq!(
+                btree_deserialized.query(query, threshold),
+                btree.query(query, threshold),
+                "{query} {threshold}"
+            );
+        }
+    }
+    // escaped strings can't be borrowed from JSON
+    let escaped = HSTree::new(["tab\tstop"]);
+    let json = serde_json::to_string(&escaped).unwrap();
+    assert!(serde_json::from_str::<HSTree>(&json).is_err());
+}
+
+#[test]
+/// Tests that similarity joins find the same pairs as comparing every pair
+fn hs_tree_joins() {
+    let left = ["kitten", "sitting", "abcdefgh", "ab", ""];
+    let right = ["mitten", "sitting", "kitchen", "abcdefhg", "b", "kitten"];
+    // thresholds past the longest string join every pair instead of overflowing
+    for threshold in [0, 1, 2, 3, usize::MAX] {
+        let mut expected = Vec::new();
+        for (i, first) in left.iter().enumerate() {
+            for (j, second) in right.iter().enumerate() {
+                let distance = edit_distance(first, second);
+                if distance <= threshold {
+                    expected.push((i, j, distance));
+                }
+            }
+        }
+        let pairs: Vec<(usize, usize, usize)> = similarity_join(&left, &right, threshold)
+            .into_iter()
+            .map(|pair| (pair.left, pair.right, pair.distance))
+            .collect();
+        assert_eq!(pairs, expected, "{threshold}");
+    }
+    let pairs = similarity_self_join(&right, 1);
+    assert_eq!(pairs.len(), 1);
+    assert_eq!((pairs[0].left, pairs[0].right), (0, 5));
+}
+
+#[test]
+/// Tests that near-duplicates are clustered transitively or mutually
+fn hs_tree_clusters() {
+    let strings = [
+        "color", "colour", "colours", "flavor", "flavour", "unique", "colors",
+    ];
+    let clusters = |linkage| -> Vec<(usize, Vec<usize>)> {
+        dedup_clusters(&strings, 1, linkage)
+            .into_iter()
+            .map(|cluster| (cluster.representative, cluster.members))
+            .collect()
+    };
+    // each spelling of "color" is similar to two others, so the first of them represents the cluster
+    assert_eq!(
+        clusters(Linkage::Transitive),
+        [(0, vec![0, 1, 2, 6]), (3, vec![3, 4]), (5, vec![5])]
+    );
+    // "colours" is two edits from "color"
+    assert_eq!(
+        clusters(Linkage::Mutual),
+        [
+            (0, vec![0, 1]),
+            (2, vec![2, 6]),
+            (3, vec![3, 4]),
+            (5, vec![5])
+        ]
+    );
+}
+
+#[test]
+/// Tests that searchers give the same results through the trait as their own methods, whichever backend is chosen
+fn searcher_backends() {
+    let strings = [
+        "kitten", "sitting", "mitten", "kitchen", "smitten", "bitten", "knitting", "a",
+    ];
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows.clone());
+    let prefixes = Searcher::new(strings, Backend::Meta);
+    let whole = Searcher::new(strings, Backend::HSTree);
+    assert_eq!(
+        (prefixes.backend(), prefixes.match_kind()),
+        (Backend::Meta, MatchKind::Prefix)
+    );
+    assert_eq!(
+        (whole.backend(), whole.match_kind()),
+        (Backend::HSTree, MatchKind::Whole)
+    );
+    let mut cache = Cache::default();
+    for query in ["kitten", "sittin", "kit", ""] {
+        for requested in [0, 1, 3, 20] {
+            let expected: Vec<MeasuredString> = autocompleter
+                .autocomplete(query, requested, &mut cache)
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            assert_eq!(
+                prefixes.topk(query, requested),
+                expected,
+                "{query} {requested}"
+            );
+            assert_eq!(
+                whole.topk(query, requested),
+                unindexed_correct(query, 100, requested, &cows, None),
+                "{query} {requested}"
+            );
+        }
+        for threshold in 0..3 {
+            let options = QueryOptions {
+                max_prefix_distance: Some(threshold),
+                ..Default::default()
+            };
+            let expected: Vec<MeasuredString> = autocompleter
+                .autocomplete_with_options(query, strings.len(), |_| true, &options, &mut cache)
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            assert!(expected.iter().all(|measure| measure.distance <= threshold));
+            assert_eq!(
+                prefixes.threshold_query(query, threshold),
+                expected,
+                "{query} {threshold}"
+            );
+            assert_eq!(
+                whole.threshold_query(query, threshold),
+                unindexed_correct(query, threshold, usize::MAX, &cows, None),
+                "{query} {threshold}"
+            );
+            let mut expected = whole.threshold_query(query, threshold);
+            expected.truncate(2);
+            assert_eq!(
+                whole.threshold_topk(query, 2, threshold),
+                expected,
+                "{query} {threshold}"
+            );
+        }
+    }
+    assert_eq!(
+        whole.threshold_query("kitten", usize::MAX).len(),
+        strings.len()
+    );
+    // queries through the facade reuse the matching sets of earlier queries
+    let Searcher::Meta(_, cache) = &prefixes else {
+        unreachable!()
+    };
+    assert!(cache.stats().hits > 0);
+}
+
+#[test]
+/// Tests that the deletion index finds the same strings as comparing every string, up to its maximum distance
+fn symspell_queries() {
+    let strings = [
+        "kitten", "sitting", "mitten", "kitchen", "smitten", "bitten", "knitting", "a", "", "café",
+        "cafe", "cafés", "kitten",
+    ];
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    let index = SymSpell::new(strings);
+    assert_eq!(index.len(), strings.len());
+    for query in [
+        "kitten", "sittin", "kitchens", "caffe", "cfé", "", "b", "xyzzy",
+    ] {
+        for threshold in 0..=2 {
+            assert_eq!(
+                index.query(query, threshold),
+                unindexed_correct(query, threshold, usize::MAX, &cows, None),
+                "{query} {threshold}"
+            );
+        }
+        // thresholds beyond the precomputed variants compare every string
+        assert_eq!(
+            index.query(query, 5),
+            unindexed_correct(query, 5, usize::MAX, &cows, None),
+            "{query}"
+        );
+        assert_eq!(
+            index.topk(query, 2),
+            unindexed_correct(query, usize::MAX, 2, &cows, None),
+            "{query}"
+        );
+    }
+    let index = SymSpell::with_max_distance(strings, 1).unwrap();
+    assert_eq!(
+        index.query("sittin", 2),
+        unindexed_correct("sittin", 2, usize::MAX, &cows, None)
+    );
+    assert_eq!(
+        index.threshold_topk("sittin", 3, 1),
+        unindexed_correct("sittin", 1, 3, &cows, None)
+    );
+    assert_eq!(
+        SymSpell::with_max_distance(strings, 3).unwrap_err(),
+        MaxDistanceTooLarge(3)
+    );
+    let searcher = Searcher::new(strings, Backend::SymSpell);
+    assert_eq!(searcher.match_kind(), MatchKind::Whole);
+    assert_eq!(
+        searcher.threshold_query("cafe", 1),
+        unindexed_correct("cafe", 1, usize::MAX, &cows, None)
+    );
+}
+
+#[test]
+/// Tests that searching with the Levenshtein automaton finds the closest strings like comparing every string
+fn automaton_queries() {
+    let strings = [
+        "kitten", "sitting", "mitten", "kitchen", "smitten", "bitten", "knitting", "a", "café",
+        "cafés", "caffeine",
+    ];
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    let autocompleter = MetaAutocompleter::new(cows.len(), cows.clone());
+    let mut cache = Cache::default();
+    let options = QueryOptions {
+        algorithm: SearchAlgorithm::Automaton,
+        ..Default::default()
+    };
+    for query in ["kitten", "sittin", "kit", "cafe", "xyz", "knitten", ""] {
+        for requested in [0, 1, 3, 20] {
+            assert_eq!(
+                autocompleter.autocomplete_with_options(
+                    query,
+                    requested,
+                    |_| true,
+                    &options,
+                    &mut cache
+                ),
+                unindexed_autocomplete(query, requested, &cows),
+                "{query} {requested}"
+            );
+        }
+        for max_prefix_distance in 0..3 {
+            let options = QueryOptions {
+                max_prefix_distance: Some(max_prefix_distance),
+                ..options.clone()
+            };
+            let expected: Vec<MeasuredPrefix> = unindexed_autocomplete(query, strings.len(), &cows)
+                .into_iter()
+                .filter(|measure| measure.prefix_distance <= max_prefix_distance)
+                .collect();
+            assert_eq!(
+                autocompleter.autocomplete_with_options(
+                    query,
+                    strings.len(),
+                    |_| true,
+                    &options,
+                    &mut cache
+                ),
+                expected,
+                "{query} {max_prefix_distance}"
+            );
+        }
+    }
+    let strict = QueryOptions {
+        strict_prefix: 2,
+        ..options.clone()
+    };
+    let result =
+        autocompleter.autocomplete_with_options("kiten", 10, |_| true, &strict, &mut cache);
+    assert!(result
+        .iter()
+        .all(|measure| measure.string.starts_with("ki")));
+    assert_eq!(result[0].string, "kitten");
+    let bounded = QueryOptions {
+        max_matchings: Some(1),
+        ..options.clone()
+    };
+    let (_, truncated) =
+        autocompleter.autocomplete_bounded("kitten", 3, |_| true, &bounded, &mut cache);
+    assert!(truncated);
+}
+
+#[test]
+/// Tests that BK-trees find the same strings as measuring every string by their metrics
+fn bk_tree_queries() {
+    let strings = [
+        "kitten", "sitting", "mitten", "kitchen", "smitten", "bitten", "knitting", "a", "", "café",
+        "Cafe", "kitten",
+    ];
+    let costs = OperationCosts {
+        substitute: 3,
+        case_substitute: 1,
+        ..Default::default()
+    };
+    fn brute_force(strings: &[&str], metric: &impl Metric, query: &str) -> Vec<MetricString> {
+        let mut measures: Vec<MetricString> = strings
+            .iter()
+            .map(|&string| MetricString {
+                string: string.to_string(),
+                distance: metric.distance(query, string),
+            })
+            .collect();
+        measures.sort_by(|first, second| {
+            first
+                .distance
+                .total_cmp(&second.distance)
+                .then_with(|| first.string.cmp(&second.string))
+        });
+        measures.dedup();
+        measures
+    }
+    let edits = BKTree::new(strings, EditDistance);
+    let weighted = BKTree::new(strings, WeightedEditDistance(costs));
+    assert_eq!(edits.len(), strings.len());
+    for query in ["kitten", "sittin", "cafe", "", "xyzzy"] {
+        for (tree_measures, metric_measures) in [
+            (
+                edits.query(query, 2.0),
+                brute_force(&strings, &EditDistance, query),
+            ),
+            (
+                weighted.query(query, 2.0),
+                brute_force(&strings, &WeightedEditDistance(costs), query),
+            ),
+        ] {
+            let expected: Vec<_> = metric_measures
+                .into_iter()
+                .filter(|measure| measure.distance <= 2.0)
+                .collect();
+            assert_eq!(tree_measures, expected, "{query}");
+        }
+        for requested in [0, 1, 3, 20] {
+            let mut expected = brute_force(&strings, &WeightedEditDistance(costs), query);
+            expected.truncate(requested);
+            assert_eq!(
+                weighted.topk(query, requested),
+                expected,
+                "{query} {requested}"
+            );
+            let expected: Vec<MetricString> = unindexed_correct(
+                query,
+                100,
+                requested,
+                &strings.iter().map(|&s| s.into()).collect::<Vec<_>>(),
+                None,
+            )
+            .into_iter()
+            .map(|measure| MetricString {
+                string: measure.string,
+                distance: measure.distance as f64,
+            })
+            .collect();
+            assert_eq!(
+                edits.topk(query, requested),
+                expected,
+                "{query} {requested}"
+            );
+        }
+    }
+    let transpositions = BKTree::new(strings, UnrestrictedDamerauEditDistance);
+    assert_eq!(transpositions.query("iktten", 1.0)[0].string, "kitten");
+    for query in ["iktten", "ca", "stiting", "", "xyzzy"] {
+        let expected: Vec<_> = brute_force(&strings, &UnrestrictedDamerauEditDistance, query)
+            .into_iter()
+            .filter(|measure| measure.distance <= 2.0)
+            .collect();
+        assert_eq!(transpositions.query(query, 2.0), expected, "{query}");
+    }
+    assert!(BKTree::new([], EditDistance).topk("kitten", 1).is_empty());
+}
+
+#[test]
+/// Tests that MinHash candidates are verified exactly and that strings with most of their q-grams in common are found
+fn minhash_queries() {
+    let strings = [
+        "the quick brown fox jumps",
+        "the quick brown fix jumps",
+        "a quick brown dog jumps",
+        "lorem ipsum dolor sit amet",
+        "lorem ipsum dolor sit",
+        "kitten",
+        "a",
+        "",
+    ];
+    let cows: Vec<_> = strings.iter().map(|&s| s.into()).collect();
+    let index = MinHashIndex::new(strings);
+    assert_eq!(index.len(), strings.len());
+    for query in [
+        "the quick brown fox jumps",
+        "the quikc brown fox jumps",
+        "lorem ipsum dolor sit amt",
+        "kitten",
+        "a",
+        "",
+        "xyz",
+    ] {
+        let all = unindexed_correct(query, 100, usize::MAX, &cows, None);
+        for threshold in [0, 2, 6] {
+            let result = index.query(query, threshold);
+            // candidates are verified, so every result is one of the strings within the threshold
+            assert!(
+                result
+                    .iter()
+                    .all(|measure| all.contains(measure) && measure.distance <= threshold),
+                "{query} {threshold}"
+            );
+        }
+        // the closest string shares most of its q-grams with these queries
+        assert_eq!(
+            index.topk(query, 1).first(),
+            all.first().filter(|measure| measure.distance <= 2),
+            "{query}"
+        );
+    }
+    let mut typo = index.query("the quick brown fox jumsp", 2);
+    typo.truncate(2);
+    assert_eq!(
+        typo,
+        unindexed_correct("the quick brown fox jumsp", 2, 2, &cows, None)
+    );
+    // inserting keeps the bands sorted, so strings inserted later are found like those of the constructor
+    let mut inserted = MinHashIndex::new(strings[..3].iter().copied());
+    for &string in &strings[3..] {
+        inserted.insert(string);
+    }
+    for query in [
+        "the quikc brown fox jumps",
+        "lorem ipsum dolor sit amt",
+        "kitten",
+        "xyz",
+    ] {
+        assert_eq!(inserted.query(query, 6), index.query(query, 6), "{query}");
+    }
+    let params = MinHashParams {
+        q: 3,
+        bands: 4,
+        rows: 3,
+    };
+    let searcher = Searcher::new(strings, Backend::MinHash(params));
+    assert_eq!(searcher.backend(), Backend::MinHash(params));
+    assert_eq!(
+        searcher.topk("lorem ipsum dolor sit amet", 1)[0].distance,
+        0
+    );
+}
+
+#[test]
+/// Tests that caches keep their own capacity and that pruning them, automatically or not, doesn't change results
+fn cache_capacity() {
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::with_capacity(1);
+    assert_eq!(cache.capacity(), Some(1));
+    cache.set_capacity(Some(2));
+    assert_eq!(cache.capacity(), Some(2));
+    assert_eq!(Cache::default().capacity(), None);
+
+    // queries prune the cache as they go, and pruning again right away keeps the results the same
+    for query in [
+        "sol", "exam", "smple", "sold", "sölu", "ex", "sample", "exämple",
+    ] {
+        let expected = autocompleter.autocomplete(query, 2, &mut Cache::default());
+        assert_eq!(
+            autocompleter.autocomplete(query, 2, &mut cache),
+            expected,
+            "{query}"
+        );
+        cache.set_capacity(Some(0));
+        autocompleter.prune(&mut cache);
+        assert_eq!(
+            autocompleter.autocomplete(query, 2, &mut cache),
+            expected,
+            "{query}"
+        );
+        cache.set_capacity(Some(2));
+    }
+}
+
+#[test]
+/// Tests that cache statistics count prefixes, hits, misses and evictions, and that clearing keeps the counters
+fn cache_stats() {
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::default();
+    assert_eq!(cache.stats(), CacheStats::default());
+    assert!(cache.is_empty());
+
+    let expected = autocompleter.autocomplete("sol", 2, &mut Cache::default());
+    autocompleter.autocomplete("sol", 2, &mut cache);
+    let first = cache.stats();
+    assert_eq!(first.entries, 3);
+    assert_eq!(first.hits, 0);
+    assert!(first.misses > 0);
+    assert!(first.bytes > 0);
+
+    // the same query again only takes sets from the cache
+    assert_eq!(autocompleter.autocomplete("sol", 2, &mut cache), expected);
+    let second = cache.stats();
+    assert!(second.hits > 0);
+    assert_eq!(second.misses, first.misses);
+    assert_eq!(second.bytes, first.bytes);
+
+    autocompleter.autocomplete("exam", 2, &mut cache);
+    assert_eq!(cache.len(), 7);
+    let before = cache.stats();
+    cache.set_capacity(Some(1));
+    autocompleter.prune(&mut cache);
+    let pruned = cache.stats();
+    assert!(cache.len() <= 1);
+    assert_eq!(pruned.entries, cache.len());
+    assert_eq!(pruned.evictions, (before.entries - pruned.entries) as u64);
+    assert!(pruned.bytes < before.bytes);
+
+    cache.clear();
+    let cleared = cache.stats();
+    assert!(cache.is_empty());
+    assert_eq!(cleared.bytes, 0);
+    assert_eq!(
+        (cleared.hits, cleared.misses, cleared.evictions),
+        (pruned.hits, pruned.misses, pruned.evictions)
+    );
+    assert_eq!(cache.capacity(), Some(1));
+    assert_eq!(autocompleter.autocomplete("sol", 2, &mut cache), expected);
+    assert!(cache.stats().misses > cleared.misses);
+}
+
+#[test]
+/// Tests that caches with a byte budget stay within it after pruning without changing results
+fn cache_byte_budget() {
+    let autocompleter = sample_autocompleter();
+    let mut unlimited = Cache::default();
+    for query in [
+        "sol", "exam", "smple", "sold", "sölu", "ex", "sample", "exämple",
+    ] {
+        autocompleter.autocomplete(query, 2, &mut unlimited);
+    }
+    let budget = unlimited.stats().bytes / 4;
+    let mut cache = Cache::with_byte_budget(budget);
+    assert_eq!(cache.byte_budget(), Some(budget));
+    assert_eq!(Cache::default().byte_budget(), None);
+
+    // queries prune the cache once it exceeds the budget by half
+    for query in [
+        "sol", "exam", "smple", "sold", "sölu", "ex", "sample", "exämple",
+    ] {
+        let expected = autocompleter.autocomplete(query, 2, &mut Cache::default());
+        assert_eq!(
+            autocompleter.autocomplete(query, 2, &mut cache),
+            expected,
+            "{query}"
+        );
+        assert!(cache.stats().bytes <= budget + budget / 2, "{query}");
+        autocompleter.prune(&mut cache);
+        assert!(cache.stats().bytes <= budget, "{query}");
+        // only the least recently visited prefixes are evicted to fit the budget
+        assert!(!cache.is_empty(), "{query}");
+        assert_eq!(
+            autocompleter.autocomplete(query, 2, &mut cache),
+            expected,
+            "{query}"
+        );
+    }
+    assert!(cache.stats().evictions > 0);
+
+    cache.set_byte_budget(Some(0));
+    autocompleter.prune(&mut cache);
+    assert!(cache.is_empty());
+    assert_eq!(cache.stats().bytes, 0);
+    cache.set_byte_budget(None);
+    assert_eq!(cache.byte_budget(), None);
+}
+
+#[test]
+/// Tests that threads querying a shared autocompleter with a shared cache get the same results as with their own caches
+fn shared_cache() {
+    let autocompleter = Arc::new(sample_autocompleter());
+    let cache = Arc::new(SharedCache::new(4));
+    assert_eq!(cache.shard_count(), 4);
+    assert_eq!(SharedCache::default().shard_count(), DEFAULT_SHARD_COUNT);
+    assert!(cache.is_empty());
+
+    let queries = [
+        "s", "so", "sol", "e", "ex", "exam", "smple", "sölu", "", "exämple",
+    ];
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let autocompleter = Arc::clone(&autocompleter);
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                for query in queries {
+                    let expected = autocompleter.autocomplete(query, 2, &mut Cache::default());
+                    assert_eq!(
+                        autocompleter.autocomplete_shared(query, 2, &cache),
+                        expected,
+                        "{query}"
+                    );
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let stats = cache.stats();
+    assert_eq!(stats.entries, cache.len());
+    assert!(stats.hits > 0);
+    assert!(stats.misses > 0);
+
+    // the guard can be used with any method that takes a cache
+    let result =
+        autocompleter.autocomplete_filtered("sol", 3, |s| s != "sold", &mut cache.lock("sol"));
+    assert!(result.iter().all(|prefix| prefix.string != "sold"));
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(
+        SharedCache::with_capacity(2, 1).lock("s").capacity(),
+        Some(1)
+    );
+
+    // a query that panics while holding a shard leaves it cleared instead of poisoned
+    autocompleter.autocomplete_shared("sol", 2, &cache);
+    let panicking = Arc::clone(&cache);
+    assert!(thread::spawn(move || {
+        let _shard = panicking.lock("sol");
+        panic!("query failed");
+    })
+    .join()
+    .is_err());
+    assert!(cache.lock("sol").is_empty());
+    let expected = autocompleter.autocomplete("sol", 2, &mut Cache::default());
+    assert_eq!(
+        autocompleter.autocomplete_shared("sol", 2, &cache),
+        expected
+    );
+}
+
+#[test]
+/// Tests that invalidating a prefix removes the cached queries that start with it, and that rebuilt shards don't use stale caches
+fn cache_invalidation() {
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::with_capacity(8);
+    for query in ["sol", "solv", "exam"] {
+        autocompleter.autocomplete(query, 2, &mut cache);
+    }
+    assert_eq!(cache.len(), 8);
+    let bytes = cache.stats().bytes;
+    cache.invalidate_prefix(&autocompleter, "sol");
+    // "s" and "so" are still cached
+    assert_eq!(cache.len(), 6);
+    assert!(cache.stats().bytes < bytes);
+    for query in ["solv", "sol", "exam", "so"] {
+        let expected = autocompleter.autocomplete(query, 2, &mut Cache::default());
+        assert_eq!(
+            autocompleter.autocomplete(query, 2, &mut cache),
+            expected,
+            "{query}"
+        );
+    }
+    cache.invalidate_prefix(&autocompleter, "");
+    assert!(cache.is_empty());
+    autocompleter.autocomplete("sol", 2, &mut cache);
+    cache.invalidate_all();
+    assert!(cache.is_empty());
+    assert_eq!(cache.capacity(), Some(8));
+
+    let mut sharded = ShardedAutocompleter::new(3, SOLO_STRINGS.map(Into::into));
+    let mut cache = ShardedCache::default();
+    let shard = sharded.shard_of("solar");
+    let before = sharded.autocomplete("solar", 2, &mut cache);
+    assert_ne!(before[0].string, "solar");
+    let mut strings: Vec<_> = sharded.shards()[shard]
+        .iter_strings()
+        .map(|s| s.to_string().into())
+        .collect();
+    strings.push("solar".into());
+    sharded.rebuild_shard(shard, strings);
+    let result = sharded.autocomplete("solar", 2, &mut cache);
+    assert_eq!(
+        result,
+        sharded.autocomplete("solar", 2, &mut ShardedCache::default())
+    );
+    assert_eq!(result[0].string, "solar");
+
+    // a cache filled by one index holds its node ids, so it's dropped when used with another
+    let mut cache = Cache::default();
+    autocompleter.autocomplete("solu", 2, &mut cache);
+    let smaller: Vec<TreeString> = vec!["sol".into()];
+    let smaller = MetaAutocompleter::new(smaller.len(), smaller);
+    let expected = smaller.autocomplete("solu", 2, &mut Cache::default());
+    assert_eq!(smaller.autocomplete("solu", 2, &mut cache), expected);
+    let merged = autocompleter.merge(smaller);
+    let expected = merged.autocomplete("solu", 3, &mut Cache::default());
+    assert_eq!(merged.autocomplete("solu", 3, &mut cache), expected);
+}
+
+#[test]
+/// Tests that warming a cache deduces the matching sets of popular prefixes so that queries for them only take cached sets
+fn cache_warm() {
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::default();
+    cache.warm(&autocompleter, ["sol", "exam", ""], 2);
+    let warmed = cache.stats();
+    assert_eq!(warmed.entries, 7);
+    assert_eq!(warmed.hits, 0);
+    assert!(warmed.misses > 0);
+
+    for query in ["s", "so", "sol", "e", "exam"] {
+        let expected = autocompleter.autocomplete(query, 2, &mut Cache::default());
+        assert_eq!(
+            autocompleter.autocomplete(query, 2, &mut cache),
+            expected,
+            "{query}"
+        );
+    }
+    assert_eq!(cache.stats().misses, warmed.misses);
+    assert!(cache.stats().hits > 0);
+}
+
+#[cfg(all(feature = "serde", feature = "jsonl"))]
+#[test]
+/// Tests that a saved cache is restored with its prefixes and matching sets only for an autocompleter over the same strings
+fn cache_persistence() {
+    use crate::prefix::meta::CacheSnapshot;
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::with_capacity(10);
+    cache.warm(&autocompleter, ["sol", "exam"], 2);
+    let snapshot = autocompleter.save_cache(&cache);
+    let json = serde_json::to_string(&snapshot).unwrap();
+
+    let snapshot: CacheSnapshot = serde_json::from_str(&json).unwrap();
+    let rebuilt = sample_autocompleter();
+    let mut restored = rebuilt.restore_cache(snapshot.clone()).unwrap();
+    assert_eq!(restored.len(), cache.len());
+    assert_eq!(restored.stats().bytes, cache.stats().bytes);
+    assert_eq!(restored.capacity(), Some(10));
+    assert_eq!(rebuilt.save_cache(&restored), snapshot);
+    for query in ["s", "sol", "exam"] {
+        let expected = rebuilt.autocomplete(query, 2, &mut Cache::default());
+        assert_eq!(
+            rebuilt.autocomplete(query, 2, &mut restored),
+            expected,
+            "{query}"
+        );
+    }
+    assert_eq!(restored.stats().misses, 0);
+
+    let other: Vec<TreeString> = vec!["solve".into(), "sold".into()];
+    let other = MetaAutocompleter::new(other.len(), other);
+    assert!(other.restore_cache(snapshot).is_none());
+}
+
+#[test]
+/// Tests that memoized results are returned for repeated queries and pruned like cached prefixes
+fn memoized_results() {
+    let mut autocompleter = sample_autocompleter();
+    let mut cache = Cache::with_capacity(4);
+    assert!(!cache.memoizes_results());
+    autocompleter.autocomplete("sol", 2, &mut cache);
+    assert_eq!(cache.stats().results, 0);
+    cache.set_memoize_results(true);
+    assert!(cache.memoizes_results());
+
+    let expected = autocompleter.autocomplete("sol", 2, &mut Cache::default());
+    assert_eq!(autocompleter.autocomplete("sol", 2, &mut cache), expected);
+    let misses = cache.stats().misses;
+    assert_eq!(autocompleter.autocomplete("sol", 2, &mut cache), expected);
+    // the second query skips assembling matching sets entirely
+    let stats = cache.stats();
+    assert_eq!(
+        (stats.results, stats.result_hits, stats.misses),
+        (1, 1, misses)
+    );
+    // other numbers of results and thresholds are memoized separately
+    assert_eq!(
+        autocompleter.autocomplete("sol", 1, &mut cache),
+        expected[..1]
+    );
+    assert!(!autocompleter
+        .autocomplete("smple", 2, &mut cache)
+        .is_empty());
+    autocompleter.set_threshold(Some(ThresholdPolicy::Fixed(0)));
+    assert!(autocompleter
+        .autocomplete("smple", 2, &mut cache)
+        .is_empty());
+    assert_eq!(cache.stats().results, 4);
+
+    cache.set_capacity(Some(2));
+    autocompleter.prune(&mut cache);
+    assert_eq!(cache.stats().results, 2);
+    cache.invalidate_prefix(&autocompleter, "sm");
+    assert_eq!(cache.stats().results, 0);
+    autocompleter.autocomplete("exam", 2, &mut cache);
+    assert_eq!(cache.stats().results, 1);
+    cache.set_byte_budget(Some(0));
+    autocompleter.prune(&mut cache);
+    assert_eq!(cache.stats().results, 0);
+    cache.set_memoize_results(false);
+    assert!(!cache.memoizes_results());
+}
+
+#[test]
+/// Tests that memoized results aren't returned after pinning changes the ranking
+fn memoized_results_after_pin() {
+    let strings: Vec<TreeString> = ["solve", "solution", "sold", "sample"]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let mut autocompleter = MetaAutocompleter::new(strings.len(), strings);
+    let mut cache = Cache::default();
+    cache.set_memoize_results(true);
+    let result = autocompleter.autocomplete("so", 1, &mut cache);
+    assert_ne!(result[0].string, "sample");
+    assert!(autocompleter.pin("so", "sample"));
+    let result = autocompleter.autocomplete("so", 1, &mut cache);
+    assert_eq!(result[0].string, "sample");
+    assert_eq!(cache.stats().result_hits, 0);
+    autocompleter.unpin("so");
+    let result = autocompleter.autocomplete("so", 1, &mut cache);
+    assert_ne!(result[0].string, "sample");
+}
+
+#[test]
+/// Tests that queries with multibyte characters get matchings with edits for their last prefix, with or without cached prefixes
+fn multibyte_edits() {
+    let autocompleter = sample_autocompleter();
+    let result = autocompleter.autocomplete("sölu", 1, &mut Cache::default());
+    assert_eq!(result[0].string, "solution");
+    assert_eq!(result[0].prefix_distance, 1);
+
+    let mut cache = Cache::default();
+    for query in ["e", "ex", "exä", "exäm", "exämp", "exä", "exäm"] {
+        let result = autocompleter.autocomplete(query, 2, &mut cache);
+        assert_eq!(
+            result,
+            autocompleter.autocomplete(query, 2, &mut Cache::default()),
+            "{query}"
+        );
+    }
+    let result = autocompleter.autocomplete("exämp", 1, &mut cache);
+    assert_eq!(
+        (result[0].string.as_str(), result[0].prefix_distance),
+        ("example", 1)
+    );
+}
+
+#[test]
+/// Tests that the deltas of each edit budget are cached, so that queries with other budgets only deduce the missing ones
+fn cached_budgets() {
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::default();
+    let mut query = |budget: usize, cache: &mut Cache| {
+        let options = QueryOptions {
+            max_prefix_distance: Some(budget),
+            ..Default::default()
+        };
+        let result = autocompleter.autocomplete_with_options("smpl", 3, |_| true, &options, cache);
+        let expected = autocompleter.autocomplete_with_options(
+            "smpl",
+            3,
+            |_| true,
+            &options,
+            &mut Cache::default(),
+        );
+        assert_eq!(result, expected, "{budget}");
+        cache.stats().misses
+    };
+    // a budget of 0 deduces a delta for each of the 4 prefixes, and each larger budget one more for the last prefix
+    assert_eq!(query(0, &mut cache), 4);
+    assert_eq!(query(1, &mut cache), 5);
+    assert_eq!(query(2, &mut cache), 6);
+    // every budget up to 2 is cached now
+    assert_eq!(query(1, &mut cache), 6);
+    assert_eq!(query(0, &mut cache), 6);
+    assert_eq!(query(2, &mut cache), 6);
+}
+
+#[cfg(feature = "archive")]
+#[test]
+/// Tests that an archived index is loaded with the same results while borrowing its strings, and that corrupted archives are rejected
+fn archived_index() {
+    use crate::prefix::meta::{ArchiveError, ThresholdPolicy};
+    use std::borrow::Cow;
+    let source = [
+        ("solve", 3),
+        ("solution", 5),
+        ("sold", 1),
+        ("example", 2),
+        ("sample", 4),
+    ];
+    let mut autocompleter = MetaAutocompleter::new_weighted(
+        source.len(),
+        source
+            .into_iter()
+            .map(|(string, weight)| (string.into(), weight)),
+    );
+    autocompleter.pin("s", "sold");
+    autocompleter.set_threshold(Some(ThresholdPolicy::Scaled { per_chars: 3 }));
+    autocompleter.precompute_top(2);
+    let bytes = autocompleter.to_archive().unwrap();
+
+    let loaded = MetaAutocompleter::from_archive(&bytes).unwrap();
+    assert!(loaded
+        .trie
+        .strings
+        .iter()
+        .all(|string| matches!(string, Cow::Borrowed(_))));
+    assert_eq!(loaded.to_archive().unwrap(), bytes);
+    for query in ["s", "sol", "smaple", "exampel", "x"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            loaded.autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+
+    assert_eq!(
+        MetaAutocompleter::from_archive(b"nope").err(),
+        Some(ArchiveError::BadMagic)
+    );
+    let mut newer = bytes.clone();
+    newer[4] += 1;
+    assert_eq!(
+        MetaAutocompleter::from_archive(&newer).err(),
+        Some(ArchiveError::UnsupportedVersion(3))
+    );
+    for len in [8, bytes.len() / 2, bytes.len() - 1] {
+        assert!(
+            MetaAutocompleter::from_archive(&bytes[..len]).is_err(),
+            "{len}"
+        );
+    }
+}
+
+#[cfg(feature = "archive")]
+#[test]
+/// Tests that archived nodes with checksums that match but depths or ranges that aren't nested in pre-order are rejected
+fn corrupted_archived_nodes() {
+    use crate::prefix::meta::ArchiveError;
+    let strings: Vec<TreeString> = ["ab", "ac", "b"].into_iter().map(Into::into).collect();
+    let autocompleter = MetaAutocompleter::new(strings.len(), strings);
+    let bytes = autocompleter.to_archive().unwrap();
+    // the header, then the number of strings, their end offsets and their bytes, then the number of nodes
+    let nodes = 14 + 4 + 4 * 3 + 5 + 4;
+    let corrupt = |offset: usize, value: u8| {
+        let mut corrupted = bytes.clone();
+        corrupted[offset] = value;
+        let checksum = crc32fast::hash(&corrupted[14..]);
+        corrupted[10..14].copy_from_slice(&checksum.to_le_bytes());
+        MetaAutocompleter::from_archive(&corrupted).err()
+    };
+    // nodes are the root, "a", "ab", "ac" and "b", each with a character, 4 range bounds and a depth
+    let depth = |node: usize| nodes + 21 * node + 20;
+    let descendants_end = |node: usize| nodes + 21 * node + 8;
+    assert!(corrupt(depth(0), 0).is_none());
+    assert_eq!(corrupt(depth(1), 0), Some(ArchiveError::Invalid("node")));
+    assert_eq!(corrupt(depth(2), 1), Some(ArchiveError::Invalid("node")));
+    assert_eq!(corrupt(depth(0), 1), Some(ArchiveError::Invalid("node")));
+    // "ab" claiming "ac" as a descendant, and "a" claiming "b"
+    assert_eq!(
+        corrupt(descendants_end(2), 4),
+        Some(ArchiveError::Invalid("node"))
+    );
+    assert_eq!(
+        corrupt(descendants_end(1), 5),
+        Some(ArchiveError::Invalid("node"))
+    );
+}
+
+#[cfg(feature = "archive")]
+#[test]
+/// Tests that the settings of the normalizer are archived, and that custom normalization functions aren't
+fn archived_normalizer() {
+    use crate::prefix::meta::{ArchiveError, CharFilter};
+    let source = ["e-mail", "Example", "sample"];
+    let builder = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Ascii)
+        .char_filter(CharFilter::Remove);
+    let autocompleter = builder.clone().build(source.iter().map(|&s| s.into()));
+    let bytes = autocompleter.to_archive().unwrap();
+    let loaded = MetaAutocompleter::from_archive(&bytes).unwrap();
+    for query in ["E MAIL", "exam", "SAMP"] {
+        let expected = autocompleter.autocomplete(query, 2, &mut Cache::default());
+        assert_eq!(expected[0].prefix_distance, 0, "{query}");
+        assert_eq!(
+            loaded.autocomplete(query, 2, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+
+    let custom = builder
+        .normalization(|string| string.replace('x', "s"))
+        .build(source.iter().map(|&s| s.into()));
+    assert_eq!(
+        custom.to_archive().err(),
+        Some(ArchiveError::CustomNormalizer)
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+/// Tests that an autocompleter loaded from a memory-mapped archive gives the same results as the archived one
+fn mapped_index() {
+    use crate::prefix::meta::IndexFileError;
+    let autocompleter = sample_autocompleter();
+    let path = std::env::temp_dir().join(format!("strprox-mapped-{}.spxa", std::process::id()));
+    fs::write(&path, autocompleter.to_archive().unwrap()).unwrap();
+
+    // the file isn't changed until the mapping is dropped
+    let mapped = unsafe { MetaAutocompleter::load_mmap(&path) }.unwrap();
+    for query in ["s", "sol", "smaple", "exampel"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            mapped.get().autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+    drop(mapped);
+    // the file was just written by `to_archive`, so it can be loaded without validating its strings
+    let trusted = unsafe { MetaAutocompleter::load_mmap_unchecked(&path) }.unwrap();
+    for query in ["s", "sol", "smaple", "exampel"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            trusted.get().autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+    drop(trusted);
+
+    fs::write(&path, b"not an index").unwrap();
+    assert!(matches!(
+        unsafe { MetaAutocompleter::load_mmap(&path) },
+        Err(IndexFileError::Archive(_))
+    ));
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        unsafe { MetaAutocompleter::load_mmap(&path) },
+        Err(IndexFileError::Io(_))
+    ));
+}
+
+#[cfg(feature = "archive")]
+#[test]
+/// Tests that a saved index is loaded with the same results, and that files with other widths or corrupted sections are rejected
+fn index_file() {
+    use crate::prefix::meta::{ArchiveError, IndexFileError};
+    let autocompleter = sample_autocompleter();
+    let path = std::env::temp_dir().join(format!("strprox-index-{}.spxa", std::process::id()));
+    autocompleter.save_to(&path).unwrap();
+    let loaded = MetaAutocompleter::load_from(&path).unwrap();
+    for query in ["s", "sol", "smaple", "exampel"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            loaded.autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+
+    let bytes = fs::read(&path).unwrap();
+    let mut older = bytes.clone();
+    older[4..8].copy_from_slice(&1u32.to_le_bytes());
+    fs::write(&path, &older).unwrap();
+    assert!(matches!(
+        MetaAutocompleter::load_from(&path),
+        Err(IndexFileError::Archive(ArchiveError::UnsupportedVersion(1)))
+    ));
+    let mut wider = bytes.clone();
+    wider[9] = 8;
+    fs::write(&path, &wider).unwrap();
+    assert!(matches!(
+        MetaAutocompleter::load_from(&path),
+        Err(IndexFileError::Archive(ArchiveError::WidthMismatch {
+            uuu: 1,
+            sss: 8
+        }))
+    ));
+    let mut corrupted = bytes;
+    *corrupted.last_mut().unwrap() ^= 1;
+    fs::write(&path, &corrupted).unwrap();
+    assert!(matches!(
+        MetaAutocompleter::load_from(&path),
+        Err(IndexFileError::Archive(ArchiveError::ChecksumMismatch))
+    ));
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        MetaAutocompleter::load_from(&path),
+        Err(IndexFileError::Io(_))
+    ));
+}
+
+#[cfg(all(feature = "serde", feature = "jsonl"))]
+#[test]
+/// Tests that an autocompleter deserialized from a stream owns its strings and gives the same results as the serialized one
+fn owned_deserialization() {
+    use crate::prefix::meta::DeserializedAutocompleter;
+    let source = [
+        ("solve", 3),
+        ("solution", 5),
+        ("sold", 1),
+        ("example", 2),
+        ("sample", 4),
+    ];
+    let mut autocompleter = MetaAutocompleter::new_weighted(
+        source.len(),
+        source
+            .into_iter()
+            .map(|(string, weight)| (string.into(), weight)),
+    );
+    autocompleter.pin("s", "sold");
+    let json = serde_json::to_vec(&autocompleter).unwrap();
+
+    let deserialized: DeserializedAutocompleter = serde_json::from_reader(json.as_slice()).unwrap();
+    let owned: MetaAutocompleter<'static> = deserialized.into();
+    drop(json);
+    for query in ["s", "sol", "smaple", "exampel"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            owned.autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+}
+
+#[cfg(feature = "postcard")]
+#[test]
+/// Tests that an autocompleter decoded from its bytes gives the same results as the encoded one
+fn encoded_bytes() {
+    use crate::prefix::meta::{BytesError, BYTES_VERSION};
+    let source = [
+        ("solve", 3),
+        ("solution", 5),
+        ("sold", 1),
+        ("example", 2),
+        ("sample", 4),
+    ];
+    let mut autocompleter = MetaAutocompleter::new_weighted(
+        source.len(),
+        source
+            .into_iter()
+            .map(|(string, weight)| (string.into(), weight)),
+    );
+    autocompleter.pin("s", "sold");
+    let bytes = autocompleter.to_bytes().unwrap();
+    assert_eq!(bytes[0] as u32, BYTES_VERSION);
+
+    let decoded: MetaAutocompleter = MetaAutocompleter::from_bytes(&bytes).unwrap();
+    for query in ["s", "sol", "smaple", "exampel"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            decoded.autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+
+    let mut newer = bytes.clone();
+    newer[0] += 1;
+    assert!(matches!(
+        <MetaAutocompleter>::from_bytes(&newer),
+        Err(BytesError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        <MetaAutocompleter>::from_bytes(&bytes[..bytes.len() / 2]),
+        Err(BytesError::Postcard(_))
+    ));
+
+    // the normalizer is encoded, so folded queries still match
+    let source = vec!["Solid", "solo", "Solve"];
+    let folded = MetaAutocompleterBuilder::new()
+        .case_folding(CaseFolding::Ascii)
+        .build(source.iter().map(|&s| s.into()));
+    let decoded: MetaAutocompleter =
+        MetaAutocompleter::from_bytes(&folded.to_bytes().unwrap()).unwrap();
+    assert_eq!(
+        decoded.autocomplete("SOLV", 1, &mut Cache::default()),
+        folded.autocomplete("SOLV", 1, &mut Cache::default())
+    );
+    assert_eq!(
+        decoded.autocomplete("SOLV", 1, &mut Cache::default())[0].prefix_distance,
+        0
+    );
+    let custom = MetaAutocompleterBuilder::new()
+        .normalization(|string| string.to_uppercase())
+        .build(source.iter().map(|&s| s.into()));
+    assert!(matches!(custom.to_bytes(), Err(BytesError::Postcard(_))));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+/// Tests that a compressed index is detected and decompressed by `load_from`
+fn compressed_index_file() {
+    let strings: Vec<TreeString> = WORDS.lines().map(Into::into).collect();
+    let autocompleter = MetaAutocompleter::new(strings.len(), strings);
+    let path = std::env::temp_dir().join(format!(
+        "strprox-compressed-{}.spxa.zst",
+        std::process::id()
+    ));
+    autocompleter.save_compressed_to(&path, 0).unwrap();
+    assert!(fs::metadata(&path).unwrap().len() < autocompleter.to_archive().unwrap().len() as u64);
+
+    let loaded = MetaAutocompleter::load_from(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), autocompleter.len());
+    for query in ["s", "sol", "smaple", "exampel"] {
+        let expected = autocompleter.autocomplete(query, 3, &mut Cache::default());
+        assert_eq!(
+            loaded.autocomplete(query, 3, &mut Cache::default()),
+            expected,
+            "{query}"
+        );
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "jsonl"))]
+#[test]
+/// Tests that query results and cache statistics are serialized as plain JSON objects
+fn results_json() {
+    use crate::MeasuredPrefix;
+    let autocompleter = sample_autocompleter();
+    let mut cache = Cache::default();
+    let results = autocompleter.autocomplete("sola", 2, &mut cache);
+    let json = serde_json::to_string(&results).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"string":"sold","prefix_distance":1},{"string":"solution","prefix_distance":1}]"#
+    );
+    assert_eq!(
+        serde_json::from_str::<Vec<MeasuredPrefix>>(&json).unwrap(),
+        results
+    );
+
+    let stats = cache.stats();
+    let json = serde_json::to_string(&stats).unwrap();
+    assert!(json.starts_with(r#"{"entries":"#), "{json}");
+    assert_eq!(serde_json::from_str::<CacheStats>(&json).unwrap(), stats);
+    assert_eq!(
+        serde_json::to_string(&MatchKind::Prefix).unwrap(),
+        r#""Prefix""#
+    );
+}
+
+#[test]
+/// Tests that caches with a capacity stay bounded by it however many distinct queries are typed
+fn bounded_cache() {
+    let source: Vec<_> = WORDS.lines().collect();
+    let strings: Vec<TreeString> = source.iter().map(|&string| string.into()).collect();
+    let autocompleter = MetaAutocompleter::new(strings.len(), strings);
+    let mut cache = Cache::with_capacity(8);
+    for string in source.iter().take(50) {
+        let query: String = string.chars().take(6).collect();
+        let expected = autocompleter.autocomplete(&query, 3, &mut Cache::default());
+        assert_eq!(
+            autocompleter.autocomplete(&query, 3, &mut cache),
+            expected,
+            "{query}"
+        );
+        assert!(cache.len() <= 8 + 8 / 2, "{query}");
+    }
+    autocompleter.prune(&mut cache);
+    assert!(cache.len() <= 8);
+}
+
 // ideally this would use the #[bench] attribute but it's unstable
 #[ignore]
 #[test]